@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io;
+
+fuzz_target!(|data: &[u8]| {
+    // Serve the input as every external file too, so buffers and images are fuzzed along with the
+    // JSON and GLB parsers
+    let _ = wgpucube::gltf::parse(data, |_| Ok::<_, io::Error>(data.to_vec()));
+});
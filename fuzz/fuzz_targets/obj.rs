@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(source) = std::str::from_utf8(data) {
+        // Serve the input as every MTL file too, so the MTL parser is fuzzed along with the OBJ one
+        let _ = wgpucube::obj::parse(source, |_| {
+            Ok::<_, io::Error>(source.to_string())
+        });
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+use wgpucube::mesh::Normals;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = wgpucube::ply::read(Cursor::new(data), Normals::Smooth);
+});
@@ -0,0 +1,100 @@
+use crate::error::Error;
+use crate::options::Options;
+use std::fmt;
+use std::str::FromStr;
+#[cfg_attr(not(target_arch = "wasm32"), expect(unused_imports))]
+use tracing::warn;
+
+/// Selects one adapter from those enumerated by the instance
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdapterSelector {
+    /// Index into the list printed by `--list-adapters`
+    Index(usize),
+    /// Case insensitive substring of the adapter name
+    Name(String),
+}
+
+impl FromStr for AdapterSelector {
+    type Err = std::convert::Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value.parse::<usize>() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Name(value.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for AdapterSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{index}"),
+            Self::Name(name) => write!(f, "\"{name}\""),
+        }
+    }
+}
+
+impl AdapterSelector {
+    #[cfg_attr(target_arch = "wasm32", expect(dead_code))]
+    fn matches(&self, index: usize, info: &wgpu::AdapterInfo) -> bool {
+        match self {
+            Self::Index(selected) => *selected == index,
+            Self::Name(name) => info.name.to_lowercase().contains(&name.to_lowercase()),
+        }
+    }
+}
+
+/// Enumerate all adapters for `backends` in a stable order
+///
+/// The order matches the indices accepted by `AdapterSelector::Index`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn enumerate_adapters(backends: wgpu::Backends) -> Vec<wgpu::Adapter> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    instance.enumerate_adapters(backends)
+}
+
+/// Request an adapter, honoring the adapter selector and fallback options
+///
+/// Without a selector this defers to `Instance::request_adapter`, which applies the power
+/// preference. With a selector, the adapters are enumerated and the first match is used. Adapter
+/// enumeration is not available on the web, so the selector is ignored there.
+pub(crate) async fn request_adapter(
+    instance: &wgpu::Instance,
+    options: &Options,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<wgpu::Adapter, Error> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(selector) = &options.adapter {
+        let adapter = instance
+            .enumerate_adapters(options.backends)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, adapter)| {
+                !options.force_fallback_adapter
+                    || adapter.get_info().device_type == wgpu::DeviceType::Cpu
+            })
+            .filter(|(_, adapter)| {
+                compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+            })
+            .find(|(index, adapter)| selector.matches(*index, &adapter.get_info()))
+            .map(|(_, adapter)| adapter);
+        return adapter.ok_or_else(|| Error::NoMatchingAdapter(selector.clone()));
+    }
+    #[cfg(target_arch = "wasm32")]
+    if let Some(selector) = &options.adapter {
+        warn!(
+            "Adapter selection is not supported on the web, ignoring {}",
+            selector
+        );
+    }
+
+    let request_adapter_options = wgpu::RequestAdapterOptions {
+        power_preference: options.power_preference,
+        force_fallback_adapter: options.force_fallback_adapter,
+        compatible_surface,
+    };
+    Ok(instance.request_adapter(&request_adapter_options).await?)
+}
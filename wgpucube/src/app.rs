@@ -1,31 +1,62 @@
+use crate::adapter;
+use crate::attachment::AttachmentTexture;
+use crate::clock::AnimationClock;
+use crate::color::SurfaceFormats;
 use crate::cube::Cube;
+use crate::depth;
+use crate::error::Error;
+use crate::lifecycle::{Command, Event, Lifecycle, State, is_zero_size};
+use crate::msaa;
+use crate::options::Options;
+use crate::shadertoy::ShadertoyMouse;
 
 #[cfg(feature = "egui")]
 use crate::egui::EguiInterface;
 use std::sync::Arc;
-#[cfg_attr(not(target_arch = "wasm32"), expect(unused_imports))]
+use std::sync::atomic::{AtomicBool, Ordering};
 use tracing::{debug, error, info, warn};
 use winit::application::ApplicationHandler;
-use winit::event::WindowEvent;
+use winit::event::{ElementState, KeyEvent, MouseButton, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoopProxy};
+use winit::keyboard::{Key, NamedKey};
 use winit::window::{Window, WindowId};
 
 #[derive(Debug)]
-pub(crate) struct Context {
+pub struct Context {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
     size: winit::dpi::PhysicalSize<u32>,
-    surface: wgpu::Surface<'static>,
-    surface_format: wgpu::TextureFormat,
+    /// `None` while the app is suspended and the native window does not exist
+    surface: Option<wgpu::Surface<'static>>,
+    surface_formats: SurfaceFormats,
+    present_mode: wgpu::PresentMode,
+    desired_maximum_frame_latency: u32,
+    msaa: Option<AttachmentTexture>,
+    depth: AttachmentTexture,
+    clock: AnimationClock,
     cube: Cube,
+    /// Frames rendered and mouse state, passed to Shadertoy shaders
+    frame: u64,
+    mouse: ShadertoyMouse,
+    /// Set by the device lost callback, which can't send events since it may run on any thread
+    device_lost: Arc<AtomicBool>,
     #[cfg(feature = "egui")]
     egui: EguiInterface,
 }
 
 impl Context {
-    async fn new(window: Arc<Window>) -> Self {
-        let instance_descriptor = wgpu::InstanceDescriptor::default();
-        let instance = wgpu::Instance::new(&instance_descriptor);
+    /// Create the GPU context for `window`
+    ///
+    /// `clock` is passed in so that the animation continues where it was when the context is
+    /// rebuilt after the device was lost.
+    async fn new(
+        window: Arc<Window>,
+        options: Options,
+        clock: AnimationClock,
+    ) -> Result<Self, Error> {
+        let instance = wgpu::Instance::new(&options.instance_descriptor());
 
         // Note: Surface creation can only occur after the .resume() call from winit as Android does
         //       not allow surface creation prior to Resume. However, on WebGL backends the surface
@@ -34,71 +65,220 @@ impl Context {
         //       is refactored so that instance creation occurs before winit calls .resume() then
         //       additional logic would be required to create the surface at the appropriate point
         //       for different platforms.
-        let surface = instance.create_surface(Arc::clone(&window)).unwrap();
+        let surface = instance.create_surface(Arc::clone(&window))?;
 
-        let request_adapter_options = wgpu::RequestAdapterOptions::default();
-        let adapter = instance
-            .request_adapter(&request_adapter_options)
-            .await
-            .unwrap();
-        info!("Using adapter: {:?}", adapter.get_info().backend);
-        let device_descriptor = wgpu::DeviceDescriptor::default();
-        let (device, queue) = adapter.request_device(&device_descriptor).await.unwrap();
+        let adapter = adapter::request_adapter(&instance, &options, Some(&surface)).await?;
+        let adapter_info = adapter.get_info();
+        info!(
+            "Using adapter: {} ({:?})",
+            adapter_info.name, adapter_info.backend
+        );
+        let depth_format = depth::supported_format(&adapter, options.depth_format);
+        let device_descriptor = wgpu::DeviceDescriptor {
+            required_features: msaa::required_features(&adapter) | depth_format.required_features(),
+            ..Default::default()
+        };
+        let (device, queue) = adapter.request_device(&device_descriptor).await?;
+
+        // The default handler panics. Errors are logged instead so that a lost device, which
+        // causes errors in every call until the context is rebuilt, doesn't abort the app.
+        device.on_uncaptured_error(Arc::new(|error| {
+            error!("Uncaptured wgpu error: {}", error);
+        }));
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let device_lost_flag = Arc::clone(&device_lost);
+        device.set_device_lost_callback(move |reason, message| {
+            // Destroyed is only reported after an explicit Device::destroy()
+            if reason == wgpu::DeviceLostReason::Destroyed {
+                return;
+            }
+            error!("Device lost: {}", message);
+            device_lost_flag.store(true, Ordering::Release);
+        });
 
         // Note: window.inner_size() is only valid after instance.request_adapter() on web
         let size = window.inner_size();
         debug!("Window size: {:?}", size);
 
+        // The window may be minimized or collapsed to zero size when the context is created.
+        // Textures can't have a zero size, so they are created at 1x1 and resized along with the
+        // surface once the window has a real size.
+        let render_size = winit::dpi::PhysicalSize::new(size.width.max(1), size.height.max(1));
+
         let surface_capabilities = surface.get_capabilities(&adapter);
-        let surface_format = surface_capabilities.formats[0];
+        let surface_formats = SurfaceFormats::select(
+            &surface_capabilities.formats,
+            adapter.get_downlevel_capabilities().flags,
+        )
+        .ok_or(Error::IncompatibleSurface)?;
+        debug!("Surface formats: {:?}", surface_formats);
+        let present_mode = if surface_capabilities
+            .present_modes
+            .contains(&options.present_mode)
+            || matches!(
+                options.present_mode,
+                wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+            ) {
+            options.present_mode
+        } else {
+            warn!(
+                "Present mode {:?} is not supported, using AutoVsync",
+                options.present_mode
+            );
+            wgpu::PresentMode::AutoVsync
+        };
 
-        // TODO: Send size to Cube::new to set initial aspect ratio properly
-        let cube = Cube::new(surface_format, &device, &window.inner_size());
+        // All rendering happens through views in the (usually sRGB) view format
+        let view_format = surface_formats.view;
+
+        let sample_count = msaa::supported_sample_count(
+            &adapter,
+            &[view_format, depth_format],
+            options.sample_count,
+        );
+        let msaa = (sample_count > 1).then(|| {
+            AttachmentTexture::new(
+                &device,
+                "MSAA Color Texture",
+                view_format,
+                sample_count,
+                render_size,
+            )
+        });
+        let depth = AttachmentTexture::new(
+            &device,
+            "Depth Texture",
+            depth_format,
+            sample_count,
+            render_size,
+        );
+
+        let cube = Cube::new(
+            &options,
+            view_format,
+            depth_format,
+            sample_count,
+            &device,
+            &queue,
+            &render_size,
+        );
 
         #[cfg(feature = "egui")]
-        let egui = EguiInterface::new(&device, &window, surface_format);
+        let egui = EguiInterface::new(&device, &window, view_format);
 
         let context = Self {
+            instance,
+            adapter,
             device,
             queue,
             size,
-            surface,
-            surface_format,
+            surface: Some(surface),
+            surface_formats,
+            present_mode,
+            desired_maximum_frame_latency: options.desired_maximum_frame_latency,
+            msaa,
+            depth,
+            clock,
             cube,
+            frame: 0,
+            mouse: ShadertoyMouse::default(),
+            device_lost,
             #[cfg(feature = "egui")]
             egui,
         };
-        context.configure_surface();
+        if !is_zero_size(size) {
+            context.configure_surface();
+        }
 
-        context
+        Ok(context)
     }
 
     fn configure_surface(&self) {
+        // While suspended the surface is configured when it is recreated instead
+        let Some(surface) = &self.surface else {
+            return;
+        };
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: self.surface_format,
+            format: self.surface_formats.surface,
             width: self.size.width,
             height: self.size.height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            desired_maximum_frame_latency: 2,
+            present_mode: self.present_mode,
+            desired_maximum_frame_latency: self.desired_maximum_frame_latency,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            // TODO: Investigate sRGB surfaces ( surface_format.add_srgb_suffix() )
-            view_formats: vec![self.surface_format],
+            view_formats: self.surface_formats.view_formats(),
         };
-        self.surface.configure(&self.device, &surface_config);
+        surface.configure(&self.device, &surface_config);
     }
 
+    /// Drop the surface because the native window is about to be destroyed
+    ///
+    /// The device, queue, and all other GPU resources are kept so that resuming only has to
+    /// recreate the surface.
+    fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    /// Recreate the surface for a new native window after `suspend`
+    ///
+    /// The surface is configured by the following call to `resize`.
+    fn resume(&mut self, window: Arc<Window>) -> Result<(), Error> {
+        let surface = self.instance.create_surface(window)?;
+        // The render pipelines were created for the original surface's view format
+        let surface_capabilities = surface.get_capabilities(&self.adapter);
+        if !surface_capabilities
+            .formats
+            .contains(&self.surface_formats.surface)
+        {
+            return Err(Error::SurfaceFormatChanged(self.surface_formats.surface));
+        }
+        self.surface = Some(surface);
+        Ok(())
+    }
+
+    /// Resize the surface and render targets. `new_size` must not be zero.
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        debug_assert!(!is_zero_size(new_size));
         self.size = new_size;
         self.configure_surface();
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(&self.device, new_size);
+        }
+        self.depth.resize(&self.device, new_size);
     }
 
+    /// Render and present one frame
+    ///
+    /// Recoverable surface errors are handled here and the frame is skipped. Only errors which
+    /// leave the app unable to continue rendering are returned.
     #[cfg_attr(not(feature = "egui"), expect(unused_variables))]
-    fn render(&mut self, window: &Arc<Window>) {
-        let surface_texture = self.surface.get_current_texture().unwrap();
+    fn render(&mut self, window: &Arc<Window>) -> Result<(), wgpu::SurfaceError> {
+        let Some(surface) = &self.surface else {
+            return Ok(());
+        };
+        let surface_texture = match surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(error @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                // The compositor invalidated the swapchain, for example after a mode change or
+                // when the window moved to another display. Reconfiguring recreates it and the
+                // next redraw will render normally.
+                info!("Reconfiguring surface: {}", error);
+                self.configure_surface();
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                // This happens when the window is hidden or minimized on some platforms
+                warn!("Timed out acquiring surface texture, skipping frame");
+                return Ok(());
+            }
+            Err(wgpu::SurfaceError::Other) => {
+                warn!("Failed to acquire surface texture, skipping frame");
+                return Ok(());
+            }
+            Err(error @ wgpu::SurfaceError::OutOfMemory) => return Err(error),
+        };
         let texture_view_descriptor = wgpu::TextureViewDescriptor {
-            // TODO: Investigate sRGB surfaces ( surface_format.add_srgb_suffix() )
-            format: Some(self.surface_format),
+            format: Some(self.surface_formats.view),
             ..Default::default()
         };
         let texture_view = surface_texture
@@ -110,10 +290,27 @@ impl Context {
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
 
-        // Draw cube and update cube uniform buffers
-        self.cube.render(&texture_view, &self.queue, &mut encoder);
+        // Draw cube and update cube uniform buffers. With MSAA enabled the cube is drawn into the
+        // multisampled texture and resolved into the surface texture.
+        self.cube.set_time(self.clock.tick());
+        self.cube.set_frame(self.frame);
+        self.cube.set_mouse(self.mouse);
+        let (color_view, resolve_target) = match &self.msaa {
+            Some(msaa) => (msaa.view(), Some(&texture_view)),
+            None => (&texture_view, None),
+        };
+        self.cube.render(
+            color_view,
+            resolve_target,
+            self.depth.view(),
+            &self.queue,
+            &mut encoder,
+        );
+        self.frame += 1;
+        self.mouse.frame_rendered();
 
-        // Draw UI
+        // Draw UI. The egui pass loads the resolved surface texture, so it always renders single
+        // sampled regardless of the cube's MSAA setting.
         #[cfg(feature = "egui")]
         self.egui.render(
             window,
@@ -125,130 +322,200 @@ impl Context {
 
         // Submit all draw calls
         self.queue.submit(Some(encoder.finish()));
+        let suboptimal = surface_texture.suboptimal;
         surface_texture.present();
-    }
-}
 
-#[derive(Default)]
-#[allow(clippy::large_enum_variant)]
-enum State {
-    #[default]
-    Uninitialized,
-    Initializing,
-    Resumed {
-        window: Arc<Window>,
-        context: Context,
-    },
+        // The frame was still presented, but the surface should be reconfigured to match the
+        // window's current properties for the best performance
+        if suboptimal {
+            debug!("Surface is suboptimal, reconfiguring");
+            self.configure_surface();
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
-pub(crate) enum WgpuEvent {
-    #[cfg_attr(not(target_arch = "wasm32"), expect(unused))]
+pub enum WgpuEvent {
     Initialized {
         window: Arc<Window>,
         context: Context,
     },
 }
 
-pub(crate) struct App {
-    #[cfg_attr(not(target_arch = "wasm32"), expect(unused))]
+pub struct App {
+    /// Only used on the web, where the context is created asynchronously
+    #[cfg_attr(not(target_arch = "wasm32"), expect(dead_code))]
     event_loop_proxy: EventLoopProxy<WgpuEvent>,
-    state: State,
-    #[cfg(target_os = "ios")]
-    request_redraw: bool,
-    #[cfg(target_arch = "wasm32")]
-    pending_resize: Option<winit::dpi::PhysicalSize<u32>>,
+    options: Options,
+    lifecycle: Lifecycle<Arc<Window>, Context>,
+    /// Initialization failed and the event loop was stopped
+    error: Option<Error>,
 }
 
 impl App {
-    pub(crate) fn new(event_loop_proxy: EventLoopProxy<WgpuEvent>) -> Self {
+    pub fn new(event_loop_proxy: EventLoopProxy<WgpuEvent>, options: Options) -> Self {
         Self {
             event_loop_proxy,
-            state: State::Uninitialized,
-            #[cfg(target_os = "ios")]
-            request_redraw: false,
-            #[cfg(target_arch = "wasm32")]
-            pending_resize: None,
+            options,
+            lifecycle: Lifecycle::new(cfg!(target_os = "ios")),
+            error: None,
         }
     }
-}
 
-impl ApplicationHandler<WgpuEvent> for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        // TODO: Be tolerant of multiple calls to .resumed()
-        // Winit documentation states that .resumed() can be called multiple times on some
-        // platforms. This should be refactored to be tolerant of multiple calls. Currently only
-        // wasm platforms utilize State::Initializing so other platforms will only need to handle
-        // the case where .resumed() is called when State::Resumed is already true. Need to
-        // determine appropriate behavior in this case.
-        match self.state {
-            State::Uninitialized => self.state = State::Initializing,
-            State::Initializing => panic!("Call to .resumed() but state is State::Initializing"),
-            State::Resumed { .. } => panic!("Call to .resumed() but state is State::Resumed"),
+    /// The error which stopped the app, if initialization failed
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Pass an event to the lifecycle and carry out the resulting commands
+    fn handle(&mut self, event_loop: &ActiveEventLoop, event: Event<Arc<Window>, Context>) {
+        for command in self.lifecycle.handle(event) {
+            self.execute(event_loop, command);
+            if event_loop.exiting() {
+                break;
+            }
+        }
+    }
+
+    fn execute(&mut self, event_loop: &ActiveEventLoop, command: Command<Arc<Window>, Context>) {
+        match command {
+            Command::CreateContext => {
+                let window = self.create_window(event_loop);
+                let clock = self.options.animation_clock();
+                self.create_context(event_loop, window, clock);
+            }
+            Command::RebuildContext { window, context } => {
+                // The cube, depth and MSAA textures, and egui renderer are all recreated on a new
+                // device. The animation clock and options are carried over so the app continues
+                // where it was.
+                info!("Rebuilding GPU context");
+                let clock = context.clock.clone();
+                drop(context);
+                self.create_context(event_loop, window, clock);
+            }
+            Command::CreateSurface => {
+                if let State::Resumed { window, context } = self.lifecycle.state_mut()
+                    && let Err(error) = context.resume(Arc::clone(window))
+                {
+                    if let Error::SurfaceFormatChanged(_) = error {
+                        warn!("{}, rebuilding GPU context", error);
+                        self.handle(event_loop, Event::SurfaceFormatChanged);
+                    } else {
+                        self.fail(event_loop, error);
+                    }
+                }
+            }
+            Command::DropSurface => {
+                if let State::Suspended { context, .. } = self.lifecycle.state_mut() {
+                    context.suspend();
+                }
+            }
+            Command::Resize(new_size) => {
+                if let State::Resumed { context, .. } = self.lifecycle.state_mut() {
+                    // TODO: If cube stays in context then should context call cube.resize?
+                    context.cube.resize(new_size, &context.queue);
+                    context.resize(new_size);
+                }
+            }
+            Command::Render => {
+                if let State::Resumed { window, context } = self.lifecycle.state_mut() {
+                    // TODO: Is this correct order for pre_present_notify and render?
+                    window.pre_present_notify();
+                    if let Err(error) = context.render(window) {
+                        self.fail(event_loop, Error::Surface(error));
+                    }
+                }
+            }
+            Command::RequestRedraw => {
+                if let State::Resumed { window, .. } = self.lifecycle.state() {
+                    window.request_redraw();
+                }
+            }
+            Command::Exit => event_loop.exit(),
         }
+    }
 
+    fn create_window(&self, event_loop: &ActiveEventLoop) -> Arc<Window> {
         let mut window_attributes = Window::default_attributes();
         window_attributes = window_attributes.with_title("wgpucube");
+        if let Some(size) = self.options.window_size {
+            window_attributes = window_attributes.with_inner_size(size);
+        }
+        if self.options.fullscreen {
+            window_attributes = window_attributes
+                .with_fullscreen(Some(winit::window::Fullscreen::Borderless(None)));
+        }
 
         #[cfg(target_arch = "wasm32")]
         {
-            use wasm_bindgen::JsCast;
             use winit::platform::web::WindowAttributesExtWebSys;
-
-            let canvas = web_sys::window()
-                .unwrap()
-                .document()
-                .unwrap()
-                .get_element_by_id("wgpucube-canvas")
-                .unwrap()
-                .dyn_into::<web_sys::HtmlCanvasElement>()
-                .unwrap();
-            window_attributes = window_attributes.with_canvas(Some(canvas));
+            window_attributes = window_attributes.with_canvas(Some(crate::web::canvas()));
         }
 
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        Arc::new(event_loop.create_window(window_attributes).unwrap())
+    }
 
+    /// Start creating the context for `window`
+    ///
+    /// On the web the context is created asynchronously and the lifecycle is notified when the
+    /// `WgpuEvent::Initialized` event arrives. If creation fails there, the canvas is replaced
+    /// with an error message since the page can't exit.
+    #[cfg_attr(target_arch = "wasm32", expect(unused_variables))]
+    fn create_context(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window: Arc<Window>,
+        clock: AnimationClock,
+    ) {
+        let options = self.options.clone();
         cfg_if::cfg_if! {
             if #[cfg(target_arch = "wasm32")] {
                 let event_loop_proxy = self.event_loop_proxy.clone();
                 wasm_bindgen_futures::spawn_local(async move {
-                    let context = Context::new(Arc::clone(&window)).await;
-                    event_loop_proxy.send_event(WgpuEvent::Initialized { window, context }).unwrap();
+                    let context = Context::new(Arc::clone(&window), options, clock).await;
+                    match context {
+                        Ok(context) => event_loop_proxy
+                            .send_event(WgpuEvent::Initialized { window, context })
+                            .unwrap(),
+                        Err(error) => {
+                            error!("{}", error);
+                            crate::web::show_error(&error);
+                        }
+                    }
                 });
             } else {
-                let context = pollster::block_on(Context::new(Arc::clone(&window)));
-                self.state = State::Resumed {
-                    window: Arc::clone(&window),
-                    context,
-                };
-                window.request_redraw();
+                let context = pollster::block_on(Context::new(Arc::clone(&window), options, clock));
+                match context {
+                    Ok(context) => self.handle(event_loop, Event::Initialized { window, context }),
+                    Err(error) => self.fail(event_loop, error),
+                }
             }
         }
     }
 
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: WgpuEvent) {
-        match event {
-            #[cfg_attr(not(target_arch = "wasm32"), expect(unused_mut))]
-            WgpuEvent::Initialized {
-                mut context,
-                window,
-            } => {
-                // TODO: Is it safe to assume state will be initializing?
-                assert!(matches!(self.state, State::Initializing));
+    /// Stop the event loop because of an unrecoverable error, which is reported by `error`
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: Error) {
+        self.error = Some(error);
+        event_loop.exit();
+    }
+}
 
-                #[cfg(target_arch = "wasm32")]
-                if let Some(new_size) = self.pending_resize.take() {
-                    context.cube.resize(new_size, &context.queue);
-                    context.resize(new_size);
-                }
+impl ApplicationHandler<WgpuEvent> for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.handle(event_loop, Event::Resumed);
+    }
 
-                self.state = State::Resumed {
-                    context,
-                    window: Arc::clone(&window),
-                };
-                window.request_redraw();
-            }
-        }
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: WgpuEvent) {
+        let event = match event {
+            WgpuEvent::Initialized { window, context } => Event::Initialized { window, context },
+        };
+        self.handle(event_loop, event);
+    }
+
+    fn suspended(&mut self, event_loop: &ActiveEventLoop) {
+        self.handle(event_loop, Event::Suspended);
     }
 
     fn window_event(
@@ -257,75 +524,84 @@ impl ApplicationHandler<WgpuEvent> for App {
         _window_id: WindowId,
         event: WindowEvent,
     ) {
-        match &mut self.state {
-            State::Uninitialized => {
-                error!("Received WindowEvent in State::Uninitialized: {:?}", event);
+        // Let egui-winit handle events first
+        // TODO: Use EventResponse return info from egui-winit
+        #[cfg(feature = "egui")]
+        if let State::Resumed { window, context } = self.lifecycle.state_mut() {
+            context.egui.handle_input(window, &event);
+        }
+
+        let event = match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Space),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if let State::Resumed { context, .. } = self.lifecycle.state_mut() {
+                    context.clock.toggle_pause();
+                    debug!("Animation paused: {}", context.clock.is_paused());
+                }
+                return;
             }
-            State::Initializing => {
-                #[cfg(target_arch = "wasm32")]
-                match event {
-                    WindowEvent::Resized(new_size) => {
-                        // The wgpu initialization may not be complete when winit sends the resize
-                        // event. Store the most recent resize event so it can be used when wgpu
-                        // initialization is complete.
-                        self.pending_resize = Some(new_size);
-                    }
-                    WindowEvent::CloseRequested => {
-                        event_loop.exit();
-                    }
-                    _ => {
-                        warn!("Dropped event during initialization: {:?}", event);
-                    }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Character(character),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } if character.eq_ignore_ascii_case("s") => {
+                // The mode is also kept in the options so a rebuilt context continues using it
+                if let State::Resumed { context, .. } = self.lifecycle.state_mut() {
+                    let shading = context.cube.shading().next();
+                    context.cube.set_shading(shading);
+                    self.options.shading = shading;
+                    info!("Shading: {}", shading.name());
                 }
+                return;
             }
-            State::Resumed { window, context } => {
-                // Let egui-winit handle events first
-                // TODO: Use EventResponse return info from egui-winit
-                #[cfg(feature = "egui")]
-                context.egui.handle_input(window, &event);
-
-                match event {
-                    WindowEvent::Resized(new_size) => {
-                        // TODO: If cube stays in context then should context call cube.resize?
-                        context.cube.resize(new_size, &context.queue);
-                        context.resize(new_size)
-                        // Winit will automatically provide a RedrawRequested event after this event
-                    }
-                    WindowEvent::CloseRequested => {
-                        // TODO: Could drop resources here for cleanup
-                        event_loop.exit();
-                    }
-                    WindowEvent::RedrawRequested => {
-                        // TODO: Is this correct order for pre_present_notify and render?
-                        window.pre_present_notify();
-                        context.render(window);
-                        // Calling window.request_redraw() during a WindowEvent::RedrawRequested
-                        // does not work properly on iOS. As a workaround, the request_redraw flag
-                        // is set. The about_to_wait() method checks this flag and calls
-                        // window.request_redraw(), which appears to work.
-                        //
-                        // Issue: https://github.com/rust-windowing/winit/issues/3406
-                        cfg_if::cfg_if! {
-                            if #[cfg(target_os = "ios")] {
-                                self.request_redraw = true;
-                            } else {
-                                window.request_redraw();
-                            }
-                        }
-                    }
-                    _ => {}
+            WindowEvent::CursorMoved { position, .. } => {
+                if let State::Resumed { context, .. } = self.lifecycle.state_mut() {
+                    let height = context.size.height;
+                    context
+                        .mouse
+                        .cursor_moved(position.x as f32, position.y as f32, height);
                 }
+                return;
             }
-        }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                if let State::Resumed { context, .. } = self.lifecycle.state_mut() {
+                    context.mouse.button(state.is_pressed());
+                }
+                return;
+            }
+            WindowEvent::Resized(new_size) => Event::Resized(new_size),
+            WindowEvent::Occluded(occluded) => Event::Occluded(occluded),
+            WindowEvent::CloseRequested => Event::CloseRequested,
+            WindowEvent::RedrawRequested => Event::RedrawRequested,
+            _ => return,
+        };
+        self.handle(event_loop, event);
     }
 
-    #[cfg(target_os = "ios")]
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if let State::Resumed { window, .. } = &self.state {
-            // See the comment above about request_redraw workaround on iOS
-            if std::mem::take(&mut self.request_redraw) {
-                window.request_redraw();
-            }
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let State::Resumed { context, .. } | State::Suspended { context, .. } =
+            self.lifecycle.state()
+            && context.device_lost.swap(false, Ordering::Acquire)
+        {
+            self.handle(event_loop, Event::DeviceLost);
         }
+        self.handle(event_loop, Event::AboutToWait);
     }
 }
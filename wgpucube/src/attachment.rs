@@ -0,0 +1,71 @@
+use winit::dpi::PhysicalSize;
+
+/// Texture which is only used as a render attachment, such as a depth buffer or a multisampled
+/// color target
+///
+/// Attachments must always be the same size as the render target they are used with, so owners
+/// are expected to call `resize` whenever the surface or offscreen target is resized.
+#[derive(Debug)]
+pub(crate) struct AttachmentTexture {
+    label: &'static str,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl AttachmentTexture {
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let (texture, view) = Self::create_texture(device, label, format, sample_count, size);
+        Self {
+            label,
+            format,
+            sample_count,
+            texture,
+            view,
+        }
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        label: &str,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        size: PhysicalSize<u32>,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
+        if self.texture.width() == size.width && self.texture.height() == size.height {
+            return;
+        }
+        (self.texture, self.view) =
+            Self::create_texture(device, self.label, self.format, self.sample_count, size);
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+}
@@ -0,0 +1,566 @@
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
+use glam::Vec3;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::warn;
+use wgpucube::Options;
+use wgpucube::adapter::AdapterSelector;
+use wgpucube::light::{self, Light, LightKind, Lighting};
+use wgpucube::mesh::Normals;
+use wgpucube::primitive::Primitive;
+use wgpucube::scene::Scene;
+use wgpucube::shadertoy::ShadertoyTarget;
+use wgpucube::shading::Shading;
+use wgpucube::texture::SamplerOptions;
+use wgpucube::yuv::{YuvFormat, YuvMatrix, YuvRange};
+use winit::dpi::PhysicalSize;
+
+pub(crate) fn command() -> Command {
+    Command::new("wgpucube")
+        .about("WebGPU cross platform demo using Rust, winit, and wgpu")
+        .arg(
+            Arg::new("size")
+                .long("size")
+                .value_parser(parse_size)
+                .help("Window size, or headless render target size, as WIDTHxHEIGHT in pixels"),
+        )
+        .arg(
+            Arg::new("fullscreen")
+                .long("fullscreen")
+                .action(ArgAction::SetTrue)
+                .help("Start in borderless fullscreen mode"),
+        )
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .value_parser(
+                    PossibleValuesParser::new(["vulkan", "gl", "dx12", "metal"])
+                        .map(|backend| parse_backend(&backend)),
+                )
+                .help("Graphics backend to use [default: any available]"),
+        )
+        .arg(
+            Arg::new("adapter")
+                .long("adapter")
+                .value_parser(value_parser!(AdapterSelector))
+                .help("Adapter to use, by index from --list-adapters or by name substring"),
+        )
+        .arg(
+            Arg::new("force-fallback-adapter")
+                .long("force-fallback-adapter")
+                .action(ArgAction::SetTrue)
+                .help("Only use a software (CPU) fallback adapter"),
+        )
+        .arg(
+            Arg::new("list-adapters")
+                .long("list-adapters")
+                .action(ArgAction::SetTrue)
+                .help("Print the available adapters with their features and limits, then exit"),
+        )
+        .arg(
+            Arg::new("power-preference")
+                .long("power-preference")
+                .value_parser(
+                    PossibleValuesParser::new(["none", "low", "high"])
+                        .map(|power| parse_power_preference(&power)),
+                )
+                .default_value("none")
+                .help("Adapter power preference"),
+        )
+        .arg(
+            Arg::new("present-mode")
+                .long("present-mode")
+                .value_parser(
+                    PossibleValuesParser::new([
+                        "auto-vsync",
+                        "auto-no-vsync",
+                        "fifo",
+                        "fifo-relaxed",
+                        "immediate",
+                        "mailbox",
+                    ])
+                    .map(|mode| parse_present_mode(&mode)),
+                )
+                .default_value("auto-vsync")
+                .help("Surface presentation mode"),
+        )
+        .arg(
+            Arg::new("frame-latency")
+                .long("frame-latency")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("2")
+                .help("Desired maximum number of frames queued for presentation"),
+        )
+        .arg(
+            Arg::new("samples")
+                .long("samples")
+                .value_parser(
+                    PossibleValuesParser::new(["1", "2", "4", "8"])
+                        .map(|samples| parse_samples(&samples)),
+                )
+                .default_value("1")
+                .help(
+                    "Number of MSAA samples per pixel, reduced if the adapter doesn't support it",
+                ),
+        )
+        .arg(
+            Arg::new("depth-format")
+                .long("depth-format")
+                .value_parser(
+                    PossibleValuesParser::new([
+                        "depth16unorm",
+                        "depth24plus",
+                        "depth24plus-stencil8",
+                        "depth32float",
+                        "depth32float-stencil8",
+                    ])
+                    .map(|format| parse_depth_format(&format)),
+                )
+                .default_value("depth32float")
+                .help("Depth buffer format, replaced by depth32float if the adapter lacks support"),
+        )
+        .arg(
+            Arg::new("speed")
+                .long("speed")
+                .value_parser(parse_speed)
+                .default_value("1.0")
+                .help("Animation speed relative to real time"),
+        )
+        .arg(
+            Arg::new("fixed-step")
+                .long("fixed-step")
+                .action(ArgAction::SetTrue)
+                .help("Advance the animation by a fixed 1/60 s per frame instead of by real time"),
+        )
+        .arg(
+            Arg::new("model")
+                .long("model")
+                .value_parser(value_parser!(PathBuf))
+                .help("Wavefront OBJ, glTF, STL, or PLY model to render instead of the cube"),
+        )
+        .arg(
+            Arg::new("primitive")
+                .long("primitive")
+                .value_name("SHAPE[:KEY=VALUE,...]")
+                .value_parser(value_parser!(Primitive))
+                .conflicts_with("model")
+                .help(format!(
+                    "Generated shape to render instead of the cube: {}. For example \
+                     sphere:segments=64,rings=32 or torus:radius=0.2",
+                    Primitive::NAMES.join(", ")
+                )),
+        )
+        .arg(
+            Arg::new("texture")
+                .long("texture")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .conflicts_with_all(["model", "primitive"])
+                .help(
+                    "PNG or JPEG image for the cube's faces. Give one image for every face, or six \
+                     for the front, back, right, left, top, and bottom faces.",
+                ),
+        )
+        .arg(
+            Arg::new("textured")
+                .long("textured")
+                .action(ArgAction::SetTrue)
+                .conflicts_with_all(["model", "primitive", "texture"])
+                .help("Render the cube with the built-in texture instead of vertex colors"),
+        )
+        .arg(
+            Arg::new("yuv")
+                .long("yuv")
+                .value_parser(
+                    PossibleValuesParser::new(["nv12", "i420"])
+                        .map(|format| parse_yuv_format(&format)),
+                )
+                .conflicts_with_all(["model", "primitive", "texture", "textured"])
+                .help(
+                    "Texture the cube with a YUV frame in this format, uploaded as separate luma and \
+                     chroma planes and converted to RGB on the GPU. The frame is a color bar test \
+                     pattern unless --yuv-file is given.",
+                ),
+        )
+        .arg(
+            Arg::new("yuv-file")
+                .long("yuv-file")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .requires_all(["yuv", "yuv-size"])
+                .help("Raw YUV file whose first frame textures the cube"),
+        )
+        .arg(
+            Arg::new("yuv-size")
+                .long("yuv-size")
+                .value_parser(parse_size)
+                .requires("yuv")
+                .help(
+                    "Size of the YUV frame as WIDTHxHEIGHT in pixels. Required with --yuv-file, \
+                     and 256x256 for the test pattern by default.",
+                ),
+        )
+        .arg(
+            Arg::new("yuv-matrix")
+                .long("yuv-matrix")
+                .value_parser(
+                    PossibleValuesParser::new(["bt601", "bt709"])
+                        .map(|matrix| parse_yuv_matrix(&matrix)),
+                )
+                .default_value("bt601")
+                .help("Matrix converting YUV to RGB"),
+        )
+        .arg(
+            Arg::new("yuv-range")
+                .long("yuv-range")
+                .value_parser(
+                    PossibleValuesParser::new(["full", "limited"])
+                        .map(|range| parse_yuv_range(&range)),
+                )
+                .default_value("limited")
+                .help("Range of YUV code values from black to white"),
+        )
+        .arg(
+            Arg::new("shadertoy")
+                .long("shadertoy")
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with_all(["model", "primitive", "texture", "textured", "yuv"])
+                .help(
+                    "Shadertoy fragment shader defining mainImage, in GLSL (.glsl, .frag, or .fs) or \
+                     WGSL (.wgsl), to render on the cube's faces",
+                ),
+        )
+        .arg(
+            Arg::new("shadertoy-target")
+                .long("shadertoy-target")
+                .value_parser(
+                    PossibleValuesParser::new(["cube", "fullscreen"])
+                        .map(|target| parse_shadertoy_target(&target)),
+                )
+                .default_value("cube")
+                .help("Render the Shadertoy shader on the cube's faces or over the whole window"),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .value_parser(
+                    PossibleValuesParser::new(["nearest", "linear"])
+                        .map(|filter| parse_filter(&filter)),
+                )
+                .default_value("linear")
+                .help("Texture magnification and minification filter"),
+        )
+        .arg(
+            Arg::new("mipmap-filter")
+                .long("mipmap-filter")
+                .value_parser(
+                    PossibleValuesParser::new(["nearest", "linear"])
+                        .map(|filter| parse_filter(&filter)),
+                )
+                .default_value("linear")
+                .help("Filter between texture mipmap levels"),
+        )
+        .arg(
+            Arg::new("address-mode")
+                .long("address-mode")
+                .value_parser(
+                    PossibleValuesParser::new(["repeat", "mirror-repeat", "clamp-to-edge"])
+                        .map(|address_mode| parse_address_mode(&address_mode)),
+                )
+                .default_value("repeat")
+                .help("How texture coordinates outside the texture are handled"),
+        )
+        .arg(
+            Arg::new("anisotropy")
+                .long("anisotropy")
+                .value_parser(value_parser!(u16).range(1..=16))
+                .default_value("1")
+                .help("Maximum anisotropic filtering ratio, used when both filters are linear"),
+        )
+        .arg(
+            Arg::new("normals")
+                .long("normals")
+                .value_parser(
+                    PossibleValuesParser::new(["smooth", "flat"])
+                        .map(|normals| parse_normals(&normals)),
+                )
+                .default_value("smooth")
+                .help("Normals to generate for STL and PLY models"),
+        )
+        .arg(
+            Arg::new("shading")
+                .long("shading")
+                .value_parser(
+                    PossibleValuesParser::new(Shading::ALL.map(Shading::name))
+                        .map(|shading| parse_shading(&shading)),
+                )
+                .default_value("gouraud")
+                .help("Shading mode. Press S while running to cycle through the modes."),
+        )
+        .arg(
+            Arg::new("light")
+                .long("light")
+                .value_name("KIND[:KEY=VALUE,...]")
+                .value_parser(value_parser!(Light))
+                .action(ArgAction::Append)
+                .help(format!(
+                    "Light source, which may be repeated to replace the default point light: {}. \
+                     For example point:position=0/4/4,color=1/0.5/0.2,attenuation=1/0/0.05",
+                    LightKind::NAMES.join(", ")
+                )),
+        )
+        .arg(
+            Arg::new("ambient")
+                .long("ambient")
+                .value_name("R/G/B")
+                .value_parser(parse_ambient)
+                .default_value("0")
+                .help("Ambient light color, or a single value for white light"),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_parser(value_parser!(tracing::Level))
+                .default_value("info")
+                .help("Maximum log level (error, warn, info, debug, trace)"),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(ArgAction::SetTrue)
+                .help("Render a single frame offscreen and write it to a PNG file"),
+        )
+        .arg(
+            Arg::new("frame")
+                .long("frame")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Animation step to render in headless mode"),
+        )
+        .arg(
+            Arg::new("out")
+                .long("out")
+                .value_parser(value_parser!(PathBuf))
+                .default_value("wgpucube.png")
+                .help("Output PNG path for headless mode"),
+        )
+}
+
+pub(crate) fn options(matches: &ArgMatches) -> Options {
+    Options {
+        window_size: matches.get_one::<PhysicalSize<u32>>("size").copied(),
+        fullscreen: matches.get_flag("fullscreen"),
+        backends: matches
+            .get_one::<wgpu::Backends>("backend")
+            .copied()
+            .unwrap_or(wgpu::Backends::all()),
+        power_preference: *matches
+            .get_one::<wgpu::PowerPreference>("power-preference")
+            .unwrap(),
+        adapter: matches.get_one::<AdapterSelector>("adapter").cloned(),
+        force_fallback_adapter: matches.get_flag("force-fallback-adapter"),
+        present_mode: *matches
+            .get_one::<wgpu::PresentMode>("present-mode")
+            .unwrap(),
+        desired_maximum_frame_latency: *matches.get_one::<u32>("frame-latency").unwrap(),
+        sample_count: *matches.get_one::<u32>("samples").unwrap(),
+        depth_format: *matches
+            .get_one::<wgpu::TextureFormat>("depth-format")
+            .unwrap(),
+        time_scale: *matches.get_one::<f64>("speed").unwrap(),
+        fixed_step: matches.get_flag("fixed-step"),
+        // Models and textures are loaded by main, which reports load errors
+        scene: matches
+            .get_one::<Primitive>("primitive")
+            .map(|primitive| Arc::new(Scene::from(primitive.mesh()))),
+        shading: *matches.get_one::<Shading>("shading").unwrap(),
+        lighting: lighting(matches),
+        sampler: SamplerOptions {
+            filter: *matches.get_one::<wgpu::FilterMode>("filter").unwrap(),
+            mipmap_filter: *matches
+                .get_one::<wgpu::FilterMode>("mipmap-filter")
+                .unwrap(),
+            address_mode: *matches
+                .get_one::<wgpu::AddressMode>("address-mode")
+                .unwrap(),
+            anisotropy: *matches.get_one::<u16>("anisotropy").unwrap(),
+        },
+        // Shaders are also loaded by main
+        shadertoy: None,
+        shadertoy_target: *matches
+            .get_one::<ShadertoyTarget>("shadertoy-target")
+            .unwrap(),
+    }
+}
+
+fn lighting(matches: &ArgMatches) -> Lighting {
+    let mut lighting = Lighting {
+        ambient: *matches.get_one::<Vec3>("ambient").unwrap(),
+        ..Lighting::default()
+    };
+    if let Some(lights) = matches.get_many::<Light>("light") {
+        lighting.lights = lights.copied().collect();
+        if lighting.lights.len() > Lighting::MAX_LIGHTS {
+            warn!(
+                "Only the first {} of {} lights are used",
+                Lighting::MAX_LIGHTS,
+                lighting.lights.len()
+            );
+        }
+    }
+    lighting
+}
+
+fn parse_size(value: &str) -> Result<PhysicalSize<u32>, String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, got '{value}'"))?;
+    let width = width
+        .parse::<u32>()
+        .map_err(|error| format!("invalid width '{width}': {error}"))?;
+    let height = height
+        .parse::<u32>()
+        .map_err(|error| format!("invalid height '{height}': {error}"))?;
+    if width == 0 || height == 0 {
+        return Err("width and height must be non-zero".to_string());
+    }
+    Ok(PhysicalSize::new(width, height))
+}
+
+fn parse_ambient(value: &str) -> Result<Vec3, String> {
+    let ambient = light::parse_vec3(value)
+        .ok_or_else(|| format!("expected R/G/B or a single value, got '{value}'"))?;
+    if !(ambient.is_finite() && ambient.min_element() >= 0.0) {
+        return Err("ambient light components must be non-negative".to_string());
+    }
+    Ok(ambient)
+}
+
+fn parse_speed(value: &str) -> Result<f64, String> {
+    let speed = value
+        .parse::<f64>()
+        .map_err(|error| format!("invalid speed '{value}': {error}"))?;
+    if !speed.is_finite() || speed < 0.0 {
+        return Err("speed must be a non-negative number".to_string());
+    }
+    Ok(speed)
+}
+
+// The following parsers are only called with values accepted by their PossibleValuesParser
+
+fn parse_backend(value: &str) -> wgpu::Backends {
+    match value {
+        "vulkan" => wgpu::Backends::VULKAN,
+        "gl" => wgpu::Backends::GL,
+        "dx12" => wgpu::Backends::DX12,
+        "metal" => wgpu::Backends::METAL,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_power_preference(value: &str) -> wgpu::PowerPreference {
+    match value {
+        "none" => wgpu::PowerPreference::None,
+        "low" => wgpu::PowerPreference::LowPower,
+        "high" => wgpu::PowerPreference::HighPerformance,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_present_mode(value: &str) -> wgpu::PresentMode {
+    match value {
+        "auto-vsync" => wgpu::PresentMode::AutoVsync,
+        "auto-no-vsync" => wgpu::PresentMode::AutoNoVsync,
+        "fifo" => wgpu::PresentMode::Fifo,
+        "fifo-relaxed" => wgpu::PresentMode::FifoRelaxed,
+        "immediate" => wgpu::PresentMode::Immediate,
+        "mailbox" => wgpu::PresentMode::Mailbox,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_samples(value: &str) -> u32 {
+    value.parse().unwrap()
+}
+
+fn parse_depth_format(value: &str) -> wgpu::TextureFormat {
+    match value {
+        "depth16unorm" => wgpu::TextureFormat::Depth16Unorm,
+        "depth24plus" => wgpu::TextureFormat::Depth24Plus,
+        "depth24plus-stencil8" => wgpu::TextureFormat::Depth24PlusStencil8,
+        "depth32float" => wgpu::TextureFormat::Depth32Float,
+        "depth32float-stencil8" => wgpu::TextureFormat::Depth32FloatStencil8,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_normals(value: &str) -> Normals {
+    match value {
+        "smooth" => Normals::Smooth,
+        "flat" => Normals::Flat,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_filter(value: &str) -> wgpu::FilterMode {
+    match value {
+        "nearest" => wgpu::FilterMode::Nearest,
+        "linear" => wgpu::FilterMode::Linear,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_address_mode(value: &str) -> wgpu::AddressMode {
+    match value {
+        "repeat" => wgpu::AddressMode::Repeat,
+        "mirror-repeat" => wgpu::AddressMode::MirrorRepeat,
+        "clamp-to-edge" => wgpu::AddressMode::ClampToEdge,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_shading(value: &str) -> Shading {
+    match value {
+        "flat" => Shading::Flat,
+        "gouraud" => Shading::Gouraud,
+        "lambert" => Shading::Lambert,
+        "blinn-phong" => Shading::BlinnPhong,
+        "unlit" => Shading::Unlit,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_yuv_format(value: &str) -> YuvFormat {
+    match value {
+        "nv12" => YuvFormat::Nv12,
+        "i420" => YuvFormat::I420,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_yuv_matrix(value: &str) -> YuvMatrix {
+    match value {
+        "bt601" => YuvMatrix::Bt601,
+        "bt709" => YuvMatrix::Bt709,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_yuv_range(value: &str) -> YuvRange {
+    match value {
+        "full" => YuvRange::Full,
+        "limited" => YuvRange::Limited,
+        _ => unreachable!(),
+    }
+}
+
+fn parse_shadertoy_target(value: &str) -> ShadertoyTarget {
+    match value {
+        "cube" => ShadertoyTarget::Cube,
+        "fullscreen" => ShadertoyTarget::Fullscreen,
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,154 @@
+//! Animation clock
+//!
+//! The cube's rotation is driven by elapsed animation time rather than by a frame counter, so it
+//! spins at the same speed regardless of the display refresh rate. The clock can be paused and
+//! scaled, and can run in a fixed-step mode which advances by the same amount every frame for
+//! deterministic output in tests and recordings.
+
+use std::time::Duration;
+use web_time::Instant;
+
+/// Frame rate assumed by kmscube, which advances its animation by a fixed amount every frame
+pub const REFERENCE_FRAME_RATE: f64 = 60.0;
+
+/// Longest real time gap which is applied to the animation in a single tick. Longer gaps, such as
+/// when the window was hidden or the process was stopped in a debugger, are clamped so the
+/// animation resumes smoothly instead of jumping ahead.
+const MAX_FRAME_DELTA: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    /// Advance by the real time elapsed between ticks
+    RealTime,
+    /// Advance by the same duration on every tick, regardless of real time
+    FixedStep(Duration),
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClock {
+    mode: ClockMode,
+    scale: f64,
+    paused: bool,
+    elapsed: Duration,
+    last_tick: Option<Instant>,
+}
+
+impl AnimationClock {
+    pub fn new(mode: ClockMode) -> Self {
+        Self {
+            mode,
+            scale: 1.0,
+            paused: false,
+            elapsed: Duration::ZERO,
+            last_tick: None,
+        }
+    }
+
+    /// Animation time of frame `frame` when running at the reference frame rate
+    pub fn frame_time(frame: u64) -> Duration {
+        Duration::from_secs_f64(frame as f64 / REFERENCE_FRAME_RATE)
+    }
+
+    /// Advance the clock to the current frame and return the animation time to render
+    ///
+    /// The first tick after creation or `resume` does not advance the clock.
+    pub fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        let delta = match (self.mode, self.last_tick) {
+            (_, None) => Duration::ZERO,
+            (ClockMode::RealTime, Some(last_tick)) => (now - last_tick).min(MAX_FRAME_DELTA),
+            (ClockMode::FixedStep(step), Some(_)) => step,
+        };
+        self.last_tick = Some(now);
+        if !self.paused {
+            self.elapsed += delta.mul_f64(self.scale);
+        }
+        self.elapsed
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        // Don't count the time spent paused when the next tick happens
+        self.last_tick = None;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Set the rate at which animation time advances relative to real time (or to the fixed step)
+    pub fn set_scale(&mut self, scale: f64) {
+        assert!(
+            scale.is_finite() && scale >= 0.0,
+            "Invalid time scale {scale}"
+        );
+        self.scale = scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn fixed_step_advances_by_step() {
+        let mut clock = AnimationClock::new(ClockMode::FixedStep(STEP));
+        assert_eq!(clock.tick(), Duration::ZERO);
+        assert_eq!(clock.tick(), STEP);
+        assert_eq!(clock.tick(), STEP * 2);
+    }
+
+    #[test]
+    fn paused_clock_does_not_advance() {
+        let mut clock = AnimationClock::new(ClockMode::FixedStep(STEP));
+        clock.tick();
+        clock.tick();
+        clock.toggle_pause();
+        assert!(clock.is_paused());
+        assert_eq!(clock.tick(), STEP);
+        assert_eq!(clock.tick(), STEP);
+
+        // The first tick after resuming only restarts the clock
+        clock.toggle_pause();
+        assert!(!clock.is_paused());
+        assert_eq!(clock.tick(), STEP);
+        assert_eq!(clock.tick(), STEP * 2);
+    }
+
+    #[test]
+    fn scale_multiplies_step() {
+        let mut clock = AnimationClock::new(ClockMode::FixedStep(STEP));
+        clock.tick();
+        clock.set_scale(2.5);
+        assert_eq!(clock.tick(), STEP.mul_f64(2.5));
+        clock.set_scale(0.0);
+        assert_eq!(clock.tick(), STEP.mul_f64(2.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid time scale")]
+    fn negative_scale_panics() {
+        AnimationClock::new(ClockMode::RealTime).set_scale(-1.0);
+    }
+
+    #[test]
+    fn real_time_gaps_are_clamped() {
+        let mut clock = AnimationClock::new(ClockMode::RealTime);
+        clock.last_tick = Some(Instant::now() - Duration::from_secs(10));
+        assert_eq!(clock.tick(), MAX_FRAME_DELTA);
+    }
+}
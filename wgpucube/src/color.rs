@@ -0,0 +1,107 @@
+use tracing::warn;
+
+/// Convert an sRGB encoded color component in the range 0.0..=1.0 to linear
+///
+/// Colors authored by hand or read from images are sRGB encoded, but lighting and blending must be
+/// done in linear space. Writing to an sRGB render target converts back to sRGB automatically.
+pub(crate) fn srgb_to_linear(component: f32) -> f32 {
+    if component <= 0.04045 {
+        component / 12.92
+    } else {
+        ((component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Formats used to configure a surface and to create views of its textures
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SurfaceFormats {
+    /// Format of the surface textures
+    pub surface: wgpu::TextureFormat,
+    /// Format of the views rendered into. This is an sRGB format whenever the surface offers one or
+    /// supports an sRGB view of its first format, so that the shaders can output linear colors.
+    /// Otherwise it is the surface's first format, which may be a non-sRGB format that makes the
+    /// output too dark.
+    pub view: wgpu::TextureFormat,
+}
+
+impl SurfaceFormats {
+    /// Choose the surface and view formats from the formats supported by a surface
+    ///
+    /// Backends differ in which formats they list first. Vulkan and Metal usually offer an sRGB
+    /// format directly, while WebGPU canvases only offer non-sRGB formats and require an sRGB view
+    /// format instead. The policy in order of preference is:
+    ///
+    /// 1. An sRGB surface format, used directly
+    /// 2. A non-sRGB surface format with its sRGB equivalent as a view format, if the adapter
+    ///    supports surface view formats
+    /// 3. The first surface format, used directly
+    ///
+    /// Returns `None` if there are no formats, which means the adapter can't present to the
+    /// surface.
+    pub fn select(
+        formats: &[wgpu::TextureFormat],
+        downlevel_flags: wgpu::DownlevelFlags,
+    ) -> Option<Self> {
+        if let Some(format) = formats.iter().find(|format| format.is_srgb()) {
+            return Some(Self {
+                surface: *format,
+                view: *format,
+            });
+        }
+
+        let format = *formats.first()?;
+        let srgb_format = format.add_srgb_suffix();
+        if srgb_format != format
+            && downlevel_flags.contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS)
+        {
+            return Some(Self {
+                surface: format,
+                view: srgb_format,
+            });
+        }
+
+        if srgb_format != format {
+            warn!(
+                "No sRGB surface format available, colors will be too dark (using {:?})",
+                format
+            );
+        }
+        Some(Self {
+            surface: format,
+            view: format,
+        })
+    }
+
+    /// Additional formats which views of the surface textures may use
+    pub fn view_formats(&self) -> Vec<wgpu::TextureFormat> {
+        if self.view == self.surface {
+            vec![]
+        } else {
+            vec![self.view]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::{DownlevelFlags, TextureFormat};
+
+    #[test]
+    fn selects_srgb_formats() {
+        let formats = [TextureFormat::Bgra8Unorm, TextureFormat::Bgra8UnormSrgb];
+        let selected = SurfaceFormats::select(&formats, DownlevelFlags::empty()).unwrap();
+        assert_eq!(selected.surface, TextureFormat::Bgra8UnormSrgb);
+        assert_eq!(selected.view, TextureFormat::Bgra8UnormSrgb);
+
+        let formats = [TextureFormat::Rgba8Unorm];
+        let selected =
+            SurfaceFormats::select(&formats, DownlevelFlags::SURFACE_VIEW_FORMATS).unwrap();
+        assert_eq!(selected.surface, TextureFormat::Rgba8Unorm);
+        assert_eq!(selected.view_formats(), [TextureFormat::Rgba8UnormSrgb]);
+        let selected = SurfaceFormats::select(&formats, DownlevelFlags::empty()).unwrap();
+        assert_eq!(selected.view, TextureFormat::Rgba8Unorm);
+
+        assert!(SurfaceFormats::select(&[], DownlevelFlags::all()).is_none());
+    }
+}
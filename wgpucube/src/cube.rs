@@ -1,116 +1,27 @@
+use crate::color::srgb_to_linear;
+use crate::light::{LightKind, Lighting};
+use crate::mesh::Mesh;
+use crate::options::Options;
+use crate::scene::{Material, Scene, Texture};
+use crate::shadertoy::{ShadertoyMouse, ShadertoyRenderer};
+use crate::shading::Shading;
+use crate::texture::{SamplerOptions, TextureUploader};
 use glam::{Mat4, Vec3};
+use std::sync::Arc;
+use std::time::Duration;
 use wgpu::TextureFormat;
 use wgpu::util::DeviceExt;
 use winit::dpi::PhysicalSize;
 
-const CUBE_VERTICES: [[f32; 3]; 24] = [
-    // Front
-    [-1.0, -1.0, 1.0],
-    [1.0, -1.0, 1.0],
-    [-1.0, 1.0, 1.0],
-    [1.0, 1.0, 1.0],
-    // Back
-    [1.0, -1.0, -1.0],
-    [-1.0, -1.0, -1.0],
-    [1.0, 1.0, -1.0],
-    [-1.0, 1.0, -1.0],
-    // Right
-    [1.0, -1.0, 1.0],
-    [1.0, -1.0, -1.0],
-    [1.0, 1.0, 1.0],
-    [1.0, 1.0, -1.0],
-    // Left
-    [-1.0, -1.0, -1.0],
-    [-1.0, -1.0, 1.0],
-    [-1.0, 1.0, -1.0],
-    [-1.0, 1.0, 1.0],
-    // Top
-    [-1.0, 1.0, 1.0],
-    [1.0, 1.0, 1.0],
-    [-1.0, 1.0, -1.0],
-    [1.0, 1.0, -1.0],
-    // Bottom
-    [-1.0, -1.0, -1.0],
-    [1.0, -1.0, -1.0],
-    [-1.0, -1.0, 1.0],
-    [1.0, -1.0, 1.0],
-];
-
-const CUBE_COLORS: [[f32; 3]; 24] = [
-    // Front
-    [0.0, 0.0, 1.0], // blue
-    [1.0, 0.0, 1.0], // magenta
-    [0.0, 1.0, 1.0], // cyan
-    [1.0, 1.0, 1.0], // white
-    // Back
-    [1.0, 0.0, 0.0], // red
-    [0.0, 0.0, 0.0], // black
-    [1.0, 1.0, 0.0], // yellow
-    [0.0, 1.0, 0.0], // green
-    // Right
-    [1.0, 0.0, 1.0], // magenta
-    [1.0, 0.0, 0.0], // red
-    [1.0, 1.0, 1.0], // white
-    [1.0, 1.0, 0.0], // yellow
-    // Left
-    [0.0, 0.0, 0.0], // black
-    [0.0, 0.0, 1.0], // blue
-    [0.0, 1.0, 0.0], // green
-    [0.0, 1.0, 1.0], // cyan
-    // Top
-    [0.0, 1.0, 1.0], // cyan
-    [1.0, 1.0, 1.0], // white
-    [0.0, 1.0, 0.0], // green
-    [1.0, 1.0, 0.0], // yellow
-    // Bottom
-    [0.0, 0.0, 0.0], // black
-    [1.0, 0.0, 0.0], // red
-    [0.0, 0.0, 1.0], // blue
-    [1.0, 0.0, 1.0], // magenta
-];
-
-const CUBE_NORMALS: [[f32; 3]; 24] = [
-    // Front
-    [0.0, 0.0, 1.0], // forward
-    [0.0, 0.0, 1.0], // forward
-    [0.0, 0.0, 1.0], // forward
-    [0.0, 0.0, 1.0], // forward
-    // Back
-    [0.0, 0.0, -1.0], // backward
-    [0.0, 0.0, -1.0], // backward
-    [0.0, 0.0, -1.0], // backward
-    [0.0, 0.0, -1.0], // backward
-    // Right
-    [1.0, 0.0, 0.0], // right
-    [1.0, 0.0, 0.0], // right
-    [1.0, 0.0, 0.0], // right
-    [1.0, 0.0, 0.0], // right
-    // Left
-    [-1.0, 0.0, 0.0], // left
-    [-1.0, 0.0, 0.0], // left
-    [-1.0, 0.0, 0.0], // left
-    [-1.0, 0.0, 0.0], // left
-    // Top
-    [0.0, 1.0, 0.0], // up
-    [0.0, 1.0, 0.0], // up
-    [0.0, 1.0, 0.0], // up
-    [0.0, 1.0, 0.0], // up
-    // Bottom
-    [0.0, -1.0, 0.0], // down
-    [0.0, -1.0, 0.0], // down
-    [0.0, -1.0, 0.0], // down
-    [0.0, -1.0, 0.0], // down
-];
-
-const CUBE_INDICES: [u16; 36] = [
-    // Each face is composed of 2 triangles, therefore there are 6 indices per face
-    0, 1, 2, 1, 3, 2, // Front
-    4, 5, 6, 5, 7, 6, // Back
-    8, 9, 10, 9, 11, 10, // Right
-    12, 13, 14, 13, 15, 14, // Left
-    16, 17, 18, 17, 19, 18, // Top
-    20, 21, 22, 21, 23, 22, // Bottom
-];
+// Initial rotation in degrees about the X, Y, and Z axes
+const INITIAL_ROTATION: [f64; 3] = [45.0, 45.0, 10.0];
+
+// Rotation rate in degrees per second about the X, Y, and Z axes. kmscube advances 0.25, -0.5, and
+// 0.15 degrees per frame, which are converted here assuming its 60 Hz reference frame rate.
+const ROTATION_RATE: [f64; 3] = [15.0, -30.0, 9.0];
+
+// Background color, sRGB encoded like the vertex colors. This matches kmscube's glClearColor.
+const CLEAR_COLOR: [f32; 3] = [0.5, 0.5, 0.5];
 
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Debug, Clone, Copy)]
@@ -118,13 +29,16 @@ struct Vertex {
     position: [f32; 3],
     color: [f32; 3],
     normal: [f32; 3],
+    // Zero for meshes without texture coordinates
+    uv: [f32; 2],
 }
 
 impl Vertex {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
         0 => Float32x3,
         1 => Float32x3,
         2 => Float32x3,
+        3 => Float32x2,
     ];
 
     fn buffer_layout() -> wgpu::VertexBufferLayout<'static> {
@@ -147,170 +61,525 @@ struct Uniforms {
     normal: [[f32; 4]; 4],
 }
 
-fn create_vertices<const N: usize>(
-    vertices: &[[f32; 3]; N],
-    colors: &[[f32; 3]; N],
-    normals: &[[f32; 3]; N],
-) -> Vec<Vertex> {
-    (0..N)
-        .map(|i| Vertex {
-            position: vertices[i],
-            color: colors[i],
-            normal: normals[i],
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialUniforms {
+    base_color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniforms {
+    position: [f32; 3],
+    kind: u32,
+    /// Normalized direction the light shines in
+    direction: [f32; 3],
+    /// Cosines of a spot light's inner and outer cone angles
+    cos_inner: f32,
+    /// Color multiplied by intensity
+    color: [f32; 3],
+    cos_outer: f32,
+    attenuation: [f32; 3],
+    _padding: u32,
+}
+
+impl LightUniforms {
+    // Light kinds, matching the constants in `cube.wgsl`
+    const DIRECTIONAL: u32 = 0;
+    const POINT: u32 = 1;
+    const SPOT: u32 = 2;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniforms {
+    ambient: [f32; 3],
+    light_count: u32,
+    lights: [LightUniforms; Lighting::MAX_LIGHTS],
+}
+
+impl From<&Lighting> for LightingUniforms {
+    fn from(lighting: &Lighting) -> Self {
+        let mut lights = [LightUniforms::default(); Lighting::MAX_LIGHTS];
+        let count = lighting.lights.len().min(Lighting::MAX_LIGHTS);
+        for (uniforms, light) in lights.iter_mut().zip(&lighting.lights) {
+            let (kind, position, direction, cos_inner, cos_outer) = match light.kind {
+                LightKind::Directional { direction } => {
+                    (LightUniforms::DIRECTIONAL, Vec3::ZERO, direction, 0.0, 0.0)
+                }
+                LightKind::Point { position } => {
+                    (LightUniforms::POINT, position, Vec3::ZERO, 0.0, 0.0)
+                }
+                LightKind::Spot {
+                    position,
+                    direction,
+                    inner_angle,
+                    outer_angle,
+                } => (
+                    LightUniforms::SPOT,
+                    position,
+                    direction,
+                    inner_angle.to_radians().cos(),
+                    outer_angle.to_radians().cos(),
+                ),
+            };
+            let attenuation = light.attenuation;
+            *uniforms = LightUniforms {
+                position: position.to_array(),
+                kind,
+                direction: direction.normalize_or_zero().to_array(),
+                cos_inner,
+                color: (light.color * light.intensity).to_array(),
+                cos_outer,
+                attenuation: [
+                    attenuation.constant,
+                    attenuation.linear,
+                    attenuation.quadratic,
+                ],
+                _padding: 0,
+            };
+        }
+        Self {
+            ambient: lighting.ambient.to_array(),
+            light_count: count as u32,
+            lights,
+        }
+    }
+}
+
+fn create_vertices(mesh: &Mesh) -> Vec<Vertex> {
+    let uvs = mesh
+        .uvs()
+        .map_or_else(|| vec![[0.0; 2]; mesh.vertex_count()], <[_]>::to_vec);
+    mesh.positions()
+        .iter()
+        .zip(mesh.colors())
+        .zip(mesh.normals())
+        .zip(uvs)
+        .map(|(((&position, &color), &normal), uv)| Vertex {
+            position,
+            color,
+            normal,
+            uv,
         })
         .collect()
 }
 
+/// Vertex and index buffers of one of the scene's meshes
 #[derive(Debug)]
-pub(crate) struct Cube {
+struct GpuMesh {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    index_count: u32,
+}
+
+impl GpuMesh {
+    /// Returns `None` for meshes without triangles, since empty buffers can't be bound
+    fn new(device: &wgpu::Device, mesh: &Mesh) -> Option<Self> {
+        if mesh.indices().is_empty() {
+            return None;
+        }
+        let vertices = create_vertices(mesh);
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cube Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cube Index Buffer"),
+            contents: mesh.indices().as_bytes(),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Some(Self {
+            vertex_buffer,
+            index_buffer,
+            index_format: mesh.indices().format(),
+            index_count: mesh.indices().len() as u32,
+        })
+    }
+}
+
+/// A mesh drawn with a material and model matrix
+#[derive(Debug)]
+struct GpuInstance {
+    mesh: usize,
+    material: usize,
+    transform: Mat4,
+    /// Mirroring transforms reverse the winding order of the mesh's triangles
+    mirrored: bool,
+}
+
+/// Renders a scene, rotating it like kmscube's cube
+#[derive(Debug)]
+pub(crate) struct Cube {
+    meshes: Vec<Option<GpuMesh>>,
+    material_bind_groups: Vec<wgpu::BindGroup>,
+    instances: Vec<GpuInstance>,
+    /// Per-instance `Uniforms`, each at a multiple of `uniform_stride`
     uniform_buffer: wgpu::Buffer,
-    pipeline: wgpu::RenderPipeline,
+    uniform_stride: u64,
+    lighting_buffer: wgpu::Buffer,
+    /// Pipelines for counter-clockwise and clockwise front faces, for each shading mode in the
+    /// order of `Shading::ALL`
+    pipelines: Vec<[wgpu::RenderPipeline; 2]>,
+    shading: Shading,
     bind_group: wgpu::BindGroup,
     aspect_ratio: f32,
-    step: u64,
+    time: Duration,
+    shadertoy: Option<ShadertoyRenderer>,
+    /// Shadertoy's `iFrame` and `iMouse`
+    frame: u64,
+    mouse: ShadertoyMouse,
 }
 
 impl Cube {
+    /// Create the renderer for the options' scene, with its shading, lighting, and sampler
     pub fn new(
+        options: &Options,
         texture_format: TextureFormat,
+        depth_format: TextureFormat,
+        sample_count: u32,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         size: &PhysicalSize<u32>,
     ) -> Self {
-        // Create vertex and index buffers
-        let vertices = create_vertices(&CUBE_VERTICES, &CUBE_COLORS, &CUBE_NORMALS);
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Cube Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Cube Index Buffer"),
-            contents: bytemuck::cast_slice(&CUBE_INDICES),
-            usage: wgpu::BufferUsages::INDEX,
+        // A Shadertoy shader applied to the cube replaces the scene with a cube textured by it
+        let shadertoy = options.shadertoy.as_ref().map(|shadertoy| {
+            ShadertoyRenderer::new(
+                shadertoy,
+                options.shadertoy_target,
+                texture_format,
+                sample_count,
+                *size,
+                device,
+            )
         });
+        let shadertoy_texture = shadertoy.as_ref().and_then(ShadertoyRenderer::texture);
+        let scene = match shadertoy_texture {
+            Some(_) => Arc::new(Scene::textured_cube(vec![
+                Texture::new(1, 1, vec![255; 4]).unwrap(),
+            ])),
+            None => options.scene(),
+        };
 
-        // Create uniform buffer
-        let step = 0;
-        let aspect_ratio = size.width as f32 / size.height as f32;
-        let uniforms = Self::uniforms(step, aspect_ratio);
-        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        // Create vertex and index buffers
+        let meshes = scene
+            .meshes()
+            .iter()
+            .map(|mesh| GpuMesh::new(device, mesh))
+            .collect();
+        let instances: Vec<_> = scene
+            .instances()
+            .iter()
+            .map(|instance| GpuInstance {
+                mesh: instance.mesh,
+                material: instance.material,
+                transform: instance.transform,
+                mirrored: instance.transform.determinant() < 0.0,
+            })
+            .collect();
+
+        // Create the uniform buffer, with one `Uniforms` per instance at aligned offsets
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let uniform_stride = (std::mem::size_of::<Uniforms>() as u64).next_multiple_of(alignment);
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Uniform Buffer"),
-            contents: bytemuck::cast_slice(&[uniforms]),
+            size: uniform_stride * instances.len().max(1) as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
+        let lighting_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cube Lighting Buffer"),
+            contents: bytemuck::bytes_of(&LightingUniforms::from(&Lighting::default())),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let time = Duration::ZERO;
+        let aspect_ratio = size.width as f32 / size.height as f32;
 
-        // Create render pipeline
+        // Create bind groups
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Cube Uniform Buffer Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<Uniforms>() as u64),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<Uniforms>() as u64
+                        ),
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
-        });
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Cube Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
+                // Gouraud shading lights vertices, and the other modes light fragments
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<LightingUniforms>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
         });
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Cube Uniform Buffer BindGroup"),
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: uniform_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &uniform_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(std::mem::size_of::<Uniforms>() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: lighting_buffer.as_entire_binding(),
+                },
+            ],
         });
-        let shader = device.create_shader_module(wgpu::include_wgsl!("cube_smooth.wgsl"));
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Cube Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &[Vertex::buffer_layout()],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: None, // Depth stencil is omitted for this simple example
-            multisample: wgpu::MultisampleState::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
-                targets: &[Some(texture_format.into())],
-            }),
-            multiview: None,
-            cache: None,
+        let material_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Cube Material Bind Group Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<
+                                MaterialUniforms,
+                            >()
+                                as u64),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let material_bind_groups = create_material_bind_groups(
+            &scene,
+            &options.sampler,
+            shadertoy_texture,
+            &material_bind_group_layout,
+            device,
+            queue,
+        );
+
+        // Create render pipelines
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cube Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout, &material_bind_group_layout],
+            push_constant_ranges: &[],
         });
+        // Every shading mode's pipelines are created up front so the mode can be switched
+        // between frames
+        let shader = device.create_shader_module(wgpu::include_wgsl!("cube.wgsl"));
+        let create_pipeline = |shading: Shading, front_face| {
+            let (vertex_entry_point, fragment_entry_point) = shading.entry_points();
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Cube Render Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(vertex_entry_point),
+                    compilation_options: Default::default(),
+                    buffers: &[Vertex::buffer_layout()],
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face,
+                    cull_mode: Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: depth_format,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fragment_entry_point),
+                    compilation_options: Default::default(),
+                    targets: &[Some(texture_format.into())],
+                }),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let pipelines = Shading::ALL
+            .iter()
+            .map(|&shading| {
+                [wgpu::FrontFace::Ccw, wgpu::FrontFace::Cw]
+                    .map(|front_face| create_pipeline(shading, front_face))
+            })
+            .collect();
 
-        Self {
-            vertex_buffer,
-            index_buffer,
+        let mut cube = Self {
+            meshes,
+            material_bind_groups,
+            instances,
             uniform_buffer,
-            pipeline,
+            uniform_stride,
+            lighting_buffer,
+            pipelines,
+            shading: Shading::default(),
             bind_group,
             aspect_ratio,
-            step,
-        }
+            time,
+            shadertoy,
+            frame: 0,
+            mouse: ShadertoyMouse::default(),
+        };
+        cube.set_shading(options.shading);
+        cube.set_lighting(&options.lighting, queue);
+        cube.write_uniforms(queue);
+        cube
     }
 
-    fn uniforms(step: u64, aspect_ratio: f32) -> Uniforms {
+    /// The scene's model view and projection matrices
+    fn view_projection(time: Duration, aspect_ratio: f32) -> (Mat4, Mat4) {
+        // Calculate rotation angles from the animation time. This is done in f64 and wrapped to a
+        // single revolution so the animation stays smooth when the app has been running for a
+        // long time.
+        let seconds = time.as_secs_f64();
+        let [x, y, z] = std::array::from_fn(|axis| {
+            ((INITIAL_ROTATION[axis] + ROTATION_RATE[axis] * seconds) % 360.0).to_radians() as f32
+        });
+
         // Calculate transformation matrices
         //
         // This attemps to replicate the behavior of kmscube's custom transformation code which
         // keeps the vertical FOV fixed.
         let model_view = Mat4::from_translation(Vec3::new(0.0, 0.0, -8.0))
-            * Mat4::from_rotation_x((45.0 + 0.25 * step as f32).to_radians())
-            * Mat4::from_rotation_y((45.0 - 0.5 * step as f32).to_radians())
-            * Mat4::from_rotation_z((10.0 + 0.15 * step as f32).to_radians());
+            * Mat4::from_rotation_x(x)
+            * Mat4::from_rotation_y(y)
+            * Mat4::from_rotation_z(z);
         let top = 2.8 * (1.0 / aspect_ratio);
         let near = 6.0;
         let far = 10.0;
         let fov_y = 2.0 * (top / near).atan(); // Equivalent vertical FOV
         let projection = Mat4::perspective_rh(fov_y, aspect_ratio, near, far);
-        let model_view_projection = projection * model_view;
-        let normal = model_view.inverse().transpose();
-
-        // Create uniform buffer
-        Uniforms {
-            model_view: model_view.to_cols_array_2d(),
-            model_view_projection: model_view_projection.to_cols_array_2d(),
-            normal: normal.to_cols_array_2d(),
+        (model_view, projection)
+    }
+
+    /// Write each instance's uniforms for the current time and aspect ratio
+    fn write_uniforms(&self, queue: &wgpu::Queue) {
+        let (scene_model_view, projection) = Self::view_projection(self.time, self.aspect_ratio);
+        let mut data = vec![0; self.uniform_buffer.size() as usize];
+        for (index, instance) in self.instances.iter().enumerate() {
+            let model_view = scene_model_view * instance.transform;
+            let model_view_projection = projection * model_view;
+            let normal = model_view.inverse().transpose();
+            let uniforms = Uniforms {
+                model_view: model_view.to_cols_array_2d(),
+                model_view_projection: model_view_projection.to_cols_array_2d(),
+                normal: normal.to_cols_array_2d(),
+            };
+            let offset = index * self.uniform_stride as usize;
+            data[offset..offset + std::mem::size_of::<Uniforms>()]
+                .copy_from_slice(bytemuck::bytes_of(&uniforms));
         }
+        queue.write_buffer(&self.uniform_buffer, 0, &data);
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>, queue: &wgpu::Queue) {
         self.aspect_ratio = new_size.width as f32 / new_size.height as f32;
-        let uniforms = Self::uniforms(self.step, self.aspect_ratio);
-        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        self.write_uniforms(queue);
+        if let Some(shadertoy) = &mut self.shadertoy {
+            shadertoy.resize(new_size);
+        }
+    }
+
+    /// Set the animation time used for the next call to `render`
+    pub fn set_time(&mut self, time: Duration) {
+        self.time = time;
+    }
+
+    /// Set the frame number a Shadertoy shader sees as `iFrame` in the next call to `render`
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    /// Set the mouse state a Shadertoy shader sees as `iMouse` in the next call to `render`
+    pub fn set_mouse(&mut self, mouse: ShadertoyMouse) {
+        self.mouse = mouse;
+    }
+
+    pub fn shading(&self) -> Shading {
+        self.shading
+    }
+
+    /// Set the shading mode used from the next call to `render`
+    pub fn set_shading(&mut self, shading: Shading) {
+        self.shading = shading;
+    }
+
+    /// Replace the scene's lights. This may be called every frame to animate them.
+    pub fn set_lighting(&mut self, lighting: &Lighting, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.lighting_buffer,
+            0,
+            bytemuck::bytes_of(&LightingUniforms::from(lighting)),
+        );
     }
 
     pub fn render(
         &mut self,
         view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        depth_view: &wgpu::TextureView,
         queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
     ) {
+        // A fullscreen Shadertoy shader is drawn instead of the scene, and one on the cube is drawn
+        // into its texture first
+        if let Some(shadertoy) = &self.shadertoy {
+            shadertoy.write_inputs(self.time, self.frame, &self.mouse, queue);
+            match shadertoy.texture() {
+                Some(texture) => shadertoy.render(texture, None, encoder),
+                None => {
+                    shadertoy.render(view, resolve_target, encoder);
+                    return;
+                }
+            }
+        }
+
         // Update uniform buffer to animate the cube
-        let uniforms = Self::uniforms(self.step, self.aspect_ratio);
-        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
-        self.step += 1;
+        self.write_uniforms(queue);
 
         // While not necessary for this example, this is a good place to note that the render pass
         // returned by `begin_render_pass` has a lifetime relationship with the `encoder`. This is
@@ -321,31 +590,117 @@ impl Cube {
         // creates the render pass in the same scope as the `encoder` then you will need to drop the
         // render pass before submitting the `encoder` to the queue. The easiest way to do this is
         // by nesting the render pass creation and calls into their own scoped block.
+        //
+        // When multisampling is enabled, `view` is the multisampled texture and `resolve_target` is
+        // the single sampled texture which receives the resolved result. The multisampled contents
+        // are not needed after the resolve, so they are discarded instead of stored.
+        let store = match resolve_target {
+            Some(_) => wgpu::StoreOp::Discard,
+            None => wgpu::StoreOp::Store,
+        };
 
+        let [r, g, b] = CLEAR_COLOR.map(|component| srgb_to_linear(component) as f64);
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Cube Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 view,
                 depth_slice: None,
-                resolve_target: None,
+                resolve_target,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.5,
-                        g: 0.5,
-                        b: 0.5,
-                        a: 1.0,
-                    }),
-                    store: wgpu::StoreOp::Store,
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a: 1.0 }),
+                    store,
                 },
             })],
-            depth_stencil_attachment: None,
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    // The depth buffer is not used after this pass
+                    store: wgpu::StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
             timestamp_writes: None,
             occlusion_query_set: None,
         });
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        render_pass.draw_indexed(0..36, 0, 0..1);
+        for (index, instance) in self.instances.iter().enumerate() {
+            let Some(mesh) = &self.meshes[instance.mesh] else {
+                continue;
+            };
+            let offset = (index as u64 * self.uniform_stride) as u32;
+            let pipeline = &self.pipelines[self.shading as usize][instance.mirrored as usize];
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[offset]);
+            render_pass.set_bind_group(1, &self.material_bind_groups[instance.material], &[]);
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), mesh.index_format);
+            render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+        }
     }
 }
+
+/// Create each material's bind group, with `texture_override` replacing every texture if given
+fn create_material_bind_groups(
+    scene: &Scene,
+    sampler: &SamplerOptions,
+    texture_override: Option<&wgpu::TextureView>,
+    layout: &wgpu::BindGroupLayout,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Vec<wgpu::BindGroup> {
+    // Materials without a texture sample a white texture, so one shader handles both
+    let uploader = TextureUploader::new(device);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Texture Upload Encoder"),
+    });
+    let white = Texture::new(1, 1, vec![255; 4]).unwrap();
+    let white = uploader
+        .upload(&white, device, queue, &mut encoder)
+        .unwrap();
+    let textures: Vec<_> = scene
+        .textures()
+        .iter()
+        .map(|texture| uploader.upload(texture, device, queue, &mut encoder))
+        .collect();
+    queue.submit(Some(encoder.finish()));
+    let sampler = sampler.create_sampler(device);
+
+    scene
+        .materials()
+        .iter()
+        .map(|material: &Material| {
+            let uniforms = MaterialUniforms {
+                base_color: material.base_color,
+            };
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Cube Material Buffer"),
+                contents: bytemuck::bytes_of(&uniforms),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let texture = texture_override.unwrap_or_else(|| {
+                material
+                    .base_color_texture
+                    .and_then(|texture| textures[texture].as_ref())
+                    .unwrap_or(&white)
+            });
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Cube Material Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(texture),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,177 @@
+struct Uniforms {
+    model_view: mat4x4<f32>,
+    model_view_projection: mat4x4<f32>,
+    normal: mat3x3<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+// Light kinds, matching `LightUniforms` in `cube.rs`
+const DIRECTIONAL = 0u;
+const POINT = 1u;
+const SPOT = 2u;
+
+// Matches `Lighting::MAX_LIGHTS`
+const MAX_LIGHTS = 8u;
+
+// Positions and directions are in view space
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    cos_inner: f32,
+    color: vec3<f32>,
+    cos_outer: f32,
+    attenuation: vec3<f32>,
+};
+
+struct Lighting {
+    ambient: vec3<f32>,
+    light_count: u32,
+    lights: array<Light, MAX_LIGHTS>,
+};
+
+@group(0) @binding(1)
+var<uniform> lighting: Lighting;
+
+struct Material {
+    base_color: vec4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> material: Material;
+@group(1) @binding(1)
+var base_color_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var base_color_sampler: sampler;
+
+// Blinn-Phong specular exponent and intensity
+const SHININESS = 32.0;
+const SPECULAR = 0.5;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    // View space position and normal for per fragment lighting
+    @location(2) position: vec3<f32>,
+    @location(3) normal: vec3<f32>,
+};
+
+// Transform a vertex to clip and view space, passing its color through unlit
+fn transform(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = uniforms.model_view_projection * vec4<f32>(in.position, 1.0);
+    let position4 = uniforms.model_view * vec4<f32>(in.position, 1.0);
+    out.position = position4.xyz / position4.w;
+    out.normal = uniforms.normal * in.normal;
+    out.color = in.color;
+    out.uv = in.uv;
+    return out;
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    return transform(in);
+}
+
+// Like `vs_main`, with the color lit per vertex
+@vertex
+fn vs_gouraud(in: VertexInput) -> VertexOutput {
+    var out = transform(in);
+
+    out.color = in.color * diffuse(out.position, out.normal);
+
+    return out;
+}
+
+// The material's color at the fragment, tinting the interpolated vertex color. Alpha is ignored
+// since every material is opaque.
+fn base_color(in: VertexOutput) -> vec3<f32> {
+    let texel = textureSample(base_color_texture, base_color_sampler, in.uv);
+    return in.color * (material.base_color.rgb * texel.rgb);
+}
+
+// Light arriving at a surface point from one light
+struct Incident {
+    // Normalized direction from the surface towards the light
+    direction: vec3<f32>,
+    radiance: vec3<f32>,
+};
+
+fn incident(light: Light, position: vec3<f32>) -> Incident {
+    if light.kind == DIRECTIONAL {
+        return Incident(-light.direction, light.color);
+    }
+    let offset = light.position - position;
+    let distance = length(offset);
+    let direction = normalize(offset);
+    let falloff = dot(light.attenuation, vec3<f32>(1.0, distance, distance * distance));
+    var radiance = light.color / falloff;
+    if light.kind == SPOT {
+        let cos_angle = dot(-direction, light.direction);
+        radiance *= smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+    }
+    return Incident(direction, radiance);
+}
+
+// Ambient light plus Lambert diffuse light from every light source
+fn diffuse(position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var total = lighting.ambient;
+    for (var i = 0u; i < min(lighting.light_count, MAX_LIGHTS); i++) {
+        let light = incident(lighting.lights[i], position);
+        total += max(0.0, dot(normal, light.direction)) * light.radiance;
+    }
+    return total;
+}
+
+@fragment
+fn fs_color(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(base_color(in), 1.0);
+}
+
+@fragment
+fn fs_flat(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The screen space derivatives of the view space position span the triangle's plane. The
+    // normal is flipped if needed to face the camera, which is at the origin.
+    var normal = normalize(cross(dpdx(in.position), dpdy(in.position)));
+    if dot(normal, in.position) > 0.0 {
+        normal = -normal;
+    }
+    return vec4<f32>(diffuse(in.position, normal) * base_color(in), 1.0);
+}
+
+@fragment
+fn fs_lambert(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    return vec4<f32>(diffuse(in.position, normal) * base_color(in), 1.0);
+}
+
+@fragment
+fn fs_blinn_phong(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    let view_dir = normalize(-in.position);
+    var diffuse_light = lighting.ambient;
+    var specular_light = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lighting.light_count, MAX_LIGHTS); i++) {
+        let light = incident(lighting.lights[i], in.position);
+        let diff = max(0.0, dot(normal, light.direction));
+        diffuse_light += diff * light.radiance;
+
+        // Surfaces facing away from the light get no highlight
+        if diff > 0.0 {
+            let half_dir = normalize(light.direction + view_dir);
+            let spec = SPECULAR * pow(max(0.0, dot(normal, half_dir)), SHININESS);
+            specular_light += spec * light.radiance;
+        }
+    }
+    return vec4<f32>(diffuse_light * base_color(in) + specular_light, 1.0);
+}
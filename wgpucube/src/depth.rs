@@ -0,0 +1,31 @@
+use tracing::warn;
+
+/// Depth32Float is supported on every backend, including WebGL2, without requesting features
+pub(crate) const DEFAULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Returns `requested` if the adapter can render to it as a depth buffer, or `DEFAULT_FORMAT`
+/// otherwise
+///
+/// Formats which need a device feature, such as `Depth32FloatStencil8`, are only accepted if the
+/// adapter supports the feature. The device must then be requested with
+/// `format.required_features()`.
+pub(crate) fn supported_format(
+    adapter: &wgpu::Adapter,
+    requested: wgpu::TextureFormat,
+) -> wgpu::TextureFormat {
+    let supported = requested.is_depth_stencil_format()
+        && adapter.features().contains(requested.required_features())
+        && adapter
+            .get_texture_format_features(requested)
+            .allowed_usages
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT);
+    if supported {
+        requested
+    } else {
+        warn!(
+            "Depth format {:?} is not supported, using {:?}",
+            requested, DEFAULT_FORMAT
+        );
+        DEFAULT_FORMAT
+    }
+}
@@ -0,0 +1,87 @@
+use crate::adapter::AdapterSelector;
+use std::fmt;
+
+/// Failure to create the GPU context, identifying the initialization stage which failed
+#[derive(Debug)]
+pub enum Error {
+    /// The window surface could not be created, usually because the graphics API is unavailable
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// No adapter supports the requested backends, options, and surface
+    RequestAdapter(wgpu::RequestAdapterError),
+    /// No adapter matches the adapter selector
+    NoMatchingAdapter(AdapterSelector),
+    /// The adapter could not create a device with the required features and limits
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The adapter doesn't support any format for presenting to the window surface
+    IncompatibleSurface,
+    /// The surface recreated on resume doesn't support the format the pipelines were created for
+    SurfaceFormatChanged(wgpu::TextureFormat),
+    /// The surface could not provide a frame to render, for example because it ran out of memory
+    Surface(wgpu::SurfaceError),
+    /// The offscreen render target is larger than the device's maximum texture dimension
+    TargetTooLarge {
+        size: winit::dpi::PhysicalSize<u32>,
+        max: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateSurface(error) => write!(f, "Unable to create a surface: {error}"),
+            Self::RequestAdapter(error) => {
+                write!(f, "Unable to request a graphics adapter: {error}")
+            }
+            Self::NoMatchingAdapter(selector) => write!(
+                f,
+                "No graphics adapter matches {selector}, use --list-adapters to show them"
+            ),
+            Self::RequestDevice(error) => write!(f, "Unable to create a graphics device: {error}"),
+            Self::IncompatibleSurface => {
+                write!(f, "The graphics adapter can't present to the window")
+            }
+            Self::SurfaceFormatChanged(format) => {
+                write!(f, "The recreated surface does not support {format:?}")
+            }
+            Self::Surface(error) => write!(f, "Unable to render: {error}"),
+            Self::TargetTooLarge { size, max } => write!(
+                f,
+                "Size {}x{} exceeds the graphics device's limit of {max} pixels per dimension",
+                size.width, size.height
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::CreateSurface(error) => Some(error),
+            Self::RequestAdapter(error) => Some(error),
+            Self::NoMatchingAdapter(_) => None,
+            Self::RequestDevice(error) => Some(error),
+            Self::Surface(error) => Some(error),
+            Self::IncompatibleSurface
+            | Self::SurfaceFormatChanged(_)
+            | Self::TargetTooLarge { .. } => None,
+        }
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(error: wgpu::CreateSurfaceError) -> Self {
+        Self::CreateSurface(error)
+    }
+}
+
+impl From<wgpu::RequestAdapterError> for Error {
+    fn from(error: wgpu::RequestAdapterError) -> Self {
+        Self::RequestAdapter(error)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(error: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(error)
+    }
+}
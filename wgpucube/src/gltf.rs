@@ -0,0 +1,1231 @@
+//! glTF 2.0 model loading
+//!
+//! Loads `.gltf` JSON files with external or data URI buffers, and binary `.glb` files. The
+//! default scene's node hierarchy is flattened into a `Scene` with one instance per placed mesh
+//! primitive, using each node's world transform as the instance's model matrix.
+//!
+//! Supported primitive attributes are `POSITION`, `NORMAL`, `TEXCOORD_0`, and `COLOR_0`, with
+//! triangle list, strip, and fan topologies. Materials contribute their base color factor and
+//! base color texture, which may be a PNG or JPEG image. Cameras, lights, skins, morph targets,
+//! animations, and other material properties are ignored, and files requiring extensions are
+//! rejected.
+
+use crate::mesh::{Indices, Mesh, MeshError, flat_mesh, smooth_normals};
+use crate::scene::{Instance, Material, Scene, SceneError, Texture, TextureError};
+use base64::Engine;
+use base64::engine::DecodePaddingMode;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use glam::{Mat4, Quat, Vec3};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use tracing::warn;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4e4f534a;
+const GLB_CHUNK_BIN: u32 = 0x004e4942;
+
+/// Standard base64 for data URIs, which some exporters write without padding
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Largest number of elements in an accessor without a buffer view, whose count isn't bounded by
+/// any data, so a corrupt count can't cause a huge allocation
+const MAX_ZERO_ELEMENTS: usize = 1 << 20;
+
+// Accessor component types
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+
+// Primitive topologies
+const TRIANGLES: usize = 4;
+const TRIANGLE_STRIP: usize = 5;
+const TRIANGLE_FAN: usize = 6;
+
+#[derive(Debug)]
+pub enum GltfError {
+    /// The model file or a file it references could not be read
+    Io(io::Error),
+    /// The JSON could not be parsed
+    Json(serde_json::Error),
+    /// The file is not valid glTF. The message starts with the JSON path of the invalid item.
+    Invalid(String),
+    /// The file uses a feature this loader doesn't support
+    Unsupported(String),
+    /// The default scene places no triangle meshes
+    NoMeshes,
+    /// A primitive forms an invalid mesh
+    Mesh {
+        mesh: usize,
+        primitive: usize,
+        error: MeshError,
+    },
+    /// The loaded items form an invalid scene
+    Scene(SceneError),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Json(error) => write!(f, "Invalid JSON: {error}"),
+            Self::Invalid(message) => write!(f, "Invalid glTF: {message}"),
+            Self::Unsupported(message) => write!(f, "Unsupported glTF: {message}"),
+            Self::NoMeshes => write!(f, "The scene has no triangle meshes"),
+            Self::Mesh {
+                mesh,
+                primitive,
+                error,
+            } => write!(f, "Invalid mesh {mesh} primitive {primitive}: {error}"),
+            Self::Scene(error) => write!(f, "Invalid scene: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Mesh { error, .. } => Some(error),
+            Self::Scene(error) => Some(error),
+            Self::Json(error) => Some(error),
+            Self::Invalid(_) | Self::Unsupported(_) | Self::NoMeshes => None,
+        }
+    }
+}
+
+impl From<io::Error> for GltfError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<SceneError> for GltfError {
+    fn from(error: SceneError) -> Self {
+        Self::Scene(error)
+    }
+}
+
+fn invalid(path: impl fmt::Display, message: impl fmt::Display) -> GltfError {
+    GltfError::Invalid(format!("{path}: {message}"))
+}
+
+/// Load a `.gltf` or `.glb` file and the files it references, which are resolved relative to its
+/// directory
+///
+/// The scene is centered and scaled to fit the built-in cube's bounds.
+pub fn load(path: impl AsRef<Path>) -> Result<Scene, GltfError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    parse(&bytes, |uri| std::fs::read(directory.join(uri)))
+}
+
+/// Parse a glTF JSON or GLB file, calling `read_file` with the percent-decoded relative URI of
+/// each external buffer and image
+///
+/// The scene is centered and scaled to fit the built-in cube's bounds.
+pub fn parse(
+    bytes: &[u8],
+    mut read_file: impl FnMut(&str) -> io::Result<Vec<u8>>,
+) -> Result<Scene, GltfError> {
+    let (json, bin) = if bytes.starts_with(GLB_MAGIC) {
+        parse_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let json = std::str::from_utf8(json)
+        .map_err(|error| invalid("JSON", error))?
+        // UTF-8 byte order marks are not allowed, but are written by some tools
+        .trim_start_matches('\u{feff}');
+    let root: Value = serde_json::from_str(json).map_err(GltfError::Json)?;
+    if !root.is_object() {
+        return Err(invalid("root", "Expected an object"));
+    }
+
+    let version = root
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("asset.version", "Missing version"))?;
+    if !version.starts_with("2.") {
+        return Err(GltfError::Unsupported(format!("Version {version}")));
+    }
+    let required = array(&root, "extensionsRequired", "extensionsRequired")?;
+    if !required.is_empty() {
+        let names: Vec<_> = required.iter().filter_map(Value::as_str).collect();
+        return Err(GltfError::Unsupported(format!(
+            "Required extensions {}",
+            names.join(", ")
+        )));
+    }
+
+    let buffers = array(&root, "buffers", "buffers")?
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| load_buffer(buffer, index, bin, &mut read_file))
+        .collect::<Result<Vec<_>, _>>()?;
+    let document = Document {
+        root: &root,
+        buffers,
+    };
+
+    let mut scene = document.scene(&mut read_file)?;
+    scene.fit_to_cube();
+    Ok(scene)
+}
+
+/// Split a GLB file into its JSON chunk and optional binary chunk
+fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfError> {
+    let u32_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .ok_or_else(|| invalid("GLB", "Unexpected end of file"))
+    };
+    let version = u32_at(4)?;
+    if version != 2 {
+        return Err(GltfError::Unsupported(format!("GLB version {version}")));
+    }
+    let length = (u32_at(8)? as usize).min(bytes.len());
+
+    let mut chunks = vec![];
+    let mut offset = 12;
+    while offset < length {
+        let chunk_length = u32_at(offset)? as usize;
+        let chunk_type = u32_at(offset + 4)?;
+        let start = offset + 8;
+        let data = start
+            .checked_add(chunk_length)
+            .and_then(|end| bytes.get(start..end))
+            .ok_or_else(|| invalid("GLB", "Chunk extends past the end of the file"))?;
+        chunks.push((chunk_type, data));
+        // Chunks are padded to 4 byte alignment
+        offset = start + chunk_length.next_multiple_of(4);
+    }
+
+    match chunks[..] {
+        [(GLB_CHUNK_JSON, json)] => Ok((json, None)),
+        [(GLB_CHUNK_JSON, json), (GLB_CHUNK_BIN, bin), ..] => Ok((json, Some(bin))),
+        [(GLB_CHUNK_JSON, json), ..] => Ok((json, None)),
+        _ => Err(invalid("GLB", "The first chunk must be JSON")),
+    }
+}
+
+fn load_buffer(
+    buffer: &Value,
+    index: usize,
+    bin: Option<&[u8]>,
+    read_file: &mut impl FnMut(&str) -> io::Result<Vec<u8>>,
+) -> Result<Vec<u8>, GltfError> {
+    let path = format!("buffers[{index}]");
+    let byte_length = usize_member(buffer, "byteLength", &path)?
+        .ok_or_else(|| invalid(&path, "Missing byteLength"))?;
+    let data = match buffer.get("uri").map(|uri| uri.as_str()) {
+        Some(Some(uri)) => load_uri(uri, &path, read_file)?,
+        Some(None) => return Err(invalid(&path, "uri must be a string")),
+        // Only the first buffer may refer to the GLB binary chunk
+        None if index == 0 => bin.ok_or_else(|| invalid(&path, "Missing uri"))?.to_vec(),
+        None => return Err(invalid(&path, "Missing uri")),
+    };
+    if data.len() < byte_length {
+        return Err(invalid(
+            &path,
+            format!(
+                "byteLength is {byte_length}, but only {} bytes were read",
+                data.len()
+            ),
+        ));
+    }
+    Ok(data)
+}
+
+/// Read the contents of a data URI or relative file URI
+fn load_uri(
+    uri: &str,
+    path: &str,
+    read_file: &mut impl FnMut(&str) -> io::Result<Vec<u8>>,
+) -> Result<Vec<u8>, GltfError> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (media_type, data) = data
+            .split_once(',')
+            .ok_or_else(|| invalid(path, "Invalid data URI"))?;
+        if !media_type.ends_with(";base64") {
+            return Err(GltfError::Unsupported(format!(
+                "{path}: Data URIs must be base64 encoded"
+            )));
+        }
+        BASE64
+            .decode(data)
+            .map_err(|error| invalid(path, format!("Invalid base64 in data URI: {error}")))
+    } else if uri.contains("://") {
+        Err(GltfError::Unsupported(format!("{path}: Remote URI {uri}")))
+    } else {
+        let decoded = percent_decode(uri).ok_or_else(|| invalid(path, "Invalid URI"))?;
+        read_file(&decoded).map_err(|error| {
+            GltfError::Io(io::Error::new(error.kind(), format!("{decoded}: {error}")))
+        })
+    }
+}
+
+fn percent_decode(uri: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut input = uri.bytes();
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let high = (input.next()? as char).to_digit(16)?;
+            let low = (input.next()? as char).to_digit(16)?;
+            bytes.push((high * 16 + low) as u8);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The array member `key`, or an empty slice if it is absent
+fn array<'a>(value: &'a Value, key: &str, path: &str) -> Result<&'a [Value], GltfError> {
+    match value.get(key) {
+        None => Ok(&[]),
+        Some(member) => member
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or_else(|| invalid(path, "Expected an array")),
+    }
+}
+
+/// The JSON path of member `key` of the item at `path`, which is empty for the root
+fn member_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// A non-negative integer, limited to the 32 bit range glTF uses for counts and indices
+fn as_usize(value: &Value) -> Option<usize> {
+    value
+        .as_u64()
+        .and_then(|value| u32::try_from(value).ok())
+        .map(|value| value as usize)
+}
+
+/// The non-negative integer member `key`, or `None` if it is absent
+fn usize_member(value: &Value, key: &str, path: &str) -> Result<Option<usize>, GltfError> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(member) => as_usize(member)
+            .map(Some)
+            .ok_or_else(|| invalid(member_path(path, key), "Expected a non-negative integer")),
+    }
+}
+
+/// The index array member `key`, or an empty list if it is absent
+fn indices(value: &Value, key: &str, path: &str) -> Result<Vec<usize>, GltfError> {
+    let path = member_path(path, key);
+    array(value, key, &path)?
+        .iter()
+        .map(|index| as_usize(index).ok_or_else(|| invalid(&path, "Expected indices")))
+        .collect()
+}
+
+/// The number array member `key` with exactly `N` elements, or `None` if it is absent
+fn floats<const N: usize>(
+    value: &Value,
+    key: &str,
+    path: &str,
+) -> Result<Option<[f32; N]>, GltfError> {
+    let Some(member) = value.get(key) else {
+        return Ok(None);
+    };
+    let error = || invalid(member_path(path, key), format!("Expected {N} numbers"));
+    let values = member.as_array().ok_or_else(error)?;
+    if values.len() != N {
+        return Err(error());
+    }
+    let mut floats = [0.0; N];
+    for (float, value) in floats.iter_mut().zip(values) {
+        *float = value
+            .as_f64()
+            .map(|value| value as f32)
+            .filter(|value| value.is_finite())
+            .ok_or_else(error)?;
+    }
+    Ok(Some(floats))
+}
+
+/// The item at `index` in the top level array `key`
+fn item<'a>(root: &'a Value, key: &str, index: usize) -> Result<&'a Value, GltfError> {
+    array(root, key, key)?
+        .get(index)
+        .ok_or_else(|| invalid(format!("{key}[{index}]"), "No such item"))
+}
+
+struct Document<'a> {
+    root: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Document<'_> {
+    fn scene(
+        &self,
+        read_file: &mut impl FnMut(&str) -> io::Result<Vec<u8>>,
+    ) -> Result<Scene, GltfError> {
+        let (textures, image_textures) = self.textures(read_file)?;
+        let mut materials = self.materials(&image_textures)?;
+        // Primitives without a material use the default material, which is added after the
+        // file's materials
+        let default_material = materials.len();
+        materials.push(Material::default());
+
+        // Meshes are loaded the first time a node uses them
+        let mut meshes = vec![];
+        let mut loaded: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        let mut instances = vec![];
+        for (node, transform) in self.node_transforms()? {
+            let path = format!("nodes[{node}]");
+            let Some(mesh) = usize_member(item(self.root, "nodes", node)?, "mesh", &path)? else {
+                continue;
+            };
+            let primitives = match loaded.entry(mesh) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let primitives = self.mesh(mesh, default_material)?;
+                    let first = meshes.len();
+                    let indices = primitives
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (_, material))| (first + index, *material))
+                        .collect();
+                    meshes.extend(primitives.into_iter().map(|(mesh, _)| mesh));
+                    entry.insert(indices)
+                }
+            };
+            for &(mesh, material) in primitives.iter() {
+                instances.push(Instance {
+                    mesh,
+                    material,
+                    transform,
+                });
+            }
+        }
+        if instances.is_empty() {
+            return Err(GltfError::NoMeshes);
+        }
+        Ok(Scene::new(meshes, materials, textures, instances)?)
+    }
+
+    /// World transforms of the nodes in the default scene, in depth first order
+    fn node_transforms(&self) -> Result<Vec<(usize, Mat4)>, GltfError> {
+        let nodes = array(self.root, "nodes", "nodes")?;
+        let scenes = array(self.root, "scenes", "scenes")?;
+        let roots: Vec<usize> = match usize_member(self.root, "scene", "")? {
+            Some(scene) => {
+                let path = format!("scenes[{scene}]");
+                indices(item(self.root, "scenes", scene)?, "nodes", &path)?
+            }
+            None if !scenes.is_empty() => indices(&scenes[0], "nodes", "scenes[0]")?,
+            // Without scenes, show every node which isn't a child of another node
+            None => {
+                let mut is_child = vec![false; nodes.len()];
+                for (index, node) in nodes.iter().enumerate() {
+                    for child in indices(node, "children", &format!("nodes[{index}]"))? {
+                        if let Some(is_child) = is_child.get_mut(child) {
+                            *is_child = true;
+                        }
+                    }
+                }
+                (0..nodes.len()).filter(|&node| !is_child[node]).collect()
+            }
+        };
+
+        // Traverse with an explicit stack so deep hierarchies can't overflow the call stack
+        let mut visited = vec![false; nodes.len()];
+        let mut transforms = vec![];
+        let mut stack: Vec<(usize, Mat4)> = roots
+            .into_iter()
+            .rev()
+            .map(|root| (root, Mat4::IDENTITY))
+            .collect();
+        while let Some((index, parent)) = stack.pop() {
+            let path = format!("nodes[{index}]");
+            let node = nodes
+                .get(index)
+                .ok_or_else(|| invalid(&path, "No such node"))?;
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(invalid(&path, "Node is used more than once"));
+            }
+            let transform = parent * local_transform(node, &path)?;
+            transforms.push((index, transform));
+            for child in indices(node, "children", &path)?.into_iter().rev() {
+                stack.push((child, transform));
+            }
+        }
+        Ok(transforms)
+    }
+
+    /// Decode the images used by textures, returning them and the scene texture index of each
+    /// glTF texture which has a supported image
+    fn textures(
+        &self,
+        read_file: &mut impl FnMut(&str) -> io::Result<Vec<u8>>,
+    ) -> Result<(Vec<Texture>, Vec<Option<usize>>), GltfError> {
+        let mut textures = vec![];
+        let mut images: HashMap<usize, Option<usize>> = HashMap::new();
+        let mut image_textures = vec![];
+        for (index, texture) in array(self.root, "textures", "textures")?.iter().enumerate() {
+            let path = format!("textures[{index}]");
+            let Some(source) = usize_member(texture, "source", &path)? else {
+                warn!("{} has no image in a supported format", path);
+                image_textures.push(None);
+                continue;
+            };
+            let texture = match images.entry(source) {
+                Entry::Occupied(entry) => *entry.get(),
+                Entry::Vacant(entry) => {
+                    let texture = self.image(source, read_file)?.map(|texture| {
+                        textures.push(texture);
+                        textures.len() - 1
+                    });
+                    *entry.insert(texture)
+                }
+            };
+            image_textures.push(texture);
+        }
+        Ok((textures, image_textures))
+    }
+
+    /// Decode an image, or return `None` if its format is unsupported
+    fn image(
+        &self,
+        index: usize,
+        read_file: &mut impl FnMut(&str) -> io::Result<Vec<u8>>,
+    ) -> Result<Option<Texture>, GltfError> {
+        let path = format!("images[{index}]");
+        let image = item(self.root, "images", index)?;
+        let data = match (image.get("uri"), usize_member(image, "bufferView", &path)?) {
+            (Some(uri), _) => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| invalid(&path, "uri must be a string"))?;
+                load_uri(uri, &path, read_file)?
+            }
+            (None, Some(view)) => self.buffer_view(view)?.0.to_vec(),
+            (None, None) => return Err(invalid(&path, "Missing uri or bufferView")),
+        };
+        match Texture::decode(&data) {
+            Ok(texture) => Ok(Some(texture)),
+            Err(TextureError::UnknownFormat) => {
+                warn!("{} is not a PNG or JPEG image, so it is ignored", path);
+                Ok(None)
+            }
+            Err(error) => Err(invalid(&path, error)),
+        }
+    }
+
+    fn materials(&self, image_textures: &[Option<usize>]) -> Result<Vec<Material>, GltfError> {
+        let mut materials = vec![];
+        for (index, material) in array(self.root, "materials", "materials")?
+            .iter()
+            .enumerate()
+        {
+            let path = format!("materials[{index}].pbrMetallicRoughness");
+            let Some(pbr) = material.get("pbrMetallicRoughness") else {
+                materials.push(Material::default());
+                continue;
+            };
+            let base_color = floats::<4>(pbr, "baseColorFactor", &path)?.unwrap_or([1.0; 4]);
+            let base_color_texture = match pbr.get("baseColorTexture") {
+                None => None,
+                Some(texture_info) => {
+                    let path = format!("{path}.baseColorTexture");
+                    let texture = usize_member(texture_info, "index", &path)?
+                        .ok_or_else(|| invalid(&path, "Missing index"))?;
+                    let tex_coord = usize_member(texture_info, "texCoord", &path)?.unwrap_or(0);
+                    if tex_coord != 0 {
+                        warn!("{} uses TEXCOORD_{}, so it is ignored", path, tex_coord);
+                        None
+                    } else {
+                        *image_textures
+                            .get(texture)
+                            .ok_or_else(|| invalid(&path, "No such texture"))?
+                    }
+                }
+            };
+            materials.push(Material {
+                base_color,
+                base_color_texture,
+            });
+        }
+        Ok(materials)
+    }
+
+    /// Load each triangle primitive of a mesh, with its material index
+    fn mesh(&self, index: usize, default_material: usize) -> Result<Vec<(Mesh, usize)>, GltfError> {
+        let path = format!("meshes[{index}]");
+        let mesh = item(self.root, "meshes", index)?;
+        let mut primitives = vec![];
+        for (primitive_index, primitive) in array(mesh, "primitives", &path)?.iter().enumerate() {
+            let path = format!("{path}.primitives[{primitive_index}]");
+            let mode = usize_member(primitive, "mode", &path)?.unwrap_or(TRIANGLES);
+            if !matches!(mode, TRIANGLES | TRIANGLE_STRIP | TRIANGLE_FAN) {
+                warn!("{} is not made of triangles, so it is ignored", path);
+                continue;
+            }
+            let material = match usize_member(primitive, "material", &path)? {
+                Some(material) if material < default_material => material,
+                Some(_) => return Err(invalid(&path, "No such material")),
+                None => default_material,
+            };
+            let mesh =
+                self.primitive(primitive, mode, &path)?
+                    .map_err(|error| GltfError::Mesh {
+                        mesh: index,
+                        primitive: primitive_index,
+                        error,
+                    })?;
+            primitives.push((mesh, material));
+        }
+        Ok(primitives)
+    }
+
+    fn primitive(
+        &self,
+        primitive: &Value,
+        mode: usize,
+        path: &str,
+    ) -> Result<Result<Mesh, MeshError>, GltfError> {
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| invalid(path, "Missing attributes"))?;
+        let path = format!("{path}.attributes");
+        let attribute = |name: &str| usize_member(attributes, name, &path);
+
+        let position = attribute("POSITION")?.ok_or_else(|| invalid(&path, "Missing POSITION"))?;
+        let positions: Vec<[f32; 3]> = self.read(position, &[FLOAT], false)?;
+        if positions
+            .iter()
+            .any(|position| !position.iter().all(|value| value.is_finite()))
+        {
+            return Err(invalid(&path, "POSITION has non-finite values"));
+        }
+        let vertex_count = positions.len();
+        let check_count = |name: &str, count: usize| {
+            if count == vertex_count {
+                Ok(())
+            } else {
+                Err(invalid(
+                    format!("{path}.{name}"),
+                    format!("Expected {vertex_count} elements, found {count}"),
+                ))
+            }
+        };
+
+        let normals = match attribute("NORMAL")? {
+            Some(accessor) => {
+                let normals: Vec<[f32; 3]> = self.read(accessor, &[FLOAT], false)?;
+                check_count("NORMAL", normals.len())?;
+                Some(normals)
+            }
+            None => None,
+        };
+        let uvs = match attribute("TEXCOORD_0")? {
+            Some(accessor) => {
+                let uvs: Vec<[f32; 2]> =
+                    self.read(accessor, &[FLOAT, UNSIGNED_BYTE, UNSIGNED_SHORT], true)?;
+                check_count("TEXCOORD_0", uvs.len())?;
+                Some(uvs)
+            }
+            None => None,
+        };
+        let colors = match attribute("COLOR_0")? {
+            Some(accessor) => {
+                let colors = self.read_colors(accessor)?;
+                check_count("COLOR_0", colors.len())?;
+                colors
+            }
+            None => vec![[1.0; 3]; vertex_count],
+        };
+
+        let indices = match usize_member(primitive, "indices", &format!("{path}.indices"))? {
+            Some(accessor) => self.read_indices(accessor)?,
+            None => (0..vertex_count as u32).collect(),
+        };
+        let triangles = triangles(&indices, mode);
+
+        Ok(match normals {
+            Some(normals) => {
+                // Normals are required to be unit length, but exporters write slightly off values
+                // and the occasional zero normal
+                let triangle_indices = triangles
+                    .iter()
+                    .filter(|triangle| {
+                        triangle
+                            .iter()
+                            .all(|&index| (index as usize) < vertex_count)
+                    })
+                    .map(|triangle| triangle.map(|index| index as usize));
+                let fallback = smooth_normals(&positions, triangle_indices);
+                let normals = normals
+                    .iter()
+                    .zip(fallback)
+                    .map(|(&normal, fallback)| {
+                        Vec3::from(normal)
+                            .try_normalize()
+                            .map_or(fallback, |normal| normal.to_array())
+                    })
+                    .collect();
+                Mesh::new(
+                    positions,
+                    colors,
+                    normals,
+                    uvs,
+                    Indices::from_u32(triangles.into_flattened()),
+                )
+            }
+            // glTF requires flat shading for primitives without normals
+            None => flat_mesh(&positions, &colors, uvs.as_deref(), &triangles),
+        })
+    }
+
+    /// Vertex colors, with alpha dropped and normalized integers converted to floats
+    fn read_colors(&self, accessor: usize) -> Result<Vec<[f32; 3]>, GltfError> {
+        let types = [FLOAT, UNSIGNED_BYTE, UNSIGNED_SHORT];
+        match self.accessor_type(accessor)? {
+            "VEC4" => {
+                let colors: Vec<[f32; 4]> = self.read(accessor, &types, true)?;
+                Ok(colors.into_iter().map(|[r, g, b, _]| [r, g, b]).collect())
+            }
+            _ => self.read(accessor, &types, true),
+        }
+    }
+
+    fn read_indices(&self, index: usize) -> Result<Vec<u32>, GltfError> {
+        let types = [UNSIGNED_BYTE, UNSIGNED_SHORT, UNSIGNED_INT];
+        let layout = self.accessor_layout(index, "SCALAR", &types, false)?;
+        Ok(layout
+            .elements()
+            .map(|bytes| match bytes {
+                [byte] => *byte as u32,
+                [low, high] => u16::from_le_bytes([*low, *high]) as u32,
+                _ => u32::from_le_bytes(bytes.try_into().unwrap()),
+            })
+            .collect())
+    }
+
+    fn accessor_type(&self, index: usize) -> Result<&str, GltfError> {
+        item(self.root, "accessors", index)?
+            .get("type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid(format!("accessors[{index}]"), "Missing type"))
+    }
+
+    /// Read an accessor with `N` components of one of the allowed component types, converting to
+    /// floats. `normalized` integer types are required to be marked normalized.
+    fn read<const N: usize>(
+        &self,
+        index: usize,
+        component_types: &[u64],
+        normalized: bool,
+    ) -> Result<Vec<[f32; N]>, GltfError> {
+        let accessor_type = match N {
+            2 => "VEC2",
+            3 => "VEC3",
+            4 => "VEC4",
+            _ => unreachable!(),
+        };
+        let layout = self.accessor_layout(index, accessor_type, component_types, normalized)?;
+        let size = layout.component_size;
+        let component = |bytes: &[u8]| -> f32 {
+            match layout.component_type {
+                BYTE => {
+                    let value = bytes[0] as i8 as f32;
+                    if normalized {
+                        (value / 127.0).max(-1.0)
+                    } else {
+                        value
+                    }
+                }
+                UNSIGNED_BYTE => {
+                    let value = bytes[0] as f32;
+                    if normalized { value / 255.0 } else { value }
+                }
+                SHORT => {
+                    let value = i16::from_le_bytes([bytes[0], bytes[1]]) as f32;
+                    if normalized {
+                        (value / 32767.0).max(-1.0)
+                    } else {
+                        value
+                    }
+                }
+                UNSIGNED_SHORT => {
+                    let value = u16::from_le_bytes([bytes[0], bytes[1]]) as f32;
+                    if normalized { value / 65535.0 } else { value }
+                }
+                UNSIGNED_INT => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+                _ => f32::from_le_bytes(bytes.try_into().unwrap()),
+            }
+        };
+        Ok(layout
+            .elements()
+            .map(|bytes| std::array::from_fn(|i| component(&bytes[i * size..(i + 1) * size])))
+            .collect())
+    }
+
+    /// Validate an accessor's type and its range within its buffer view
+    fn accessor_layout(
+        &self,
+        index: usize,
+        accessor_type: &str,
+        component_types: &[u64],
+        normalized: bool,
+    ) -> Result<AccessorLayout<'_>, GltfError> {
+        let path = format!("accessors[{index}]");
+        let accessor = item(self.root, "accessors", index)?;
+        if accessor.get("sparse").is_some() {
+            return Err(GltfError::Unsupported(format!("{path}: Sparse accessors")));
+        }
+
+        let actual_type = self.accessor_type(index)?;
+        if actual_type != accessor_type {
+            return Err(invalid(
+                &path,
+                format!("Expected type {accessor_type}, found {actual_type}"),
+            ));
+        }
+        let component_type = accessor
+            .get("componentType")
+            .and_then(Value::as_f64)
+            .map(|component_type| component_type as u64)
+            .ok_or_else(|| invalid(&path, "Missing componentType"))?;
+        if !component_types.contains(&component_type) {
+            return Err(invalid(
+                &path,
+                format!("Component type {component_type} is not allowed here"),
+            ));
+        }
+        let is_normalized = matches!(accessor.get("normalized"), Some(Value::Bool(true)));
+        if component_type != FLOAT && is_normalized != normalized {
+            return Err(invalid(&path, "Unexpected normalized value"));
+        }
+
+        let count = usize_member(accessor, "count", &path)?
+            .ok_or_else(|| invalid(&path, "Missing count"))?;
+        let component_size = match component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            _ => 4,
+        };
+        let components = match accessor_type {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            _ => 4,
+        };
+        let element_size = component_size * components;
+
+        // Accessors without a buffer view are all zeros
+        let Some(view) = usize_member(accessor, "bufferView", &path)? else {
+            if count > MAX_ZERO_ELEMENTS {
+                return Err(invalid(
+                    &path,
+                    format!(
+                        "Accessor without a buffer view has more than {MAX_ZERO_ELEMENTS} elements"
+                    ),
+                ));
+            }
+            return Ok(AccessorLayout {
+                data: None,
+                offset: 0,
+                stride: 0,
+                count,
+                element_size,
+                component_type,
+                component_size,
+            });
+        };
+        let (data, stride) = self.buffer_view(view)?;
+        let stride = stride.unwrap_or(element_size);
+        let offset = usize_member(accessor, "byteOffset", &path)?.unwrap_or(0);
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(offset))
+                .and_then(|start| start.checked_add(element_size)),
+            None => Some(0),
+        };
+        if end.is_none_or(|end| end > data.len()) {
+            return Err(invalid(
+                &path,
+                "Accessor extends past the end of its buffer view",
+            ));
+        }
+        Ok(AccessorLayout {
+            data: Some(data),
+            offset,
+            stride,
+            count,
+            element_size,
+            component_type,
+            component_size,
+        })
+    }
+
+    /// The bytes of a buffer view and its byte stride, if any
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), GltfError> {
+        let path = format!("bufferViews[{index}]");
+        let view = item(self.root, "bufferViews", index)?;
+        let buffer =
+            usize_member(view, "buffer", &path)?.ok_or_else(|| invalid(&path, "Missing buffer"))?;
+        let buffer = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| invalid(&path, "No such buffer"))?;
+        let offset = usize_member(view, "byteOffset", &path)?.unwrap_or(0);
+        let length = usize_member(view, "byteLength", &path)?
+            .ok_or_else(|| invalid(&path, "Missing byteLength"))?;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| invalid(&path, "View extends past the end of its buffer"))?;
+        let stride = usize_member(view, "byteStride", &path)?;
+        if stride == Some(0) {
+            return Err(invalid(&path, "byteStride must be positive"));
+        }
+        Ok((data, stride))
+    }
+}
+
+/// The location of an accessor's elements, which have been checked to fit in its buffer view
+struct AccessorLayout<'a> {
+    /// The buffer view's data, or `None` if the accessor is all zeros
+    data: Option<&'a [u8]>,
+    offset: usize,
+    stride: usize,
+    count: usize,
+    element_size: usize,
+    component_type: u64,
+    component_size: usize,
+}
+
+impl AccessorLayout<'_> {
+    /// The bytes of each element
+    fn elements(&self) -> impl Iterator<Item = &[u8]> {
+        const ZEROS: [u8; 16] = [0; 16];
+        (0..self.count).map(move |element| match self.data {
+            Some(data) => {
+                let start = self.offset + element * self.stride;
+                &data[start..start + self.element_size]
+            }
+            None => &ZEROS[..self.element_size],
+        })
+    }
+}
+
+/// A node's transform relative to its parent, from its matrix or its translation, rotation, and
+/// scale
+fn local_transform(node: &Value, path: &str) -> Result<Mat4, GltfError> {
+    if let Some(matrix) = floats::<16>(node, "matrix", path)? {
+        return Ok(Mat4::from_cols_array(&matrix));
+    }
+    let translation = floats::<3>(node, "translation", path)?.unwrap_or([0.0; 3]);
+    let rotation = floats::<4>(node, "rotation", path)?.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let scale = floats::<3>(node, "scale", path)?.unwrap_or([1.0; 3]);
+    let rotation = Quat::from_array(rotation);
+    if rotation.length_squared() == 0.0 {
+        return Err(invalid(
+            format!("{path}.rotation"),
+            "Zero length quaternion",
+        ));
+    }
+    Ok(Mat4::from_scale_rotation_translation(
+        Vec3::from(scale),
+        rotation.normalize(),
+        Vec3::from(translation),
+    ))
+}
+
+/// Convert indices of any triangle topology to triangles, dropping degenerate triangles
+fn triangles(indices: &[u32], mode: usize) -> Vec<[u32; 3]> {
+    let triangles: Vec<[u32; 3]> = match mode {
+        TRIANGLE_STRIP => indices
+            .windows(3)
+            .enumerate()
+            // Every other triangle in a strip is reversed to keep the winding consistent
+            .map(|(i, window)| match i % 2 {
+                0 => [window[0], window[1], window[2]],
+                _ => [window[1], window[0], window[2]],
+            })
+            .collect(),
+        TRIANGLE_FAN if indices.len() >= 3 => indices[1..]
+            .windows(2)
+            .map(|window| [indices[0], window[0], window[1]])
+            .collect(),
+        TRIANGLE_FAN => vec![],
+        _ => indices
+            .chunks_exact(3)
+            .map(|chunk| [chunk[0], chunk[1], chunk[2]])
+            .collect(),
+    };
+    triangles
+        .into_iter()
+        .filter(|[a, b, c]| a != b && b != c && a != c)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A right triangle in the XY plane, with u16 indices
+    fn triangle_buffer() -> Vec<u8> {
+        let mut bytes = vec![];
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        for index in [0u16, 1, 2, 0] {
+            bytes.extend(index.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// A glTF document drawing the triangle buffer at each of `nodes`
+    fn triangle_gltf(buffer_uri: &str, nodes: &str, extra: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": {nodes},
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1 }}] }}],
+                "buffers": [{{ {buffer_uri} "byteLength": 44 }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+                ]
+                {extra}
+            }}"#
+        )
+    }
+
+    fn data_uri() -> String {
+        format!(
+            r#""uri": "data:application/octet-stream;base64,{}","#,
+            BASE64.encode(triangle_buffer())
+        )
+    }
+
+    fn no_files(uri: &str) -> io::Result<Vec<u8>> {
+        Err(io::Error::new(io::ErrorKind::NotFound, uri.to_string()))
+    }
+
+    #[test]
+    fn decodes_uris() {
+        let read = |uri: &str| load_uri(uri, "buffers[0]", &mut no_files);
+        assert_eq!(read("data:;base64,AAEC").unwrap(), [0, 1, 2]);
+        // Padding is optional
+        assert_eq!(read("data:;base64,AAE=").unwrap(), [0, 1]);
+        assert_eq!(read("data:;base64,AAE").unwrap(), [0, 1]);
+        assert!(matches!(
+            read("data:;base64,a*b"),
+            Err(GltfError::Invalid(_))
+        ));
+        assert_eq!(percent_decode("a%20b.bin").unwrap(), "a b.bin");
+    }
+
+    #[test]
+    fn loads_data_uri_buffers_with_flat_normals() {
+        let gltf = triangle_gltf(&data_uri(), r#"[{ "mesh": 0 }]"#, "");
+        let scene = parse(gltf.as_bytes(), no_files).unwrap();
+        assert_eq!(scene.meshes().len(), 1);
+        assert_eq!(scene.instances().len(), 1);
+        let mesh = &scene.meshes()[0];
+        assert_eq!(mesh.triangle_count(), 1);
+        assert_eq!(mesh.normals(), [[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.colors(), [[1.0; 3]; 3]);
+        assert_eq!(
+            scene.materials()[scene.instances()[0].material],
+            Material::default()
+        );
+    }
+
+    #[test]
+    fn loads_external_buffers() {
+        let gltf = triangle_gltf(r#""uri": "my%20mesh.bin","#, r#"[{ "mesh": 0 }]"#, "");
+        let scene = parse(gltf.as_bytes(), |uri| {
+            assert_eq!(uri, "my mesh.bin");
+            Ok(triangle_buffer())
+        })
+        .unwrap();
+        assert_eq!(scene.meshes()[0].vertex_count(), 3);
+        assert!(matches!(
+            parse(gltf.as_bytes(), no_files),
+            Err(GltfError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn loads_glb() {
+        let gltf = triangle_gltf("", r#"[{ "mesh": 0 }]"#, "");
+        let mut json = gltf.into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let bin = triangle_buffer();
+
+        let mut glb = vec![];
+        glb.extend(GLB_MAGIC);
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend(&json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend(&bin);
+
+        let scene = parse(&glb, no_files).unwrap();
+        assert_eq!(scene.meshes()[0].vertex_count(), 3);
+        assert!(parse(&glb[..30], no_files).is_err());
+    }
+
+    #[test]
+    fn composes_node_transforms() {
+        // The child's translation is scaled by its parent, and the mesh is shared by both nodes
+        let nodes = r#"[
+            { "mesh": 0, "scale": [2, 2, 2], "children": [1] },
+            { "mesh": 0, "translation": [1, 0, 0] }
+        ]"#;
+        let gltf = triangle_gltf(&data_uri(), nodes, "");
+        let scene = parse(gltf.as_bytes(), no_files).unwrap();
+        assert_eq!(scene.meshes().len(), 1);
+        let [parent, child] = scene.instances() else {
+            panic!("Expected 2 instances");
+        };
+        assert_eq!(parent.mesh, child.mesh);
+
+        // Undo the fit to cube, which is the same for both instances, by comparing them relatively
+        let relative = parent.transform.inverse() * child.transform;
+        assert!(relative.abs_diff_eq(Mat4::from_translation(Vec3::X), 1e-6));
+    }
+
+    #[test]
+    fn applies_materials() {
+        let nodes = r#"[{ "mesh": 0 }]"#;
+        let gltf = triangle_gltf(
+            &data_uri(),
+            nodes,
+            r#",
+            "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [0.5, 0.25, 1, 1] } }]"#,
+        )
+        .replace(r#""indices": 1"#, r#""indices": 1, "material": 0"#);
+        let scene = parse(gltf.as_bytes(), no_files).unwrap();
+        let material = &scene.materials()[scene.instances()[0].material];
+        assert_eq!(material.base_color, [0.5, 0.25, 1.0, 1.0]);
+        assert_eq!(material.base_color_texture, None);
+    }
+
+    #[test]
+    fn loads_jpeg_textures() {
+        let gltf = triangle_gltf(
+            &data_uri(),
+            r#"[{ "mesh": 0 }]"#,
+            r#",
+            "images": [{ "uri": "rgb.jpg" }, { "uri": "image.gif" }],
+            "textures": [{ "source": 0 }, { "source": 1 }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }]"#,
+        )
+        .replace(r#""indices": 1"#, r#""indices": 1, "material": 0"#);
+        let scene = parse(gltf.as_bytes(), |uri| match uri {
+            "rgb.jpg" => Ok(include_bytes!("../tests/textures/rgb.jpg").to_vec()),
+            _ => Ok(b"GIF89a".to_vec()),
+        })
+        .unwrap();
+        assert_eq!(scene.textures().len(), 1);
+        assert_eq!(
+            (scene.textures()[0].width(), scene.textures()[0].height()),
+            (16, 8)
+        );
+        let material = &scene.materials()[scene.instances()[0].material];
+        assert_eq!(material.base_color_texture, Some(0));
+    }
+
+    #[test]
+    fn converts_strips_and_fans() {
+        assert_eq!(
+            triangles(&[0, 1, 2, 3], TRIANGLE_STRIP),
+            [[0, 1, 2], [2, 1, 3]]
+        );
+        assert_eq!(
+            triangles(&[0, 1, 2, 3], TRIANGLE_FAN),
+            [[0, 1, 2], [0, 2, 3]]
+        );
+        assert_eq!(triangles(&[0, 1, 1, 2, 3, 4], TRIANGLES), [[2, 3, 4]]);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let cases = [
+            (
+                "[",
+                "Invalid JSON: EOF while parsing a list at line 1 column 1",
+            ),
+            (
+                r#"{ "asset": {} }"#,
+                "Invalid glTF: asset.version: Missing version",
+            ),
+            (
+                r#"{ "asset": { "version": "2.0" }, "extensionsRequired": ["KHR_draco_mesh_compression"] }"#,
+                "Unsupported glTF: Required extensions KHR_draco_mesh_compression",
+            ),
+            (
+                r#"{ "asset": { "version": "2.0" } }"#,
+                "The scene has no triangle meshes",
+            ),
+        ];
+        for (source, message) in cases {
+            let error = parse(source.as_bytes(), no_files).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+
+        let cyclic = triangle_gltf(&data_uri(), r#"[{ "mesh": 0, "children": [0] }]"#, "");
+        assert_eq!(
+            parse(cyclic.as_bytes(), no_files).unwrap_err().to_string(),
+            "Invalid glTF: nodes[0]: Node is used more than once"
+        );
+
+        let out_of_bounds = triangle_gltf(&data_uri(), r#"[{ "mesh": 0 }]"#, "").replace(
+            r#""count": 3, "type": "VEC3""#,
+            r#""count": 4, "type": "VEC3""#,
+        );
+        assert_eq!(
+            parse(out_of_bounds.as_bytes(), no_files)
+                .unwrap_err()
+                .to_string(),
+            "Invalid glTF: accessors[0]: Accessor extends past the end of its buffer view"
+        );
+
+        let huge_zeros = triangle_gltf(&data_uri(), r#"[{ "mesh": 0 }]"#, "").replace(
+            r#""bufferView": 0, "componentType": 5126, "count": 3"#,
+            r#""componentType": 5126, "count": 4000000000"#,
+        );
+        assert_eq!(
+            parse(huge_zeros.as_bytes(), no_files)
+                .unwrap_err()
+                .to_string(),
+            "Invalid glTF: accessors[0]: Accessor without a buffer view has more than 1048576 elements"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+//! Offscreen rendering without a window or surface
+//!
+//! The cube is rendered into an offscreen texture which is copied into a buffer and mapped back to
+//! the CPU. This allows individual frames to be produced from scripts or CI where no window system
+//! is available. Buffer mapping blocks on `Device::poll`, so this module is not available on web.
+
+use crate::adapter;
+use crate::attachment::AttachmentTexture;
+use crate::clock::AnimationClock;
+use crate::cube::Cube;
+use crate::depth;
+use crate::error::Error;
+use crate::msaa;
+use crate::options::Options;
+use crate::scene::Texture;
+use crate::shadertoy::ShadertoyMouse;
+use crate::texture::TextureUploader;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use tracing::info;
+use winit::dpi::PhysicalSize;
+
+/// Format of the offscreen render target. PNG images are stored in sRGB, so an sRGB format lets
+/// the GPU handle the encoding when writing to the texture.
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// A single rendered frame in tightly packed RGBA8 (sRGB) format
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub size: PhysicalSize<u32>,
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.size.width, self.size.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct Headless {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    size: PhysicalSize<u32>,
+    texture: wgpu::Texture,
+    msaa: Option<AttachmentTexture>,
+    depth: AttachmentTexture,
+    readback_buffer: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+    cube: Cube,
+}
+
+impl Headless {
+    pub async fn new(size: PhysicalSize<u32>, options: &Options) -> Result<Self, Error> {
+        let instance = wgpu::Instance::new(&options.instance_descriptor());
+
+        let adapter = adapter::request_adapter(&instance, options, None).await?;
+        Self::from_adapter(&adapter, size, options).await
+    }
+
+    /// Create a headless renderer on a specific adapter, such as the software fallback adapter
+    pub async fn from_adapter(
+        adapter: &wgpu::Adapter,
+        size: PhysicalSize<u32>,
+        options: &Options,
+    ) -> Result<Self, Error> {
+        let adapter_info = adapter.get_info();
+        info!(
+            "Using adapter: {} ({:?})",
+            adapter_info.name, adapter_info.backend
+        );
+        let depth_format = depth::supported_format(adapter, options.depth_format);
+        let device_descriptor = wgpu::DeviceDescriptor {
+            required_features: msaa::required_features(adapter) | depth_format.required_features(),
+            ..Default::default()
+        };
+        let (device, queue) = adapter.request_device(&device_descriptor).await?;
+
+        // wgpu panics when creating a texture larger than the limit
+        let max = device.limits().max_texture_dimension_2d;
+        if size.width > max || size.height > max {
+            return Err(Error::TargetTooLarge { size, max });
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Headless Render Target"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        // Rows copied out of a texture must be aligned to COPY_BYTES_PER_ROW_ALIGNMENT. The padding
+        // is stripped again when the buffer is read back.
+        let padded_bytes_per_row =
+            (size.width * BYTES_PER_PIXEL).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Readback Buffer"),
+            size: padded_bytes_per_row as u64 * size.height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let sample_count = msaa::supported_sample_count(
+            adapter,
+            &[TEXTURE_FORMAT, depth_format],
+            options.sample_count,
+        );
+        let msaa = (sample_count > 1).then(|| {
+            AttachmentTexture::new(
+                &device,
+                "MSAA Color Texture",
+                TEXTURE_FORMAT,
+                sample_count,
+                size,
+            )
+        });
+        let depth =
+            AttachmentTexture::new(&device, "Depth Texture", depth_format, sample_count, size);
+        let cube = Cube::new(
+            options,
+            TEXTURE_FORMAT,
+            depth_format,
+            sample_count,
+            &device,
+            &queue,
+            &size,
+        );
+
+        Ok(Self {
+            device,
+            queue,
+            size,
+            texture,
+            msaa,
+            depth,
+            readback_buffer,
+            padded_bytes_per_row,
+            cube,
+        })
+    }
+
+    /// Set the mouse state a Shadertoy shader sees as `iMouse`, in pixels of the rendered frame
+    pub fn set_mouse(&mut self, mouse: ShadertoyMouse) {
+        self.cube.set_mouse(mouse);
+    }
+
+    /// Render the cube at frame `frame` and read the result back to the CPU
+    ///
+    /// Frames are numbered at the clock's reference frame rate, so the output for a given frame is
+    /// the same no matter how fast frames are rendered.
+    pub fn render(&mut self, frame: u64) -> Frame {
+        let texture_view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        self.cube.set_time(AnimationClock::frame_time(frame));
+        self.cube.set_frame(frame);
+        let (color_view, resolve_target) = match &self.msaa {
+            Some(msaa) => (msaa.view(), Some(&texture_view)),
+            None => (&texture_view, None),
+        };
+        self.cube.render(
+            color_view,
+            resolve_target,
+            self.depth.view(),
+            &self.queue,
+            &mut encoder,
+        );
+
+        let pixels = self.read_back(
+            encoder,
+            &self.texture,
+            &self.readback_buffer,
+            self.padded_bytes_per_row,
+        );
+        Frame {
+            size: self.size,
+            pixels,
+        }
+    }
+
+    /// Upload a texture as the renderer does and read back its full size level, or return `None`
+    /// if it exceeds the device's limits
+    ///
+    /// This checks texture processing on the GPU, such as YUV conversion, without the cube in the
+    /// way.
+    pub fn upload_texture(&self, texture: &Texture) -> Option<Frame> {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let view = TextureUploader::new(&self.device).upload(
+            texture,
+            &self.device,
+            &self.queue,
+            &mut encoder,
+        )?;
+        let padded_bytes_per_row = (texture.width() * BYTES_PER_PIXEL)
+            .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Headless Texture Readback Buffer"),
+            size: padded_bytes_per_row as u64 * texture.height() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let pixels = self.read_back(encoder, view.texture(), &buffer, padded_bytes_per_row);
+        Some(Frame {
+            size: PhysicalSize::new(texture.width(), texture.height()),
+            pixels,
+        })
+    }
+
+    /// Copy the top level of `texture` into `buffer`, submit `encoder`, and return the tightly
+    /// packed pixels once the copy has completed
+    fn read_back(
+        &self,
+        mut encoder: wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        buffer: &wgpu::Buffer,
+        padded_bytes_per_row: u32,
+    ) -> Vec<u8> {
+        let size = wgpu::Extent3d {
+            width: texture.width(),
+            height: texture.height(),
+            depth_or_array_layers: 1,
+        };
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        // Map the readback buffer and block until the copy has completed
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .unwrap();
+        receiver.recv().unwrap().unwrap();
+
+        // Strip the row padding required by the copy
+        let unpadded_bytes_per_row = (size.width * BYTES_PER_PIXEL) as usize;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * size.height as usize);
+        {
+            let mapped = buffer_slice.get_mapped_range();
+            for row in mapped.chunks_exact(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+        }
+        buffer.unmap();
+        pixels
+    }
+}
@@ -1,16 +1,42 @@
-#![cfg(target_os = "android")]
-
+pub mod adapter;
 mod app;
+mod attachment;
+pub mod clock;
+mod color;
 mod cube;
+mod depth;
 #[cfg(feature = "egui")]
 mod egui;
+mod error;
+pub mod gltf;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+mod lifecycle;
+pub mod light;
+pub mod mesh;
+mod msaa;
+pub mod obj;
+mod options;
+pub mod ply;
+pub mod primitive;
+pub mod processing;
+pub mod scene;
+pub mod shadertoy;
+pub mod shading;
+pub mod stl;
+pub mod texture;
+#[cfg(target_arch = "wasm32")]
+mod web;
+pub mod yuv;
 
-use app::{App, WgpuEvent};
-use winit::event_loop::EventLoop;
-use winit::platform::android::activity::AndroidApp;
+pub use app::{App, WgpuEvent};
+pub use error::Error;
+pub use options::Options;
 
+#[cfg(target_os = "android")]
 #[unsafe(no_mangle)]
-fn android_main(android_app: AndroidApp) {
+fn android_main(android_app: winit::platform::android::activity::AndroidApp) {
+    use winit::event_loop::EventLoop;
     use winit::platform::android::EventLoopBuilderExtAndroid;
     android_logger::init_once(
         android_logger::Config::default().with_max_level(log::Level::Trace.to_level_filter()),
@@ -21,6 +47,9 @@ fn android_main(android_app: AndroidApp) {
         .build()
         .unwrap();
     let event_loop_proxy = event_loop.create_proxy();
-    let mut app = App::new(event_loop_proxy);
+    let mut app = App::new(event_loop_proxy, Options::default());
     event_loop.run_app(&mut app).unwrap();
+    if let Some(error) = app.error() {
+        log::error!("{}", error);
+    }
 }
@@ -0,0 +1,611 @@
+//! App lifecycle state machine
+//!
+//! The decisions about when to create the window and context, recreate the surface, resize,
+//! render, and request redraws are made here, separately from winit and wgpu. `Lifecycle` turns
+//! lifecycle events into `Command`s which `App` carries out on the real window and GPU context.
+//! The window and context types are generic so the state machine can be driven by synthetic
+//! events in tests, without a display or GPU.
+
+use std::fmt::Debug;
+use tracing::{debug, error, warn};
+use winit::dpi::PhysicalSize;
+
+/// The part of a window the lifecycle needs to know about
+pub(crate) trait LifecycleWindow {
+    fn inner_size(&self) -> PhysicalSize<u32>;
+}
+
+impl LifecycleWindow for std::sync::Arc<winit::window::Window> {
+    fn inner_size(&self) -> PhysicalSize<u32> {
+        winit::window::Window::inner_size(self)
+    }
+}
+
+/// Surfaces can't be configured with a zero size, which happens when a window is minimized on
+/// Windows or a canvas is collapsed on the web
+pub(crate) fn is_zero_size(size: PhysicalSize<u32>) -> bool {
+    size.width == 0 || size.height == 0
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) enum State<W, C> {
+    #[default]
+    Uninitialized,
+    /// The window exists and the context is being created (asynchronously on wasm)
+    Initializing,
+    Resumed {
+        window: W,
+        context: C,
+    },
+    /// The native window was destroyed (Android) and the context has no surface
+    Suspended {
+        window: W,
+        context: C,
+    },
+}
+
+/// Events which drive the lifecycle, translated from winit events
+#[derive(Debug)]
+pub(crate) enum Event<W, C> {
+    Resumed,
+    Suspended,
+    /// The context requested by `Command::CreateContext` or `Command::RebuildContext` is ready
+    Initialized {
+        window: W,
+        context: C,
+    },
+    DeviceLost,
+    /// The surface recreated on resume needs a different format, so the context must be rebuilt
+    SurfaceFormatChanged,
+    Resized(PhysicalSize<u32>),
+    Occluded(bool),
+    CloseRequested,
+    RedrawRequested,
+    AboutToWait,
+}
+
+/// Work `App` must carry out on the window and context, in order
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command<W, C> {
+    /// Create the window and start creating the context
+    CreateContext,
+    /// Start creating a new context for `window` to replace `context`, whose device was lost or
+    /// whose surface format is no longer supported
+    RebuildContext {
+        window: W,
+        context: C,
+    },
+    /// Recreate the surface of the resumed context
+    CreateSurface,
+    /// Drop the surface of the suspended context
+    DropSurface,
+    /// Resize the surface and render targets of the resumed context
+    Resize(PhysicalSize<u32>),
+    Render,
+    RequestRedraw,
+    Exit,
+}
+
+pub(crate) struct Lifecycle<W, C> {
+    state: State<W, C>,
+    /// The window is fully hidden by other windows or is on an inactive workspace
+    occluded: bool,
+    /// The window is minimized or collapsed to zero size
+    zero_sized: bool,
+    /// The device was lost while suspended and the context must be rebuilt when resumed
+    device_lost: bool,
+    /// The most recent size received while initializing, applied when the context is ready
+    pending_resize: Option<PhysicalSize<u32>>,
+    /// Request redraws from `AboutToWait` rather than while handling `RedrawRequested`
+    defer_redraw: bool,
+    /// A deferred redraw is waiting for `AboutToWait`
+    redraw_deferred: bool,
+}
+
+impl<W: LifecycleWindow + Debug, C: Debug> Lifecycle<W, C> {
+    /// Create the lifecycle in the uninitialized state
+    ///
+    /// Calling window.request_redraw() during a WindowEvent::RedrawRequested does not work
+    /// properly on iOS. As a workaround, `defer_redraw` postpones the request until the event
+    /// loop is about to wait, which appears to work.
+    ///
+    /// Issue: https://github.com/rust-windowing/winit/issues/3406
+    pub fn new(defer_redraw: bool) -> Self {
+        Self {
+            state: State::Uninitialized,
+            occluded: false,
+            zero_sized: false,
+            device_lost: false,
+            pending_resize: None,
+            defer_redraw,
+            redraw_deferred: false,
+        }
+    }
+
+    pub fn state(&self) -> &State<W, C> {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut State<W, C> {
+        &mut self.state
+    }
+
+    /// Rendering is skipped while nothing is visible
+    pub fn can_render(&self) -> bool {
+        !self.occluded && !self.zero_sized
+    }
+
+    #[cfg(test)]
+    pub fn pending_resize(&self) -> Option<PhysicalSize<u32>> {
+        self.pending_resize
+    }
+
+    /// Update the state for `event` and return the commands to carry out
+    pub fn handle(&mut self, event: Event<W, C>) -> Vec<Command<W, C>> {
+        match event {
+            Event::Resumed => self.resumed(),
+            Event::Suspended => self.suspended(),
+            Event::Initialized { window, context } => self.initialized(window, context),
+            Event::DeviceLost => self.device_lost(),
+            Event::SurfaceFormatChanged => self.surface_format_changed(),
+            Event::CloseRequested => {
+                // TODO: Could drop resources here for cleanup
+                vec![Command::Exit]
+            }
+            Event::AboutToWait => {
+                if std::mem::take(&mut self.redraw_deferred) {
+                    vec![Command::RequestRedraw]
+                } else {
+                    vec![]
+                }
+            }
+            Event::Resized(_) | Event::Occluded(_) | Event::RedrawRequested => {
+                self.window_event(event)
+            }
+        }
+    }
+
+    fn resumed(&mut self) -> Vec<Command<W, C>> {
+        // Winit documentation states that .resumed() can be called multiple times on some
+        // platforms. On Android it is called every time the app returns to the foreground with a
+        // new native window.
+        match std::mem::take(&mut self.state) {
+            State::Uninitialized => {
+                self.state = State::Initializing;
+                vec![Command::CreateContext]
+            }
+            State::Suspended { window, context } => {
+                if std::mem::take(&mut self.device_lost) {
+                    self.state = State::Initializing;
+                    return vec![Command::RebuildContext { window, context }];
+                }
+                debug!("Recreating surface after suspend");
+                let size = window.inner_size();
+                self.state = State::Resumed { window, context };
+                let mut commands = vec![Command::CreateSurface];
+                commands.extend(self.resize(size));
+                commands.push(Command::RequestRedraw);
+                commands
+            }
+            state @ (State::Initializing | State::Resumed { .. }) => {
+                debug!("Ignoring repeated call to .resumed()");
+                self.state = state;
+                vec![]
+            }
+        }
+    }
+
+    fn suspended(&mut self) -> Vec<Command<W, C>> {
+        match std::mem::take(&mut self.state) {
+            State::Resumed { window, context } => {
+                debug!("Suspended, dropping surface");
+                self.state = State::Suspended { window, context };
+                vec![Command::DropSurface]
+            }
+            state => {
+                self.state = state;
+                vec![]
+            }
+        }
+    }
+
+    fn initialized(&mut self, window: W, context: C) -> Vec<Command<W, C>> {
+        // TODO: Is it safe to assume state will be initializing?
+        assert!(matches!(self.state, State::Initializing));
+        self.zero_sized = is_zero_size(window.inner_size());
+        self.state = State::Resumed { window, context };
+
+        let mut commands = vec![];
+        if let Some(new_size) = self.pending_resize.take() {
+            commands.extend(self.resize(new_size));
+        }
+        commands.push(Command::RequestRedraw);
+        commands
+    }
+
+    fn surface_format_changed(&mut self) -> Vec<Command<W, C>> {
+        match std::mem::take(&mut self.state) {
+            State::Resumed { window, context } => {
+                self.state = State::Initializing;
+                vec![Command::RebuildContext { window, context }]
+            }
+            state => {
+                self.state = state;
+                vec![]
+            }
+        }
+    }
+
+    fn device_lost(&mut self) -> Vec<Command<W, C>> {
+        match std::mem::take(&mut self.state) {
+            State::Resumed { window, context } => {
+                self.state = State::Initializing;
+                vec![Command::RebuildContext { window, context }]
+            }
+            State::Suspended { window, context } => {
+                // The surface can't be created until resumed, so rebuild then
+                self.device_lost = true;
+                self.state = State::Suspended { window, context };
+                vec![]
+            }
+            state => {
+                self.state = state;
+                vec![]
+            }
+        }
+    }
+
+    /// Track the window size, returning a resize command unless the size is zero
+    fn resize(&mut self, new_size: PhysicalSize<u32>) -> Option<Command<W, C>> {
+        self.zero_sized = is_zero_size(new_size);
+        if self.zero_sized {
+            debug!("Window has zero size, pausing rendering");
+            None
+        } else {
+            Some(Command::Resize(new_size))
+        }
+    }
+
+    fn window_event(&mut self, event: Event<W, C>) -> Vec<Command<W, C>> {
+        match &self.state {
+            State::Uninitialized => {
+                error!("Received window event in State::Uninitialized: {:?}", event);
+                vec![]
+            }
+            State::Initializing => {
+                if let Event::Resized(new_size) = event {
+                    // The wgpu initialization may not be complete when winit sends the resize
+                    // event. Store the most recent resize event so it can be used when wgpu
+                    // initialization is complete.
+                    self.pending_resize = Some(new_size);
+                } else {
+                    warn!("Dropped event during initialization: {:?}", event);
+                }
+                vec![]
+            }
+            // The surface is recreated and the window redrawn when the app is resumed
+            State::Suspended { .. } => vec![],
+            State::Resumed { .. } => match event {
+                Event::Resized(new_size) => match self.resize(new_size) {
+                    // Winit will automatically provide a RedrawRequested event after this event,
+                    // but the render loop must be restarted explicitly if it was stopped while
+                    // the window had zero size
+                    Some(resize) => vec![resize, Command::RequestRedraw],
+                    None => vec![],
+                },
+                Event::Occluded(occluded) => {
+                    self.occluded = occluded;
+                    debug!("Window occluded: {}", occluded);
+                    if occluded {
+                        vec![]
+                    } else {
+                        vec![Command::RequestRedraw]
+                    }
+                }
+                Event::RedrawRequested => {
+                    // Nothing is visible, so stop the render loop to save power. It is restarted
+                    // by the Resized or Occluded event once the window is visible again.
+                    if !self.can_render() {
+                        return vec![];
+                    }
+                    if self.defer_redraw {
+                        self.redraw_deferred = true;
+                        vec![Command::Render]
+                    } else {
+                        vec![Command::Render, Command::RequestRedraw]
+                    }
+                }
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct TestWindow(PhysicalSize<u32>);
+
+    impl LifecycleWindow for TestWindow {
+        fn inner_size(&self) -> PhysicalSize<u32> {
+            self.0
+        }
+    }
+
+    type TestLifecycle = Lifecycle<TestWindow, &'static str>;
+    type TestCommand = Command<TestWindow, &'static str>;
+
+    const WINDOW: TestWindow = TestWindow(PhysicalSize::new(800, 600));
+
+    /// Simulated event source which feeds events and records the commands they produce
+    struct Harness {
+        lifecycle: TestLifecycle,
+    }
+
+    impl Harness {
+        fn new(defer_redraw: bool) -> Self {
+            Self {
+                lifecycle: Lifecycle::new(defer_redraw),
+            }
+        }
+
+        /// Start up like a native platform, where the context is created synchronously
+        fn started(defer_redraw: bool) -> Self {
+            let mut harness = Self::new(defer_redraw);
+            harness.send(Event::Resumed);
+            harness.send(Event::Initialized {
+                window: WINDOW,
+                context: "context",
+            });
+            harness
+        }
+
+        fn send(&mut self, event: Event<TestWindow, &'static str>) -> Vec<TestCommand> {
+            self.lifecycle.handle(event)
+        }
+
+        fn is_resumed(&self) -> bool {
+            matches!(self.lifecycle.state(), State::Resumed { .. })
+        }
+    }
+
+    #[test]
+    fn native_startup() {
+        let mut harness = Harness::new(false);
+        assert_eq!(harness.send(Event::Resumed), vec![Command::CreateContext]);
+        assert_eq!(*harness.lifecycle.state(), State::Initializing);
+        assert_eq!(
+            harness.send(Event::Initialized {
+                window: WINDOW,
+                context: "context",
+            }),
+            vec![Command::RequestRedraw]
+        );
+        assert_eq!(
+            *harness.lifecycle.state(),
+            State::Resumed {
+                window: WINDOW,
+                context: "context"
+            }
+        );
+        assert_eq!(
+            harness.send(Event::RedrawRequested),
+            vec![Command::Render, Command::RequestRedraw]
+        );
+    }
+
+    #[test]
+    fn repeated_resume_is_ignored() {
+        let mut harness = Harness::new(false);
+        harness.send(Event::Resumed);
+        assert_eq!(harness.send(Event::Resumed), vec![]);
+        assert_eq!(*harness.lifecycle.state(), State::Initializing);
+        harness.send(Event::Initialized {
+            window: WINDOW,
+            context: "context",
+        });
+        assert_eq!(harness.send(Event::Resumed), vec![]);
+        assert!(harness.is_resumed());
+    }
+
+    #[test]
+    fn web_resize_during_initialization_is_applied_when_initialized() {
+        let mut harness = Harness::new(false);
+        harness.send(Event::Resumed);
+        assert_eq!(
+            harness.send(Event::Resized(PhysicalSize::new(640, 480))),
+            vec![]
+        );
+        assert_eq!(
+            harness.send(Event::Resized(PhysicalSize::new(1024, 768))),
+            vec![]
+        );
+        assert_eq!(harness.send(Event::RedrawRequested), vec![]);
+        assert_eq!(
+            harness.lifecycle.pending_resize(),
+            Some(PhysicalSize::new(1024, 768))
+        );
+
+        assert_eq!(
+            harness.send(Event::Initialized {
+                window: WINDOW,
+                context: "context",
+            }),
+            vec![
+                Command::Resize(PhysicalSize::new(1024, 768)),
+                Command::RequestRedraw
+            ]
+        );
+        assert_eq!(harness.lifecycle.pending_resize(), None);
+    }
+
+    #[test]
+    fn web_zero_size_during_initialization_stops_rendering() {
+        let mut harness = Harness::new(false);
+        harness.send(Event::Resumed);
+        harness.send(Event::Resized(PhysicalSize::new(0, 0)));
+        assert_eq!(
+            harness.send(Event::Initialized {
+                window: WINDOW,
+                context: "context",
+            }),
+            vec![Command::RequestRedraw]
+        );
+        assert!(!harness.lifecycle.can_render());
+        assert_eq!(harness.send(Event::RedrawRequested), vec![]);
+    }
+
+    #[test]
+    fn ios_redraw_is_deferred_until_about_to_wait() {
+        let mut harness = Harness::started(true);
+        assert_eq!(harness.send(Event::RedrawRequested), vec![Command::Render]);
+        assert_eq!(
+            harness.send(Event::AboutToWait),
+            vec![Command::RequestRedraw]
+        );
+        assert_eq!(harness.send(Event::AboutToWait), vec![]);
+    }
+
+    #[test]
+    fn zero_size_stops_and_restarts_rendering() {
+        let mut harness = Harness::started(false);
+        assert_eq!(
+            harness.send(Event::Resized(PhysicalSize::new(0, 0))),
+            vec![]
+        );
+        assert_eq!(harness.send(Event::RedrawRequested), vec![]);
+        assert_eq!(
+            harness.send(Event::Resized(PhysicalSize::new(320, 200))),
+            vec![
+                Command::Resize(PhysicalSize::new(320, 200)),
+                Command::RequestRedraw
+            ]
+        );
+        assert_eq!(
+            harness.send(Event::RedrawRequested),
+            vec![Command::Render, Command::RequestRedraw]
+        );
+    }
+
+    #[test]
+    fn occlusion_stops_and_restarts_rendering() {
+        let mut harness = Harness::started(false);
+        assert_eq!(harness.send(Event::Occluded(true)), vec![]);
+        assert_eq!(harness.send(Event::RedrawRequested), vec![]);
+        assert_eq!(
+            harness.send(Event::Occluded(false)),
+            vec![Command::RequestRedraw]
+        );
+        assert_eq!(
+            harness.send(Event::RedrawRequested),
+            vec![Command::Render, Command::RequestRedraw]
+        );
+    }
+
+    #[test]
+    fn suspend_keeps_context_and_resume_recreates_surface() {
+        let mut harness = Harness::started(false);
+        for _ in 0..2 {
+            assert_eq!(harness.send(Event::Suspended), vec![Command::DropSurface]);
+            assert_eq!(
+                *harness.lifecycle.state(),
+                State::Suspended {
+                    window: WINDOW,
+                    context: "context"
+                }
+            );
+            assert_eq!(harness.send(Event::Suspended), vec![]);
+            assert_eq!(harness.send(Event::RedrawRequested), vec![]);
+
+            assert_eq!(
+                harness.send(Event::Resumed),
+                vec![
+                    Command::CreateSurface,
+                    Command::Resize(WINDOW.0),
+                    Command::RequestRedraw
+                ]
+            );
+            assert!(harness.is_resumed());
+        }
+    }
+
+    #[test]
+    fn suspend_before_initialized_is_ignored() {
+        let mut harness = Harness::new(false);
+        assert_eq!(harness.send(Event::Suspended), vec![]);
+        assert_eq!(*harness.lifecycle.state(), State::Uninitialized);
+        harness.send(Event::Resumed);
+        assert_eq!(harness.send(Event::Suspended), vec![]);
+        assert_eq!(*harness.lifecycle.state(), State::Initializing);
+    }
+
+    #[test]
+    fn device_lost_rebuilds_context() {
+        let mut harness = Harness::started(false);
+        assert_eq!(
+            harness.send(Event::DeviceLost),
+            vec![Command::RebuildContext {
+                window: WINDOW,
+                context: "context"
+            }]
+        );
+        assert_eq!(*harness.lifecycle.state(), State::Initializing);
+        assert_eq!(harness.send(Event::DeviceLost), vec![]);
+        assert_eq!(
+            harness.send(Event::Initialized {
+                window: WINDOW,
+                context: "new context",
+            }),
+            vec![Command::RequestRedraw]
+        );
+        assert!(harness.is_resumed());
+    }
+
+    #[test]
+    fn device_lost_while_suspended_rebuilds_when_resumed() {
+        let mut harness = Harness::started(false);
+        harness.send(Event::Suspended);
+        assert_eq!(harness.send(Event::DeviceLost), vec![]);
+        assert_eq!(
+            harness.send(Event::Resumed),
+            vec![Command::RebuildContext {
+                window: WINDOW,
+                context: "context"
+            }]
+        );
+        assert_eq!(*harness.lifecycle.state(), State::Initializing);
+    }
+
+    #[test]
+    fn surface_format_changed_rebuilds_context() {
+        let mut harness = Harness::started(false);
+        harness.send(Event::Suspended);
+        harness.send(Event::Resumed);
+        assert_eq!(
+            harness.send(Event::SurfaceFormatChanged),
+            vec![Command::RebuildContext {
+                window: WINDOW,
+                context: "context"
+            }]
+        );
+        assert_eq!(*harness.lifecycle.state(), State::Initializing);
+        assert_eq!(harness.send(Event::SurfaceFormatChanged), vec![]);
+    }
+
+    #[test]
+    fn close_exits_in_every_state() {
+        let mut harness = Harness::new(false);
+        assert_eq!(harness.send(Event::CloseRequested), vec![Command::Exit]);
+        harness.send(Event::Resumed);
+        assert_eq!(harness.send(Event::CloseRequested), vec![Command::Exit]);
+        harness.send(Event::Initialized {
+            window: WINDOW,
+            context: "context",
+        });
+        assert_eq!(harness.send(Event::CloseRequested), vec![Command::Exit]);
+        harness.send(Event::Suspended);
+        assert_eq!(harness.send(Event::CloseRequested), vec![Command::Exit]);
+    }
+}
@@ -0,0 +1,391 @@
+//! Light sources
+//!
+//! Lights are positioned in view space, where the camera is at the origin looking down the
+//! negative Z axis, so they stay fixed relative to the viewer while the scene rotates.
+
+use glam::Vec3;
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a light is and which way it shines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// An infinitely distant light, like the sun, shining along `direction`
+    Directional { direction: Vec3 },
+    /// A light shining equally in every direction from `position`
+    Point { position: Vec3 },
+    /// A light shining from `position` in a cone around `direction`. Surfaces are fully lit within
+    /// `inner_angle` of the axis and the light fades out towards `outer_angle`. Angles are in
+    /// degrees.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl LightKind {
+    pub const NAMES: [&str; 3] = ["directional", "point", "spot"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Directional { .. } => "directional",
+            Self::Point { .. } => "point",
+            Self::Spot { .. } => "spot",
+        }
+    }
+}
+
+/// How a point or spot light's intensity falls off with distance `d`, as
+/// `1 / (constant + linear * d + quadratic * d²)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    /// The light's intensity is the same at every distance
+    pub const NONE: Self = Self {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// A light source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    /// Linear RGB color
+    pub color: Vec3,
+    /// Scales the color
+    pub intensity: f32,
+    /// Ignored by directional lights
+    pub attenuation: Attenuation,
+}
+
+impl Light {
+    /// A white light without attenuation
+    pub fn new(kind: LightKind) -> Self {
+        Self {
+            kind,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+        }
+    }
+
+    /// The light kmscube uses, a white point light above and to the right of the camera
+    pub fn kmscube() -> Self {
+        Self::new(LightKind::Point {
+            position: Vec3::new(2.0, 2.0, 20.0),
+        })
+    }
+
+    /// A light of `name`'s kind with default parameters, or `None` if the name is unknown
+    pub fn from_name(name: &str) -> Option<Self> {
+        let kind = match name {
+            "directional" => LightKind::Directional {
+                direction: Vec3::NEG_Z,
+            },
+            "point" => LightKind::Point {
+                position: Vec3::new(2.0, 2.0, 20.0),
+            },
+            "spot" => LightKind::Spot {
+                position: Vec3::new(0.0, 0.0, 5.0),
+                direction: Vec3::NEG_Z,
+                inner_angle: 20.0,
+                outer_angle: 30.0,
+            },
+            _ => return None,
+        };
+        Some(Self::new(kind))
+    }
+
+    /// Check that the parameters describe a light which can be rendered
+    pub fn validate(&self) -> Result<(), ParseLightError> {
+        let finite = |name: &str, value: Vec3| {
+            if value.is_finite() {
+                Ok(())
+            } else {
+                Err(ParseLightError(format!("{name} must be finite")))
+            }
+        };
+        let direction = |value: Vec3| {
+            finite("direction", value)?;
+            if value.length_squared() > 0.0 {
+                Ok(())
+            } else {
+                Err(ParseLightError("direction must not be zero".to_string()))
+            }
+        };
+        match self.kind {
+            LightKind::Directional { direction: value } => direction(value)?,
+            LightKind::Point { position } => finite("position", position)?,
+            LightKind::Spot {
+                position,
+                direction: value,
+                inner_angle,
+                outer_angle,
+            } => {
+                finite("position", position)?;
+                direction(value)?;
+                if !(0.0 <= inner_angle && inner_angle < outer_angle && outer_angle <= 90.0) {
+                    return Err(ParseLightError(format!(
+                        "angles must satisfy 0 <= inner-angle < outer-angle <= 90, got \
+                         {inner_angle} and {outer_angle}"
+                    )));
+                }
+            }
+        }
+        if !(self.color.is_finite() && self.color.min_element() >= 0.0) {
+            return Err(ParseLightError(format!(
+                "color components must be non-negative, got {}",
+                format_vec3(self.color)
+            )));
+        }
+        if !(self.intensity.is_finite() && self.intensity >= 0.0) {
+            return Err(ParseLightError(format!(
+                "intensity must be non-negative, got {}",
+                self.intensity
+            )));
+        }
+        let Attenuation {
+            constant,
+            linear,
+            quadratic,
+        } = self.attenuation;
+        let coefficients = Vec3::new(constant, linear, quadratic);
+        if !(coefficients.is_finite()
+            && coefficients.min_element() >= 0.0
+            && coefficients.max_element() > 0.0)
+        {
+            return Err(ParseLightError(format!(
+                "attenuation coefficients must be non-negative and not all zero, got {}",
+                format_vec3(coefficients)
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Light {
+    type Err = ParseLightError;
+
+    /// Parse `KIND[:KEY=VALUE,...]`, where omitted parameters have their default values and
+    /// vectors are written as `X/Y/Z`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, parameters) = value.split_once(':').unwrap_or((value, ""));
+        let mut light = Self::from_name(name).ok_or_else(|| {
+            ParseLightError(format!(
+                "unknown light '{name}', expected one of {}",
+                LightKind::NAMES.join(", ")
+            ))
+        })?;
+        for parameter in parameters
+            .split(',')
+            .filter(|parameter| !parameter.is_empty())
+        {
+            let (key, value) = parameter
+                .split_once('=')
+                .ok_or_else(|| ParseLightError(format!("expected KEY=VALUE, got '{parameter}'")))?;
+            let invalid = || ParseLightError(format!("invalid {key} '{value}'"));
+            let number = || value.parse::<f32>().map_err(|_| invalid());
+            let vector = || parse_vec3(value).ok_or_else(invalid);
+            match (&mut light.kind, key) {
+                (_, "color") => light.color = vector()?,
+                (_, "intensity") => light.intensity = number()?,
+                (LightKind::Point { .. } | LightKind::Spot { .. }, "attenuation") => {
+                    let [constant, linear, quadratic] = vector()?.to_array();
+                    light.attenuation = Attenuation {
+                        constant,
+                        linear,
+                        quadratic,
+                    };
+                }
+                (LightKind::Point { position } | LightKind::Spot { position, .. }, "position") => {
+                    *position = vector()?
+                }
+                (
+                    LightKind::Directional { direction } | LightKind::Spot { direction, .. },
+                    "direction",
+                ) => *direction = vector()?,
+                (LightKind::Spot { inner_angle, .. }, "inner-angle") => *inner_angle = number()?,
+                (LightKind::Spot { outer_angle, .. }, "outer-angle") => *outer_angle = number()?,
+                _ => {
+                    return Err(ParseLightError(format!(
+                        "{name} light has no parameter '{key}'"
+                    )));
+                }
+            }
+        }
+        light.validate()?;
+        Ok(light)
+    }
+}
+
+impl fmt::Display for Light {
+    /// Format as a specification which parses back to the same light
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.kind.name())?;
+        match self.kind {
+            LightKind::Directional { direction } => {
+                write!(f, "direction={},", format_vec3(direction))?;
+            }
+            LightKind::Point { position } => write!(f, "position={},", format_vec3(position))?,
+            LightKind::Spot {
+                position,
+                direction,
+                inner_angle,
+                outer_angle,
+            } => write!(
+                f,
+                "position={},direction={},inner-angle={inner_angle},outer-angle={outer_angle},",
+                format_vec3(position),
+                format_vec3(direction)
+            )?,
+        }
+        write!(
+            f,
+            "color={},intensity={}",
+            format_vec3(self.color),
+            self.intensity
+        )?;
+        if !matches!(self.kind, LightKind::Directional { .. }) {
+            let Attenuation {
+                constant,
+                linear,
+                quadratic,
+            } = self.attenuation;
+            write!(f, ",attenuation={constant}/{linear}/{quadratic}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The error returned when a light specification is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLightError(String);
+
+impl fmt::Display for ParseLightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseLightError {}
+
+/// Parse `X/Y/Z`, or a single value used for all three components
+pub fn parse_vec3(value: &str) -> Option<Vec3> {
+    let components = value
+        .split('/')
+        .map(|component| component.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match components[..] {
+        [value] => Some(Vec3::splat(value)),
+        [x, y, z] => Some(Vec3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+fn format_vec3(value: Vec3) -> String {
+    format!("{}/{}/{}", value.x, value.y, value.z)
+}
+
+/// The lights illuminating a scene
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lighting {
+    /// Linear RGB light reaching every surface regardless of its orientation, so surfaces facing
+    /// away from every light are not completely black
+    pub ambient: Vec3,
+    /// Lights beyond the first `MAX_LIGHTS` are ignored
+    pub lights: Vec<Light>,
+}
+
+impl Lighting {
+    /// The number of lights the renderer supports
+    pub const MAX_LIGHTS: usize = 8;
+}
+
+impl Default for Lighting {
+    /// kmscube's lighting, a single white point light without ambient light
+    fn default() -> Self {
+        Self {
+            ambient: Vec3::ZERO,
+            lights: vec![Light::kmscube()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_defaults() {
+        assert_eq!("point".parse::<Light>().unwrap(), Light::kmscube());
+        for name in LightKind::NAMES {
+            let light = name.parse::<Light>().unwrap();
+            assert_eq!(light.kind.name(), name);
+            assert_eq!(light.to_string().parse::<Light>().unwrap(), light);
+        }
+    }
+
+    #[test]
+    fn parse_parameters() {
+        let light = "spot:position=1/2/3,direction=0/-1/0,outer-angle=45,color=1/0.5/0,\
+                     intensity=2,attenuation=1/0.1/0.01"
+            .parse::<Light>()
+            .unwrap();
+        assert_eq!(
+            light,
+            Light {
+                kind: LightKind::Spot {
+                    position: Vec3::new(1.0, 2.0, 3.0),
+                    direction: Vec3::NEG_Y,
+                    inner_angle: 20.0,
+                    outer_angle: 45.0,
+                },
+                color: Vec3::new(1.0, 0.5, 0.0),
+                intensity: 2.0,
+                attenuation: Attenuation {
+                    constant: 1.0,
+                    linear: 0.1,
+                    quadratic: 0.01,
+                },
+            }
+        );
+        assert_eq!(light.to_string().parse::<Light>().unwrap(), light);
+        assert_eq!(
+            "directional:color=0.5".parse::<Light>().unwrap().color,
+            Vec3::splat(0.5)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for spec in [
+            "area",
+            "point:position=1/2",
+            "point:position",
+            "point:inner-angle=10",
+            "directional:attenuation=1/0/0",
+            "directional:direction=0/0/0",
+            "spot:inner-angle=40",
+            "point:color=-1/0/0",
+            "point:intensity=nan",
+            "point:attenuation=0/0/0",
+        ] {
+            assert!(spec.parse::<Light>().is_err(), "{spec}");
+        }
+    }
+}
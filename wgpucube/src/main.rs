@@ -1,37 +1,215 @@
 #![cfg(not(target_os = "android"))]
 
-mod app;
-mod cube;
-#[cfg(feature = "egui")]
-mod egui;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
 
-use app::{App, WgpuEvent};
+use std::process::ExitCode;
 use tracing::info;
+use wgpucube::{App, Options, WgpuEvent};
 use winit::event_loop::EventLoop;
 
-fn main() {
+fn main() -> ExitCode {
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             wasm_tracing::set_as_global_default();
+            let options = Options::default();
+            info!("Starting wgpucube");
         } else {
-            let subscriber = tracing_subscriber::FmtSubscriber::new();
+            let matches = cli::command().get_matches();
+            let subscriber = tracing_subscriber::FmtSubscriber::builder()
+                .with_max_level(*matches.get_one::<tracing::Level>("log-level").unwrap())
+                .finish();
             tracing::subscriber::set_global_default(subscriber).unwrap();
+            info!("Starting wgpucube");
+
+            let mut options = cli::options(&matches);
+            // Listing adapters doesn't need any of the inputs, which may be slow or fail to load
+            if matches.get_flag("list-adapters") {
+                list_adapters(&options);
+                return ExitCode::SUCCESS;
+            }
+            if let Some(path) = matches.get_one::<std::path::PathBuf>("model") {
+                let normals = *matches.get_one::<wgpucube::mesh::Normals>("normals").unwrap();
+                match load_model(path, normals) {
+                    Ok(scene) => options.scene = Some(std::sync::Arc::new(scene)),
+                    Err(error) => {
+                        eprintln!("Error: Unable to load {}: {error}", path.display());
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            let textures: Vec<_> = matches
+                .get_many::<std::path::PathBuf>("texture")
+                .unwrap_or_default()
+                .collect();
+            if !textures.is_empty() || matches.get_flag("textured") {
+                match load_textured_cube(&textures) {
+                    Ok(scene) => options.scene = Some(std::sync::Arc::new(scene)),
+                    Err(error) => {
+                        eprintln!("Error: {error}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            if matches.contains_id("yuv") {
+                match load_yuv_cube(&matches) {
+                    Ok(scene) => options.scene = Some(std::sync::Arc::new(scene)),
+                    Err(error) => {
+                        eprintln!("Error: {error}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            if let Some(path) = matches.get_one::<std::path::PathBuf>("shadertoy") {
+                match wgpucube::shadertoy::Shadertoy::load(path) {
+                    Ok(shadertoy) => options.shadertoy = Some(std::sync::Arc::new(shadertoy)),
+                    Err(error) => {
+                        eprintln!("Error: Unable to load {}: {error}", path.display());
+                        return ExitCode::FAILURE;
+                    }
+                }
+            }
+            if matches.get_flag("headless") {
+                return match run_headless(&matches, &options) {
+                    Ok(()) => ExitCode::SUCCESS,
+                    Err(error) => {
+                        eprintln!("Error: {error}");
+                        ExitCode::FAILURE
+                    }
+                };
+            }
         }
     }
 
-    info!("Starting wgpucube");
-
     let event_loop = EventLoop::<WgpuEvent>::with_user_event().build().unwrap();
     let event_loop_proxy = event_loop.create_proxy();
     #[cfg_attr(target_arch = "wasm32", expect(unused_mut))]
-    let mut app = App::new(event_loop_proxy);
+    let mut app = App::new(event_loop_proxy, options);
 
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
             use winit::platform::web::EventLoopExtWebSys;
             event_loop.spawn_app(app);
+            ExitCode::SUCCESS
         } else {
             event_loop.run_app(&mut app).unwrap();
+            match app.error() {
+                Some(error) => {
+                    eprintln!("Error: {error}");
+                    ExitCode::FAILURE
+                }
+                None => ExitCode::SUCCESS,
+            }
+        }
+    }
+}
+
+/// Load a model file, choosing the format by its extension
+#[cfg(not(target_arch = "wasm32"))]
+fn load_model(
+    path: &std::path::Path,
+    normals: wgpucube::mesh::Normals,
+) -> Result<wgpucube::scene::Scene, Box<dyn std::error::Error>> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let scene: wgpucube::scene::Scene = match extension.as_deref() {
+        Some("obj") => wgpucube::obj::load(path)?.into(),
+        Some("gltf" | "glb") => wgpucube::gltf::load(path)?,
+        Some("stl") => wgpucube::stl::load(path, normals)?.into(),
+        Some("ply") => wgpucube::ply::load(path, normals)?.into(),
+        _ => return Err("Unknown model format, expected .obj, .gltf, .glb, .stl, or .ply".into()),
+    };
+    // Flipped triangles are culled, but the rest of the model still renders
+    for (index, mesh) in scene.meshes().iter().enumerate() {
+        if let Err(error) = mesh.check_winding() {
+            tracing::warn!("Mesh {} of {}: {}", index, path.display(), error);
         }
     }
+    Ok(scene)
+}
+
+/// Load one texture for every face of the cube or one per face, or use the built-in texture if
+/// `paths` is empty
+#[cfg(not(target_arch = "wasm32"))]
+fn load_textured_cube(paths: &[&std::path::PathBuf]) -> Result<wgpucube::scene::Scene, String> {
+    use wgpucube::scene::{Scene, Texture};
+
+    let textures = match paths.len() {
+        0 => vec![wgpucube::texture::builtin()],
+        1 | 6 => paths
+            .iter()
+            .map(|path| {
+                Texture::load(path)
+                    .map_err(|error| format!("Unable to load {}: {error}", path.display()))
+            })
+            .collect::<Result<_, _>>()?,
+        count => return Err(format!("Expected 1 or 6 textures, got {count}")),
+    };
+    Ok(Scene::textured_cube(textures))
+}
+
+/// Texture the cube with the first frame of `--yuv-file`, or with a test pattern
+#[cfg(not(target_arch = "wasm32"))]
+fn load_yuv_cube(matches: &clap::ArgMatches) -> Result<wgpucube::scene::Scene, String> {
+    use wgpucube::scene::Scene;
+    use wgpucube::yuv::{YuvColorSpace, YuvFormat, YuvFrame};
+    use winit::dpi::PhysicalSize;
+
+    let format = *matches.get_one::<YuvFormat>("yuv").unwrap();
+    let color_space = YuvColorSpace {
+        matrix: *matches.get_one("yuv-matrix").unwrap(),
+        range: *matches.get_one("yuv-range").unwrap(),
+    };
+    let size = matches
+        .get_one::<PhysicalSize<u32>>("yuv-size")
+        .copied()
+        .unwrap_or(PhysicalSize::new(256, 256));
+    let frame = match matches.get_one::<std::path::PathBuf>("yuv-file") {
+        Some(path) => YuvFrame::load(path, format, size.width, size.height, color_space)
+            .map_err(|error| format!("Unable to load {}: {error}", path.display()))?,
+        None => YuvFrame::test_pattern(format, size.width, size.height, color_space),
+    };
+    Ok(Scene::textured_cube(vec![frame.into()]))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_headless(
+    matches: &clap::ArgMatches,
+    options: &Options,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::path::PathBuf;
+    use wgpucube::headless::Headless;
+    use winit::dpi::PhysicalSize;
+
+    let frame = *matches.get_one::<u64>("frame").unwrap();
+    let size = options.window_size.unwrap_or(PhysicalSize::new(800, 600));
+    let out = matches.get_one::<PathBuf>("out").unwrap();
+
+    let mut headless = pollster::block_on(Headless::new(size, options))?;
+    headless.render(frame).write_png(out)?;
+    info!("Wrote frame {} to {}", frame, out.display());
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn list_adapters(options: &Options) {
+    let adapters = wgpucube::adapter::enumerate_adapters(options.backends);
+    if adapters.is_empty() {
+        println!("No adapters found");
+    }
+    for (index, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!("Adapter #{index}: {}", info.name);
+        println!("  Backend: {:?}", info.backend);
+        println!("  Device type: {:?}", info.device_type);
+        println!(
+            "  Vendor: {:#06x}, device: {:#06x}",
+            info.vendor, info.device
+        );
+        println!("  Driver: {} {}", info.driver, info.driver_info);
+        println!("  Features: {:?}", adapter.features());
+        println!("  Limits: {:#?}", adapter.limits());
+    }
 }
@@ -0,0 +1,711 @@
+//! Indexed triangle meshes
+//!
+//! A `Mesh` holds per-vertex positions, colors, normals, and optional texture coordinates along
+//! with triangle list indices. Meshes are validated when they are created, so the renderer can
+//! upload them without further checks.
+
+use crate::color::srgb_to_linear;
+use glam::Vec3;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Normals must have a length within this distance of 1.0
+const NORMAL_LENGTH_TOLERANCE: f32 = 1e-3;
+
+/// Triangle list indices, stored in the smallest format that can address every vertex
+#[derive(Debug, Clone, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Store `indices` as u16 if every index fits, otherwise as u32
+    pub fn from_u32(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|&index| index <= u16::MAX as u32) {
+            Self::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Self::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U16(indices) => indices.len(),
+            Self::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = u32> + '_> {
+        match self {
+            Self::U16(indices) => Box::new(indices.iter().map(|&index| index as u32)),
+            Self::U32(indices) => Box::new(indices.iter().copied()),
+        }
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Self::U16(_) => wgpu::IndexFormat::Uint16,
+            Self::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::U16(indices) => bytemuck::cast_slice(indices),
+            Self::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+/// Reason a mesh failed validation
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshError {
+    /// A vertex attribute doesn't have one value per position
+    AttributeCount {
+        attribute: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// The index count is not a multiple of 3
+    IncompleteTriangle { index_count: usize },
+    /// An index refers to a vertex which doesn't exist
+    IndexOutOfBounds { index: u32, vertex_count: usize },
+    /// A normal is not unit length
+    InvalidNormal { vertex: usize, length: f32 },
+    /// Two triangles sharing an edge traverse it in the same direction, so one of them faces the
+    /// wrong way. Only reported by `Mesh::check_winding`.
+    InconsistentWinding { triangles: [usize; 2] },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AttributeCount {
+                attribute,
+                expected,
+                actual,
+            } => write!(f, "Expected {expected} {attribute}, found {actual}"),
+            Self::IncompleteTriangle { index_count } => {
+                write!(f, "Index count {index_count} is not a multiple of 3")
+            }
+            Self::IndexOutOfBounds {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "Index {index} is out of bounds for {vertex_count} vertices"
+            ),
+            Self::InvalidNormal { vertex, length } => {
+                write!(f, "Normal of vertex {vertex} has length {length}")
+            }
+            Self::InconsistentWinding { triangles: [a, b] } => {
+                write!(f, "Triangles {a} and {b} have inconsistent winding")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Indexed triangle list with per-vertex attributes
+///
+/// Triangles are wound counter-clockwise when viewed from the front. Colors are linear, not sRGB
+/// encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Option<Vec<[f32; 2]>>,
+    indices: Indices,
+}
+
+impl Mesh {
+    /// Create a mesh from separate attribute arrays, validating it
+    pub fn new(
+        positions: Vec<[f32; 3]>,
+        colors: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        uvs: Option<Vec<[f32; 2]>>,
+        indices: Indices,
+    ) -> Result<Self, MeshError> {
+        let mesh = Self {
+            positions,
+            colors,
+            normals,
+            uvs,
+            indices,
+        };
+        mesh.validate()?;
+        Ok(mesh)
+    }
+
+    pub fn builder() -> MeshBuilder {
+        MeshBuilder::default()
+    }
+
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    pub fn colors(&self) -> &[[f32; 3]] {
+        &self.colors
+    }
+
+    pub fn normals(&self) -> &[[f32; 3]] {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> Option<&[[f32; 2]]> {
+        self.uvs.as_deref()
+    }
+
+    pub fn indices(&self) -> &Indices {
+        &self.indices
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Iterate over the vertex indices of each triangle
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        let mut indices = self.indices.iter();
+        std::iter::from_fn(move || Some([indices.next()?, indices.next()?, indices.next()?]))
+    }
+
+    /// Check that the attributes, indices, and normals are consistent
+    pub fn validate(&self) -> Result<(), MeshError> {
+        let vertex_count = self.positions.len();
+        let attribute_counts = [
+            ("colors", Some(self.colors.len())),
+            ("normals", Some(self.normals.len())),
+            ("uvs", self.uvs.as_ref().map(Vec::len)),
+        ];
+        for (attribute, count) in attribute_counts {
+            if let Some(actual) = count
+                && actual != vertex_count
+            {
+                return Err(MeshError::AttributeCount {
+                    attribute,
+                    expected: vertex_count,
+                    actual,
+                });
+            }
+        }
+
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(MeshError::IncompleteTriangle {
+                index_count: self.indices.len(),
+            });
+        }
+        if let Some(index) = self
+            .indices
+            .iter()
+            .find(|&index| index as usize >= vertex_count)
+        {
+            return Err(MeshError::IndexOutOfBounds {
+                index,
+                vertex_count,
+            });
+        }
+
+        for (vertex, normal) in self.normals.iter().enumerate() {
+            let length = Vec3::from(*normal).length();
+            // NaN lengths are rejected too
+            if (length - 1.0).abs() > NORMAL_LENGTH_TOLERANCE || length.is_nan() {
+                return Err(MeshError::InvalidNormal { vertex, length });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every edge shared by exactly two triangles is traversed in opposite directions
+    ///
+    /// This is not part of `validate`, since models with some flipped triangles still render.
+    /// Edges shared by more than two triangles (non-manifold geometry) can't be oriented
+    /// consistently and are ignored.
+    pub fn check_winding(&self) -> Result<(), MeshError> {
+        // Each use of an undirected edge, keyed by (min, max) vertex index, with the triangle using
+        // it and whether the triangle traverses it from min to max. Sorting groups the uses of
+        // each edge together in triangle order.
+        let mut edges: Vec<((u32, u32), usize, bool)> = Vec::with_capacity(self.indices.len());
+        for (triangle, [a, b, c]) in self.triangles().enumerate() {
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                if from != to {
+                    edges.push(((from.min(to), from.max(to)), triangle, from < to));
+                }
+            }
+        }
+        edges.sort_unstable();
+
+        let conflict = edges
+            .chunk_by(|(edge, ..), (other_edge, ..)| edge == other_edge)
+            .filter_map(|uses| match uses {
+                [(_, first, forward), (_, second, other_forward)]
+                    if forward == other_forward && first != second =>
+                {
+                    Some([*first, *second])
+                }
+                _ => None,
+            })
+            .min();
+        match conflict {
+            Some(triangles) => Err(MeshError::InconsistentWinding { triangles }),
+            None => Ok(()),
+        }
+    }
+
+    /// Center the mesh's bounding box on the origin and scale it uniformly so its largest
+    /// dimension is 2 units, matching the built-in cube
+    pub fn fit_to_cube(&mut self) {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for &position in &self.positions {
+            min = min.min(Vec3::from(position));
+            max = max.max(Vec3::from(position));
+        }
+        let center = (min + max) / 2.0;
+        let half_extent = ((max - min) / 2.0).max_element();
+        // A single point has no size to scale
+        let scale = if half_extent > 0.0 {
+            1.0 / half_extent
+        } else {
+            1.0
+        };
+        for position in &mut self.positions {
+            *position = ((Vec3::from(*position) - center) * scale).to_array();
+        }
+    }
+
+    /// The kmscube cube: 2 units wide, centered on the origin, with a different color at each
+    /// corner of each face. Each face is mapped to the whole texture, upright when viewed from
+    /// outside the cube.
+    pub fn cube() -> Self {
+        let mut builder = Self::builder();
+        for (face, normal) in CUBE_NORMALS.into_iter().enumerate() {
+            for (corner, uv) in CUBE_FACE_UVS.into_iter().enumerate() {
+                let vertex = face * 4 + corner;
+                builder.vertex_with_uv(
+                    CUBE_VERTICES[vertex],
+                    CUBE_COLORS[vertex].map(srgb_to_linear),
+                    normal,
+                    uv,
+                );
+            }
+            let base = face as u32 * 4;
+            builder.triangle([base, base + 1, base + 2]);
+            builder.triangle([base + 1, base + 3, base + 2]);
+        }
+        builder.build().unwrap()
+    }
+}
+
+/// How to generate normals for model formats which don't store them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normals {
+    /// Average the normals of the triangles sharing each vertex, for curved surfaces such as scans
+    #[default]
+    Smooth,
+    /// Give each triangle its own normal, for faceted surfaces such as CAD parts
+    Flat,
+}
+
+/// Deduplicates vertices with identical positions and colors, for formats which store each
+/// triangle's corners separately
+#[derive(Debug, Default)]
+pub(crate) struct Welder {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 3]>,
+    indices: HashMap<[u32; 6], u32>,
+}
+
+impl Welder {
+    /// Return the index of the vertex, adding it if it is new
+    pub(crate) fn vertex(&mut self, position: [f32; 3], color: [f32; 3]) -> u32 {
+        // Adding zero turns -0.0 into 0.0, so the two have the same bits
+        let [x, y, z] = position.map(|value| (value + 0.0).to_bits());
+        let [r, g, b] = color.map(|value| (value + 0.0).to_bits());
+        *self.indices.entry([x, y, z, r, g, b]).or_insert_with(|| {
+            self.positions.push(position);
+            self.colors.push(color);
+            self.positions.len() as u32 - 1
+        })
+    }
+
+    /// The unique positions and colors
+    pub(crate) fn into_vertices(self) -> (Vec<[f32; 3]>, Vec<[f32; 3]>) {
+        (self.positions, self.colors)
+    }
+}
+
+/// Build a mesh from shared vertex positions and colors, generating its normals
+pub(crate) fn mesh_with_normals(
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 3]>,
+    triangles: Vec<[u32; 3]>,
+    normals: Normals,
+) -> Result<Mesh, MeshError> {
+    match normals {
+        Normals::Smooth => {
+            if let Some(&index) = triangles
+                .iter()
+                .flatten()
+                .find(|&&index| index as usize >= positions.len())
+            {
+                return Err(MeshError::IndexOutOfBounds {
+                    index,
+                    vertex_count: positions.len(),
+                });
+            }
+            let normals = smooth_normals(
+                &positions,
+                triangles
+                    .iter()
+                    .map(|triangle| triangle.map(|index| index as usize)),
+            );
+            let indices = Indices::from_u32(triangles.into_flattened());
+            Mesh::new(positions, colors, normals, None, indices)
+        }
+        Normals::Flat => flat_mesh(&positions, &colors, None, &triangles),
+    }
+}
+
+/// Smooth normals for every position from the area-weighted normals of the triangles using it
+pub(crate) fn smooth_normals(
+    positions: &[[f32; 3]],
+    triangles: impl IntoIterator<Item = [usize; 3]>,
+) -> Vec<[f32; 3]> {
+    let mut sums = vec![Vec3::ZERO; positions.len()];
+    for triangle in triangles {
+        let [a, b, c] = triangle.map(|index| Vec3::from(positions[index]));
+        // The cross product's length is twice the triangle's area
+        let normal = (b - a).cross(c - a);
+        for index in triangle {
+            sums[index] += normal;
+        }
+    }
+    sums.into_iter()
+        // Positions only used by degenerate triangles get an arbitrary normal
+        .map(|sum| sum.try_normalize().unwrap_or(Vec3::Z).to_array())
+        .collect()
+}
+
+/// Build a mesh with a separate vertex per triangle corner, so each triangle can have its own
+/// normal
+pub(crate) fn flat_mesh(
+    positions: &[[f32; 3]],
+    colors: &[[f32; 3]],
+    uvs: Option<&[[f32; 2]]>,
+    triangles: &[[u32; 3]],
+) -> Result<Mesh, MeshError> {
+    if let Some(&index) = triangles
+        .iter()
+        .flatten()
+        .find(|&&index| index as usize >= positions.len())
+    {
+        return Err(MeshError::IndexOutOfBounds {
+            index,
+            vertex_count: positions.len(),
+        });
+    }
+    let mut builder = Mesh::builder();
+    for triangle in triangles {
+        let [a, b, c] = triangle.map(|index| Vec3::from(positions[index as usize]));
+        let normal = (b - a)
+            .cross(c - a)
+            .try_normalize()
+            .unwrap_or(Vec3::Z)
+            .to_array();
+        let corners = triangle.map(|index| {
+            let index = index as usize;
+            match uvs {
+                Some(uvs) => {
+                    builder.vertex_with_uv(positions[index], colors[index], normal, uvs[index])
+                }
+                None => builder.vertex(positions[index], colors[index], normal),
+            }
+        });
+        builder.triangle(corners);
+    }
+    builder.build()
+}
+
+/// Incrementally builds a `Mesh`
+///
+/// Either every vertex or no vertex must have texture coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Add a vertex without texture coordinates and return its index
+    pub fn vertex(&mut self, position: [f32; 3], color: [f32; 3], normal: [f32; 3]) -> u32 {
+        let index = self.positions.len() as u32;
+        self.positions.push(position);
+        self.colors.push(color);
+        self.normals.push(normal);
+        index
+    }
+
+    /// Add a vertex with texture coordinates and return its index
+    pub fn vertex_with_uv(
+        &mut self,
+        position: [f32; 3],
+        color: [f32; 3],
+        normal: [f32; 3],
+        uv: [f32; 2],
+    ) -> u32 {
+        self.uvs.push(uv);
+        self.vertex(position, color, normal)
+    }
+
+    /// Add a counter-clockwise triangle
+    pub fn triangle(&mut self, indices: [u32; 3]) -> &mut Self {
+        self.indices.extend(indices);
+        self
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Validate and build the mesh, using u16 indices if possible
+    pub fn build(self) -> Result<Mesh, MeshError> {
+        let uvs = (!self.uvs.is_empty()).then_some(self.uvs);
+        let indices = if self.positions.len() <= u16::MAX as usize + 1 {
+            Indices::from_u32(self.indices)
+        } else {
+            Indices::U32(self.indices)
+        };
+        Mesh::new(self.positions, self.colors, self.normals, uvs, indices)
+    }
+}
+
+// Each face lists its corners as bottom left, bottom right, top left, top right when viewed from
+// outside the cube
+const CUBE_VERTICES: [[f32; 3]; 24] = [
+    // Front
+    [-1.0, -1.0, 1.0],
+    [1.0, -1.0, 1.0],
+    [-1.0, 1.0, 1.0],
+    [1.0, 1.0, 1.0],
+    // Back
+    [1.0, -1.0, -1.0],
+    [-1.0, -1.0, -1.0],
+    [1.0, 1.0, -1.0],
+    [-1.0, 1.0, -1.0],
+    // Right
+    [1.0, -1.0, 1.0],
+    [1.0, -1.0, -1.0],
+    [1.0, 1.0, 1.0],
+    [1.0, 1.0, -1.0],
+    // Left
+    [-1.0, -1.0, -1.0],
+    [-1.0, -1.0, 1.0],
+    [-1.0, 1.0, -1.0],
+    [-1.0, 1.0, 1.0],
+    // Top
+    [-1.0, 1.0, 1.0],
+    [1.0, 1.0, 1.0],
+    [-1.0, 1.0, -1.0],
+    [1.0, 1.0, -1.0],
+    // Bottom
+    [-1.0, -1.0, -1.0],
+    [1.0, -1.0, -1.0],
+    [-1.0, -1.0, 1.0],
+    [1.0, -1.0, 1.0],
+];
+
+// Vertex colors are sRGB encoded and converted to linear when the mesh is built
+const CUBE_COLORS: [[f32; 3]; 24] = [
+    // Front
+    [0.0, 0.0, 1.0], // blue
+    [1.0, 0.0, 1.0], // magenta
+    [0.0, 1.0, 1.0], // cyan
+    [1.0, 1.0, 1.0], // white
+    // Back
+    [1.0, 0.0, 0.0], // red
+    [0.0, 0.0, 0.0], // black
+    [1.0, 1.0, 0.0], // yellow
+    [0.0, 1.0, 0.0], // green
+    // Right
+    [1.0, 0.0, 1.0], // magenta
+    [1.0, 0.0, 0.0], // red
+    [1.0, 1.0, 1.0], // white
+    [1.0, 1.0, 0.0], // yellow
+    // Left
+    [0.0, 0.0, 0.0], // black
+    [0.0, 0.0, 1.0], // blue
+    [0.0, 1.0, 0.0], // green
+    [0.0, 1.0, 1.0], // cyan
+    // Top
+    [0.0, 1.0, 1.0], // cyan
+    [1.0, 1.0, 1.0], // white
+    [0.0, 1.0, 0.0], // green
+    [1.0, 1.0, 0.0], // yellow
+    // Bottom
+    [0.0, 0.0, 0.0], // black
+    [1.0, 0.0, 0.0], // red
+    [0.0, 0.0, 1.0], // blue
+    [1.0, 0.0, 1.0], // magenta
+];
+
+// Texture coordinates of each face's corners. Images are stored top row first, so v increases
+// downwards.
+const CUBE_FACE_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 0.0]];
+
+const CUBE_NORMALS: [[f32; 3]; 6] = [
+    [0.0, 0.0, 1.0],  // Front
+    [0.0, 0.0, -1.0], // Back
+    [1.0, 0.0, 0.0],  // Right
+    [-1.0, 0.0, 0.0], // Left
+    [0.0, 1.0, 0.0],  // Top
+    [0.0, -1.0, 0.0], // Bottom
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: [f32; 3] = [0.0, 0.0, 1.0];
+    const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+    /// Unit square in the XY plane made of two triangles sharing the edge 1-2
+    fn square() -> MeshBuilder {
+        let mut builder = Mesh::builder();
+        builder.vertex([0.0, 0.0, 0.0], WHITE, UP);
+        builder.vertex([1.0, 0.0, 0.0], WHITE, UP);
+        builder.vertex([0.0, 1.0, 0.0], WHITE, UP);
+        builder.vertex([1.0, 1.0, 0.0], WHITE, UP);
+        builder
+    }
+
+    #[test]
+    fn cube_is_valid() {
+        let cube = Mesh::cube();
+        assert_eq!(cube.vertex_count(), 24);
+        assert_eq!(cube.triangle_count(), 12);
+        assert_eq!(cube.indices().format(), wgpu::IndexFormat::Uint16);
+        assert_eq!(cube.uvs().unwrap()[..4], CUBE_FACE_UVS);
+        assert_eq!(cube.validate(), Ok(()));
+        assert_eq!(cube.check_winding(), Ok(()));
+    }
+
+    #[test]
+    fn builder_produces_u16_or_u32_indices() {
+        let mut builder = square();
+        builder.triangle([0, 1, 2]).triangle([1, 3, 2]);
+        let mesh = builder.build().unwrap();
+        assert_eq!(mesh.indices(), &Indices::U16(vec![0, 1, 2, 1, 3, 2]));
+        assert_eq!(mesh.triangles().collect::<Vec<_>>(), [[0, 1, 2], [1, 3, 2]]);
+
+        let mut builder = Mesh::builder();
+        for i in 0..70_000 {
+            builder.vertex([i as f32, 0.0, 0.0], WHITE, UP);
+        }
+        builder.triangle([0, 1, 69_999]);
+        let mesh = builder.build().unwrap();
+        assert_eq!(mesh.indices().format(), wgpu::IndexFormat::Uint32);
+    }
+
+    #[test]
+    fn rejects_index_out_of_bounds() {
+        let mut builder = square();
+        builder.triangle([0, 1, 4]);
+        assert_eq!(
+            builder.build(),
+            Err(MeshError::IndexOutOfBounds {
+                index: 4,
+                vertex_count: 4
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_triangle() {
+        let result = Mesh::new(
+            vec![[0.0; 3]; 3],
+            vec![WHITE; 3],
+            vec![UP; 3],
+            None,
+            Indices::U16(vec![0, 1]),
+        );
+        assert_eq!(
+            result,
+            Err(MeshError::IncompleteTriangle { index_count: 2 })
+        );
+    }
+
+    #[test]
+    fn rejects_attribute_count_mismatch() {
+        let mut builder = square();
+        builder.vertex_with_uv([0.0; 3], WHITE, UP, [0.0, 0.0]);
+        assert_eq!(
+            builder.build(),
+            Err(MeshError::AttributeCount {
+                attribute: "uvs",
+                expected: 5,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_non_unit_normal() {
+        let mut builder = square();
+        builder.vertex([0.0; 3], WHITE, [0.0, 0.0, 2.0]);
+        assert_eq!(
+            builder.build(),
+            Err(MeshError::InvalidNormal {
+                vertex: 4,
+                length: 2.0
+            })
+        );
+
+        let mut builder = square();
+        builder.vertex([0.0; 3], WHITE, [f32::NAN; 3]);
+        assert!(matches!(
+            builder.build(),
+            Err(MeshError::InvalidNormal { vertex: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn reports_inconsistent_winding() {
+        // The second triangle is wound clockwise, so it traverses edge 1-2 in the same direction
+        let mut builder = square();
+        builder.triangle([0, 1, 2]).triangle([1, 2, 3]);
+        let mesh = builder.build().unwrap();
+        assert_eq!(
+            mesh.check_winding(),
+            Err(MeshError::InconsistentWinding { triangles: [0, 1] })
+        );
+    }
+
+    #[test]
+    fn ignores_non_manifold_edges() {
+        let mut builder = square();
+        builder.vertex([0.0, 0.0, 1.0], WHITE, UP);
+        builder
+            .triangle([0, 1, 2])
+            .triangle([1, 3, 2])
+            .triangle([1, 2, 4]);
+        assert_eq!(builder.build().unwrap().check_winding(), Ok(()));
+    }
+}
@@ -0,0 +1,11 @@
+// Fragment stage of a pass drawn with the vertex stage in `fullscreen.wgsl`
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@fragment
+fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, uv);
+}
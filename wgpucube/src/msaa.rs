@@ -0,0 +1,52 @@
+use tracing::warn;
+
+/// Sample counts which may be requested, in descending order
+const SAMPLE_COUNTS: [u32; 4] = [8, 4, 2, 1];
+
+/// Device features required to use every sample count supported by the adapter
+///
+/// Without `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES` only the counts guaranteed by the WebGPU
+/// specification (1 and 4) can be used, regardless of what the hardware supports.
+pub(crate) fn required_features(adapter: &wgpu::Adapter) -> wgpu::Features {
+    adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+}
+
+/// Returns the highest sample count no greater than `requested` which is supported by every format
+/// in `formats`
+pub(crate) fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    formats: &[wgpu::TextureFormat],
+    requested: u32,
+) -> u32 {
+    let adapter_specific = adapter
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    let supported = |count: u32| {
+        formats.iter().all(|format| {
+            let features = if adapter_specific {
+                adapter.get_texture_format_features(*format)
+            } else {
+                format.guaranteed_format_features(adapter.features())
+            };
+            let resolvable = count == 1
+                || format.is_depth_stencil_format()
+                || features
+                    .flags
+                    .contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+            features.flags.sample_count_supported(count) && resolvable
+        })
+    };
+
+    let sample_count = SAMPLE_COUNTS
+        .into_iter()
+        .filter(|count| *count <= requested)
+        .find(|count| supported(*count))
+        .unwrap_or(1);
+    if sample_count != requested {
+        warn!(
+            "MSAA sample count {} is not supported, using {}",
+            requested, sample_count
+        );
+    }
+    sample_count
+}
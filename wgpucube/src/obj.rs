@@ -0,0 +1,614 @@
+//! Wavefront OBJ and MTL model loading
+//!
+//! Supports vertex positions (with the common `v x y z r g b` vertex color extension), texture
+//! coordinates, normals, polygonal faces, and diffuse material colors. Other statements, such as
+//! groups, smoothing groups, lines, and other material properties, are ignored.
+//!
+//! Polygons are triangulated as fans, which is correct for the convex polygons exporters write.
+//! Normals missing from the file are generated by averaging the area-weighted normals of the
+//! faces sharing each position. Colors in OBJ and MTL files are display values and are treated
+//! as sRGB encoded.
+
+use crate::color::srgb_to_linear;
+use crate::mesh::{Indices, Mesh, MeshError, smooth_normals};
+use glam::Vec3;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use tracing::warn;
+
+/// Color of faces with no material and vertices with no vertex color
+const DEFAULT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+#[derive(Debug)]
+pub enum ObjError {
+    /// The OBJ file could not be read
+    Io(io::Error),
+    /// A statement could not be parsed. `file` is the MTL file name, or `None` for the OBJ file.
+    Syntax {
+        file: Option<String>,
+        line: usize,
+        message: String,
+    },
+    /// The file contains no faces
+    NoFaces,
+    /// The faces form an invalid mesh, for example because an index is out of bounds
+    Mesh(MeshError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Syntax {
+                file: Some(file),
+                line,
+                message,
+            } => write!(f, "{file} line {line}: {message}"),
+            Self::Syntax {
+                file: None,
+                line,
+                message,
+            } => write!(f, "line {line}: {message}"),
+            Self::NoFaces => write!(f, "The model has no faces"),
+            Self::Mesh(error) => write!(f, "Invalid mesh: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Mesh(error) => Some(error),
+            Self::Syntax { .. } | Self::NoFaces => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<MeshError> for ObjError {
+    fn from(error: MeshError) -> Self {
+        Self::Mesh(error)
+    }
+}
+
+/// Load an OBJ file and the MTL files it references, which are resolved relative to its directory
+///
+/// The model is centered and scaled to fit the built-in cube's bounds.
+pub fn load(path: impl AsRef<Path>) -> Result<Mesh, ObjError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    parse(&source, |name| {
+        std::fs::read_to_string(directory.join(name))
+    })
+}
+
+/// Parse OBJ source, calling `read_mtl` to read each referenced MTL file
+///
+/// MTL files which can't be read are skipped with a warning, leaving their materials undefined.
+/// Faces using undefined materials are drawn in the default color. The model is centered and
+/// scaled to fit the built-in cube's bounds.
+pub fn parse(
+    source: &str,
+    mut read_mtl: impl FnMut(&str) -> io::Result<String>,
+) -> Result<Mesh, ObjError> {
+    let mut parser = Parser::default();
+    for (line_index, line) in source.lines().enumerate() {
+        let syntax_error = |message: String| ObjError::Syntax {
+            file: None,
+            line: line_index + 1,
+            message,
+        };
+        let mut tokens = tokens(line);
+        if tokens.next() == Some("mtllib") {
+            // Errors in the MTL file are returned as they are, with the MTL file's name and line
+            let mut names = tokens.peekable();
+            if names.peek().is_none() {
+                return Err(syntax_error("Missing MTL file name".to_string()));
+            }
+            parser.mtllib(names, &mut read_mtl)?;
+        } else {
+            parser.statement(line).map_err(syntax_error)?;
+        }
+    }
+    let mut mesh = parser.build()?;
+    mesh.fit_to_cube();
+    Ok(mesh)
+}
+
+/// Parse MTL source into diffuse colors by material name
+fn parse_mtl(name: &str, source: &str) -> Result<HashMap<String, [f32; 3]>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<String> = None;
+    for (line_index, line) in source.lines().enumerate() {
+        let syntax_error = |message: String| ObjError::Syntax {
+            file: Some(name.to_string()),
+            line: line_index + 1,
+            message,
+        };
+        let mut tokens = tokens(line);
+        match tokens.next() {
+            Some("newmtl") => {
+                let material = rest(line, "newmtl")
+                    .ok_or_else(|| syntax_error("Missing material name".to_string()))?;
+                materials.insert(material.to_string(), DEFAULT_COLOR);
+                current = Some(material.to_string());
+            }
+            Some("Kd") => {
+                let color = floats::<3>(&mut tokens, "Kd").map_err(syntax_error)?;
+                let material = current
+                    .as_ref()
+                    .ok_or_else(|| syntax_error("Kd before newmtl".to_string()))?;
+                materials.insert(material.clone(), color.map(srgb_to_linear));
+            }
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+/// One corner of a face, with 0-based indices into the parsed attributes
+#[derive(Debug, Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// A triangle of a face, with the color of its material
+#[derive(Debug)]
+struct Triangle {
+    corners: [Corner; 3],
+    material_color: Option<[f32; 3]>,
+}
+
+/// Identifies a unique output vertex: position, texture coordinate, and normal indices, and the
+/// bits of its color
+type VertexKey = (usize, Option<usize>, Option<usize>, [u32; 3]);
+
+#[derive(Debug, Default)]
+struct Parser {
+    positions: Vec<[f32; 3]>,
+    vertex_colors: Vec<Option<[f32; 3]>>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    triangles: Vec<Triangle>,
+    materials: HashMap<String, [f32; 3]>,
+    material_color: Option<[f32; 3]>,
+}
+
+impl Parser {
+    /// Parse a statement other than `mtllib`
+    fn statement(&mut self, line: &str) -> Result<(), String> {
+        let mut tokens = tokens(line);
+        match tokens.next() {
+            Some("v") => {
+                let values = tokens
+                    .map(|token| float(token, "v"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (position, color) = match values[..] {
+                    // The optional w coordinate is only meaningful for rational curves
+                    [x, y, z] | [x, y, z, _] => ([x, y, z], None),
+                    [x, y, z, r, g, b] => ([x, y, z], Some([r, g, b].map(srgb_to_linear))),
+                    _ => return Err(format!("Expected 3 or 6 values, found {}", values.len())),
+                };
+                self.positions.push(position);
+                self.vertex_colors.push(color);
+            }
+            Some("vt") => {
+                let u = float(tokens.next().ok_or("Missing u coordinate")?, "vt")?;
+                let v = match tokens.next() {
+                    Some(token) => float(token, "vt")?,
+                    None => 0.0,
+                };
+                // OBJ texture coordinates start at the bottom left, wgpu's start at the top left
+                self.uvs.push([u, 1.0 - v]);
+            }
+            Some("vn") => {
+                self.normals.push(floats::<3>(&mut tokens, "vn")?);
+            }
+            Some("f") => {
+                let corners = tokens
+                    .map(|token| self.corner(token))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if corners.len() < 3 {
+                    return Err(format!(
+                        "Faces need at least 3 vertices, found {}",
+                        corners.len()
+                    ));
+                }
+                for i in 1..corners.len() - 1 {
+                    self.triangles.push(Triangle {
+                        corners: [corners[0], corners[i], corners[i + 1]],
+                        material_color: self.material_color,
+                    });
+                }
+            }
+            Some("usemtl") => {
+                let name = rest(line, "usemtl").ok_or("Missing material name")?;
+                self.material_color = self.materials.get(name).copied();
+                if self.material_color.is_none() {
+                    warn!("Undefined material {}", name);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Read and parse the materials of each named MTL file
+    fn mtllib<'a>(
+        &mut self,
+        names: impl Iterator<Item = &'a str>,
+        read_mtl: &mut impl FnMut(&str) -> io::Result<String>,
+    ) -> Result<(), ObjError> {
+        for name in names {
+            match read_mtl(name) {
+                Ok(source) => self.materials.extend(parse_mtl(name, &source)?),
+                Err(error) => warn!("Unable to read {}: {}", name, error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse a face corner of the form `v`, `v/vt`, `v//vn`, or `v/vt/vn`
+    fn corner(&self, token: &str) -> Result<Corner, String> {
+        let mut parts = token.split('/');
+        let position = index(parts.next().unwrap_or(""), self.positions.len(), "vertex")?;
+        let uv = match parts.next() {
+            None | Some("") => None,
+            Some(part) => Some(index(part, self.uvs.len(), "texture coordinate")?),
+        };
+        let normal = match parts.next() {
+            None | Some("") => None,
+            Some(part) => Some(index(part, self.normals.len(), "normal")?),
+        };
+        if parts.next().is_some() {
+            return Err(format!("Invalid face vertex '{token}'"));
+        }
+        Ok(Corner {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    fn build(self) -> Result<Mesh, ObjError> {
+        if self.triangles.is_empty() {
+            return Err(ObjError::NoFaces);
+        }
+
+        // Unit length file normals. Zero length normals are replaced by generated ones.
+        let file_normals: Vec<Option<Vec3>> = self
+            .normals
+            .iter()
+            .map(|&normal| Vec3::from(normal).try_normalize())
+            .collect();
+        let generated_normals = smooth_normals(
+            &self.positions,
+            self.triangles
+                .iter()
+                .map(|triangle| triangle.corners.map(|corner| corner.position)),
+        );
+
+        // Texture coordinates are only kept if every corner has them
+        let has_uvs = self
+            .triangles
+            .iter()
+            .all(|triangle| triangle.corners.iter().all(|corner| corner.uv.is_some()));
+
+        let mut positions = vec![];
+        let mut colors = vec![];
+        let mut normals = vec![];
+        let mut uvs = vec![];
+        let mut indices = vec![];
+        let mut vertices: HashMap<VertexKey, u32> = HashMap::new();
+        for triangle in &self.triangles {
+            for corner in triangle.corners {
+                let color = self.vertex_colors[corner.position]
+                    .or(triangle.material_color)
+                    .unwrap_or(DEFAULT_COLOR);
+                let normal = corner
+                    .normal
+                    .filter(|&normal| file_normals[normal].is_some());
+                let uv = corner.uv.filter(|_| has_uvs);
+                let key = (corner.position, uv, normal, color.map(f32::to_bits));
+                let index = *vertices.entry(key).or_insert_with(|| {
+                    positions.push(self.positions[corner.position]);
+                    colors.push(color);
+                    normals.push(match normal {
+                        Some(normal) => file_normals[normal].unwrap().to_array(),
+                        None => generated_normals[corner.position],
+                    });
+                    if let Some(uv) = uv {
+                        uvs.push(self.uvs[uv]);
+                    }
+                    positions.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+        }
+
+        let uvs = has_uvs.then_some(uvs);
+        Ok(Mesh::new(
+            positions,
+            colors,
+            normals,
+            uvs,
+            Indices::from_u32(indices),
+        )?)
+    }
+}
+
+fn tokens(line: &str) -> impl Iterator<Item = &str> {
+    // Comments start with # anywhere on the line
+    let line = line.split('#').next().unwrap_or("");
+    line.split_whitespace()
+}
+
+/// The rest of the line after `keyword`, for names which may contain spaces
+fn rest<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let line = line.split('#').next().unwrap_or("").trim();
+    let rest = line.strip_prefix(keyword)?.trim();
+    (!rest.is_empty()).then_some(rest)
+}
+
+fn float(token: &str, statement: &str) -> Result<f32, String> {
+    match token.parse::<f32>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        _ => Err(format!("Invalid number '{token}' in {statement}")),
+    }
+}
+
+fn floats<const N: usize>(
+    tokens: &mut impl Iterator<Item = impl AsRef<str>>,
+    statement: &str,
+) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let token = tokens
+            .next()
+            .ok_or_else(|| format!("Expected {N} values in {statement}"))?;
+        *value = float(token.as_ref(), statement)?;
+    }
+    Ok(values)
+}
+
+/// Convert a 1-based, or negative relative, OBJ index to a 0-based index
+fn index(token: &str, count: usize, attribute: &str) -> Result<usize, String> {
+    let value = token
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid {attribute} index '{token}'"))?;
+    let index = match value {
+        1.. => value - 1,
+        ..0 => count as i64 + value,
+        0 => -1,
+    };
+    if (0..count as i64).contains(&index) {
+        Ok(index as usize)
+    } else {
+        Err(format!(
+            "{attribute} index {value} is out of range for {count} values"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_mtl(name: &str) -> io::Result<String> {
+        Err(io::Error::new(io::ErrorKind::NotFound, name.to_string()))
+    }
+
+    fn syntax_error(result: Result<Mesh, ObjError>) -> (usize, String) {
+        match result {
+            Err(ObjError::Syntax { line, message, .. }) => (line, message),
+            other => panic!("Expected a syntax error, got {other:?}"),
+        }
+    }
+
+    const SQUARE: &str = "
+        # Unit square in the XY plane
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        f 1 2 3 4
+    ";
+
+    #[test]
+    fn triangulates_polygons() {
+        let mesh = parse(SQUARE, no_mtl).unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangles().collect::<Vec<_>>(), [[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn generates_missing_normals() {
+        let mesh = parse(SQUARE, no_mtl).unwrap();
+        assert!(
+            mesh.normals()
+                .iter()
+                .all(|&normal| normal == [0.0, 0.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn uses_file_normals_and_uvs() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 0 1
+            vn 0 0 2
+            f 1/1/1 2/2/1 3/3/1
+        ";
+        let mesh = parse(source, no_mtl).unwrap();
+        assert_eq!(mesh.normals(), [[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(mesh.uvs(), Some(&[[0.0, 1.0], [1.0, 1.0], [0.0, 0.0]][..]));
+    }
+
+    #[test]
+    fn drops_uvs_unless_every_corner_has_them() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0 0
+            f 1/1 2 3
+        ";
+        assert_eq!(parse(source, no_mtl).unwrap().uvs(), None);
+    }
+
+    #[test]
+    fn resolves_negative_indices() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+        ";
+        let mesh = parse(source, no_mtl).unwrap();
+        assert_eq!(mesh.triangles().collect::<Vec<_>>(), [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn centers_and_scales_to_cube() {
+        let mesh = parse(SQUARE, no_mtl).unwrap();
+        assert_eq!(
+            mesh.positions(),
+            [
+                [-1.0, -1.0, 0.0],
+                [1.0, -1.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [-1.0, 1.0, 0.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_material_and_vertex_colors() {
+        let source = "
+            mtllib colors.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 1 1 0 0 1 0
+            usemtl red
+            f 1 2 3
+            usemtl blue
+            f 2 4 3
+        ";
+        let mtl = "
+            newmtl red
+            Kd 1 0 0
+            newmtl blue
+            Kd 0 0 1
+        ";
+        let mesh = parse(source, |name| {
+            assert_eq!(name, "colors.mtl");
+            Ok(mtl.to_string())
+        })
+        .unwrap();
+        let colors: Vec<_> = mesh
+            .triangles()
+            .map(|triangle| triangle.map(|index| mesh.colors()[index as usize]))
+            .collect();
+        let [red, green, blue] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_eq!(colors, [[red; 3], [blue, green, blue]]);
+    }
+
+    #[test]
+    fn missing_mtl_uses_default_color() {
+        let source = format!("mtllib missing.mtl\nusemtl red\n{SQUARE}");
+        let mesh = parse(&source, no_mtl).unwrap();
+        assert!(mesh.colors().iter().all(|&color| color == DEFAULT_COLOR));
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_line_numbers() {
+        assert_eq!(
+            syntax_error(parse("v 0 0 0\nv 1 x 0", no_mtl)),
+            (2, "Invalid number 'x' in v".to_string())
+        );
+        assert_eq!(
+            syntax_error(parse("v 0 0 0\nv 1 0 0\nf 1 2", no_mtl)),
+            (3, "Faces need at least 3 vertices, found 2".to_string())
+        );
+        assert_eq!(
+            syntax_error(parse("v 0 0 0\nf 1 2 3", no_mtl)),
+            (2, "vertex index 2 is out of range for 1 values".to_string())
+        );
+        assert_eq!(
+            syntax_error(parse("v 0 0 0\nf 0 1 1", no_mtl)),
+            (2, "vertex index 0 is out of range for 1 values".to_string())
+        );
+        assert_eq!(
+            syntax_error(parse("v 1 2 nan", no_mtl)),
+            (1, "Invalid number 'nan' in v".to_string())
+        );
+        assert_eq!(
+            syntax_error(parse("v 0 0 0\nf 1/1/1/1 1 1", no_mtl)),
+            (
+                2,
+                "texture coordinate index 1 is out of range for 0 values".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_mtl_syntax_errors() {
+        let result = parse("# Materials\nmtllib good.mtl bad.mtl", |name| match name {
+            "good.mtl" => Ok("newmtl red\nKd 1 0 0".to_string()),
+            _ => Ok("# Missing newmtl\nKd 1 0 0".to_string()),
+        });
+        match result {
+            Err(ObjError::Syntax {
+                file,
+                line,
+                message,
+            }) => {
+                assert_eq!(file.as_deref(), Some("bad.mtl"));
+                assert_eq!(line, 2);
+                assert_eq!(message, "Kd before newmtl");
+            }
+            other => panic!("Expected an MTL syntax error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_models_without_faces() {
+        assert!(matches!(parse("v 0 0 0\n", no_mtl), Err(ObjError::NoFaces)));
+    }
+
+    #[test]
+    fn survives_arbitrary_input() {
+        let inputs = [
+            "f",
+            "f 1/",
+            "f //",
+            "f -0 -0 -0",
+            "f 99999999999999999999 1 1",
+            "v 1e39 0 0",
+            "vt",
+            "vn 0 0",
+            "usemtl",
+            "mtllib",
+            "v 0 0 0\nv 0 0 0\nv 0 0 0\nf 1 2 3",
+        ];
+        for input in inputs {
+            let _ = parse(input, no_mtl);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::adapter::AdapterSelector;
+use crate::clock::{AnimationClock, ClockMode};
+use crate::depth;
+use crate::light::Lighting;
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+use crate::shadertoy::{Shadertoy, ShadertoyTarget};
+use crate::shading::Shading;
+use crate::texture::SamplerOptions;
+use std::sync::Arc;
+use winit::dpi::PhysicalSize;
+
+/// User configurable rendering options
+///
+/// These are requests rather than guarantees. Values the adapter cannot satisfy are reduced to the
+/// closest supported value when the GPU context is created.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Initial window inner size. The platform default is used if `None`.
+    pub window_size: Option<PhysicalSize<u32>>,
+    /// Start in borderless fullscreen mode on the current monitor
+    pub fullscreen: bool,
+    /// Backends which may be used when requesting an adapter
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Use a specific adapter instead of letting wgpu choose one based on the power preference
+    pub adapter: Option<AdapterSelector>,
+    /// Only consider software (CPU) adapters such as llvmpipe, lavapipe, or WARP
+    pub force_fallback_adapter: bool,
+    /// Falls back to `AutoVsync` if the surface does not support the requested mode
+    pub present_mode: wgpu::PresentMode,
+    pub desired_maximum_frame_latency: u32,
+    /// Number of MSAA samples per pixel (1, 2, 4, or 8). A value of 1 disables multisampling.
+    pub sample_count: u32,
+    /// Format of the depth buffer. Falls back to `Depth32Float` if the adapter can't render to it.
+    pub depth_format: wgpu::TextureFormat,
+    /// Animation speed relative to real time. 1.0 is kmscube's speed at 60 Hz.
+    pub time_scale: f64,
+    /// Advance the animation by one reference frame per rendered frame instead of by real time.
+    /// This makes the animation deterministic for recordings, at the cost of depending on the
+    /// frame rate again.
+    pub fixed_step: bool,
+    /// Model to render instead of the built-in cube
+    pub scene: Option<Arc<Scene>>,
+    /// Initial shading mode. In the windowed app it can be changed while running.
+    pub shading: Shading,
+    pub lighting: Lighting,
+    /// How the scene's textures are filtered and repeated
+    pub sampler: SamplerOptions,
+    /// Shadertoy shader rendered on the cube's faces or over the whole frame, replacing the scene
+    pub shadertoy: Option<Arc<Shadertoy>>,
+    pub shadertoy_target: ShadertoyTarget,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            window_size: None,
+            fullscreen: false,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            adapter: None,
+            force_fallback_adapter: false,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            sample_count: 1,
+            depth_format: depth::DEFAULT_FORMAT,
+            time_scale: 1.0,
+            fixed_step: false,
+            scene: None,
+            shading: Shading::default(),
+            lighting: Lighting::default(),
+            sampler: SamplerOptions::default(),
+            shadertoy: None,
+            shadertoy_target: ShadertoyTarget::default(),
+        }
+    }
+}
+
+impl Options {
+    pub(crate) fn instance_descriptor(&self) -> wgpu::InstanceDescriptor {
+        wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn animation_clock(&self) -> AnimationClock {
+        let mode = if self.fixed_step {
+            ClockMode::FixedStep(AnimationClock::frame_time(1))
+        } else {
+            ClockMode::RealTime
+        };
+        let mut clock = AnimationClock::new(mode);
+        clock.set_scale(self.time_scale);
+        clock
+    }
+
+    pub(crate) fn scene(&self) -> Arc<Scene> {
+        self.scene
+            .clone()
+            .unwrap_or_else(|| Arc::new(Scene::from(Mesh::cube())))
+    }
+}
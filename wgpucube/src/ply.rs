@@ -0,0 +1,668 @@
+//! PLY model loading
+//!
+//! Reads ASCII and binary little-endian PLY files, such as those written by 3D scanners. Vertex
+//! positions and optional `red`, `green`, and `blue` colors are read from the `vertex` element,
+//! and polygons from the `vertex_indices` list of the `face` element. Polygons are triangulated
+//! as fans. Other elements and properties, including stored normals, are skipped, and normals are
+//! generated instead. Colors are display values and are treated as sRGB encoded.
+//!
+//! Vertices with identical positions and colors are welded. Files are read incrementally, so
+//! memory use is proportional to the size of the mesh rather than the file.
+
+use crate::color::srgb_to_linear;
+use crate::mesh::{Mesh, MeshError, Normals, Welder, mesh_with_normals};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+const WHITE: [f32; 3] = [1.0; 3];
+
+/// Largest number of items to reserve memory for based on the header, so a corrupt header can't
+/// cause a huge allocation before any data is read
+const MAX_RESERVED: usize = 1 << 20;
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(io::Error),
+    /// The header could not be parsed or uses an unsupported format
+    Header {
+        line: usize,
+        message: String,
+    },
+    /// An element's data could not be read
+    Data {
+        element: String,
+        index: usize,
+        message: String,
+    },
+    /// The file contains no faces
+    NoFaces,
+    Mesh(MeshError),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Header { line, message } => write!(f, "Header line {line}: {message}"),
+            Self::Data {
+                element,
+                index,
+                message,
+            } => write!(f, "{element} {index}: {message}"),
+            Self::NoFaces => write!(f, "The model has no faces"),
+            Self::Mesh(error) => write!(f, "Invalid mesh: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Mesh(error) => Some(error),
+            Self::Header { .. } | Self::Data { .. } | Self::NoFaces => None,
+        }
+    }
+}
+
+impl From<io::Error> for PlyError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<MeshError> for PlyError {
+    fn from(error: MeshError) -> Self {
+        Self::Mesh(error)
+    }
+}
+
+/// Load an ASCII or binary little-endian PLY file
+///
+/// The model is centered and scaled to fit the built-in cube's bounds.
+pub fn load(path: impl AsRef<Path>, normals: Normals) -> Result<Mesh, PlyError> {
+    read(BufReader::new(File::open(path)?), normals)
+}
+
+/// Read an ASCII or binary little-endian PLY file
+///
+/// The model is centered and scaled to fit the built-in cube's bounds.
+pub fn read(mut reader: impl BufRead, normals: Normals) -> Result<Mesh, PlyError> {
+    let header = Header::read(&mut reader)?;
+    let mut values: Box<dyn Values> = match header.format {
+        Format::Ascii => Box::new(AsciiValues {
+            reader,
+            line: vec![],
+            values: vec![],
+            position: 0,
+        }),
+        Format::BinaryLittleEndian => Box::new(BinaryValues { reader }),
+    };
+
+    let mut vertices: Vec<([f32; 3], [f32; 3])> = vec![];
+    let mut faces: Vec<[u32; 3]> = vec![];
+    for element in &header.elements {
+        let data_error = |index: usize, message: String| PlyError::Data {
+            element: element.name.clone(),
+            index,
+            message,
+        };
+        match element.name.as_str() {
+            "vertex" => {
+                let layout = VertexLayout::new(element)
+                    .map_err(|message| data_error(0, message.to_string()))?;
+                vertices.reserve(element.count.min(MAX_RESERVED));
+                for index in 0..element.count {
+                    let vertex = read_vertex(values.as_mut(), element, &layout)
+                        .map_err(|message| data_error(index, message))?;
+                    vertices.push(vertex);
+                }
+            }
+            "face" => {
+                faces.reserve(element.count.min(MAX_RESERVED));
+                for index in 0..element.count {
+                    read_face(values.as_mut(), element, vertices.len(), &mut faces)
+                        .map_err(|message| data_error(index, message))?;
+                }
+            }
+            _ => {
+                for index in 0..element.count {
+                    values
+                        .begin_item()
+                        .map_err(|message| data_error(index, message))?;
+                    for property in &element.properties {
+                        values
+                            .property(property)
+                            .map_err(|message| data_error(index, message))?;
+                    }
+                }
+            }
+        }
+    }
+
+    // Weld duplicate vertices and drop the triangles this makes degenerate
+    let mut welder = Welder::default();
+    let remap: Vec<u32> = vertices
+        .iter()
+        .map(|&(position, color)| welder.vertex(position, color))
+        .collect();
+    let mut triangles = Vec::with_capacity(faces.len());
+    for face in faces {
+        let [a, b, c] = face.map(|corner| remap[corner as usize]);
+        if a != b && b != c && a != c {
+            triangles.push([a, b, c]);
+        }
+    }
+    if triangles.is_empty() {
+        return Err(PlyError::NoFaces);
+    }
+
+    let (positions, colors) = welder.into_vertices();
+    let mut mesh = mesh_with_normals(positions, colors, triangles, normals)?;
+    mesh.fit_to_cube();
+    Ok(mesh)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// The largest value of an integer color channel, which represents full intensity
+    fn color_scale(self) -> f64 {
+        match self {
+            Self::U8 | Self::I8 => 255.0,
+            Self::U16 | Self::I16 => 65535.0,
+            Self::U32 | Self::I32 => u32::MAX as f64,
+            Self::F32 | Self::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    fn read(reader: &mut impl BufRead) -> Result<Self, PlyError> {
+        let mut line = vec![];
+        let mut line_number = 0;
+        let mut format = None;
+        let mut elements: Vec<Element> = vec![];
+        loop {
+            line.clear();
+            line_number += 1;
+            let header_error = |message: &str| PlyError::Header {
+                line: line_number,
+                message: message.to_string(),
+            };
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Err(header_error("Missing end_header"));
+            }
+            let text = std::str::from_utf8(&line).map_err(|_| header_error("Invalid text"))?;
+            let tokens: Vec<&str> = text.split_whitespace().collect();
+
+            if line_number == 1 {
+                if tokens != ["ply"] {
+                    return Err(header_error("Not a PLY file"));
+                }
+                continue;
+            }
+            match tokens[..] {
+                ["format", "ascii", _] => format = Some(Format::Ascii),
+                ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+                ["format", "binary_big_endian", _] => {
+                    return Err(header_error(
+                        "Big endian binary files are not supported, convert to little endian",
+                    ));
+                }
+                ["format", ..] => return Err(header_error("Unknown format")),
+                ["element", name, count] => {
+                    let count = count
+                        .parse()
+                        .map_err(|_| header_error("Invalid element count"))?;
+                    elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: vec![],
+                    });
+                }
+                ["property", "list", count, item, name] => {
+                    let count = Scalar::parse(count)
+                        .filter(|count| !matches!(count, Scalar::F32 | Scalar::F64))
+                        .ok_or_else(|| header_error("Invalid list count type"))?;
+                    let item = Scalar::parse(item)
+                        .ok_or_else(|| header_error("Invalid list item type"))?;
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| header_error("Property before element"))?
+                        .properties
+                        .push(Property {
+                            name: name.to_string(),
+                            kind: PropertyKind::List { count, item },
+                        });
+                }
+                ["property", scalar, name] => {
+                    let scalar =
+                        Scalar::parse(scalar).ok_or_else(|| header_error("Invalid type"))?;
+                    elements
+                        .last_mut()
+                        .ok_or_else(|| header_error("Property before element"))?
+                        .properties
+                        .push(Property {
+                            name: name.to_string(),
+                            kind: PropertyKind::Scalar(scalar),
+                        });
+                }
+                ["end_header"] => break,
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                _ => return Err(header_error("Invalid header statement")),
+            }
+        }
+        let format = format.ok_or_else(|| PlyError::Header {
+            line: line_number,
+            message: "Missing format".to_string(),
+        })?;
+        Ok(Self { format, elements })
+    }
+}
+
+/// Reads property values from an element's items
+trait Values {
+    /// Start reading the next item of an element
+    fn begin_item(&mut self) -> Result<(), String>;
+
+    fn scalar(&mut self, scalar: Scalar) -> Result<f64, String>;
+
+    /// Read and discard a property
+    fn property(&mut self, property: &Property) -> Result<(), String> {
+        match property.kind {
+            PropertyKind::Scalar(scalar) => {
+                self.scalar(scalar)?;
+            }
+            PropertyKind::List { count, item } => {
+                for _ in 0..self.list_count(count)? {
+                    self.scalar(item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list_count(&mut self, count: Scalar) -> Result<usize, String> {
+        let count = self.scalar(count)?;
+        if count >= 0.0 && count.fract() == 0.0 {
+            Ok(count as usize)
+        } else {
+            Err(format!("Invalid list length {count}"))
+        }
+    }
+}
+
+/// ASCII items are one per line
+struct AsciiValues<R> {
+    reader: R,
+    line: Vec<u8>,
+    values: Vec<f64>,
+    position: usize,
+}
+
+impl<R: BufRead> Values for AsciiValues<R> {
+    fn begin_item(&mut self) -> Result<(), String> {
+        // Skip blank lines
+        loop {
+            self.line.clear();
+            let read = self
+                .reader
+                .read_until(b'\n', &mut self.line)
+                .map_err(|error| error.to_string())?;
+            if read == 0 {
+                return Err("Unexpected end of file".to_string());
+            }
+            let text = std::str::from_utf8(&self.line).map_err(|_| "Invalid text")?;
+            self.values.clear();
+            self.position = 0;
+            for token in text.split_whitespace() {
+                let value = token
+                    .parse()
+                    .map_err(|_| format!("Invalid number '{token}'"))?;
+                self.values.push(value);
+            }
+            if !self.values.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn scalar(&mut self, _: Scalar) -> Result<f64, String> {
+        let value = self
+            .values
+            .get(self.position)
+            .copied()
+            .ok_or("Too few values")?;
+        self.position += 1;
+        Ok(value)
+    }
+}
+
+struct BinaryValues<R> {
+    reader: R,
+}
+
+impl<R: BufRead> Values for BinaryValues<R> {
+    fn begin_item(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn scalar(&mut self, scalar: Scalar) -> Result<f64, String> {
+        let mut bytes = [0; 8];
+        let bytes = &mut bytes[..scalar.size()];
+        self.reader.read_exact(bytes).map_err(|error| {
+            if error.kind() == io::ErrorKind::UnexpectedEof {
+                "Unexpected end of file".to_string()
+            } else {
+                error.to_string()
+            }
+        })?;
+        Ok(match scalar {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::U16 => u16::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::I32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+}
+
+/// Indices of the vertex element's position and color properties
+struct VertexLayout {
+    position: [usize; 3],
+    color: Option<[usize; 3]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Result<Self, &'static str> {
+        let find = |names: &[&str]| {
+            element.properties.iter().position(|property| {
+                names.contains(&property.name.as_str())
+                    && matches!(property.kind, PropertyKind::Scalar(_))
+            })
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let [Some(x), Some(y), Some(z)] = position else {
+            return Err("Missing x, y, or z property");
+        };
+        let color = match [
+            find(&["red", "diffuse_red"]),
+            find(&["green", "diffuse_green"]),
+            find(&["blue", "diffuse_blue"]),
+        ] {
+            [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+            _ => None,
+        };
+        Ok(Self {
+            position: [x, y, z],
+            color,
+        })
+    }
+}
+
+fn read_vertex(
+    values: &mut dyn Values,
+    element: &Element,
+    layout: &VertexLayout,
+) -> Result<([f32; 3], [f32; 3]), String> {
+    values.begin_item()?;
+    let mut position = [0.0; 3];
+    let mut color = WHITE;
+    for (index, property) in element.properties.iter().enumerate() {
+        let PropertyKind::Scalar(scalar) = property.kind else {
+            values.property(property)?;
+            continue;
+        };
+        let value = values.scalar(scalar)?;
+        if let Some(axis) = layout.position.iter().position(|&i| i == index) {
+            position[axis] = value as f32;
+        } else if let Some(channel) = layout
+            .color
+            .and_then(|color| color.iter().position(|&i| i == index))
+        {
+            color[channel] = srgb_to_linear((value / scalar.color_scale()).clamp(0.0, 1.0) as f32);
+        }
+    }
+    if !position.iter().all(|value| value.is_finite()) {
+        return Err("Non-finite position".to_string());
+    }
+    Ok((position, color))
+}
+
+fn read_face(
+    values: &mut dyn Values,
+    element: &Element,
+    vertex_count: usize,
+    triangles: &mut Vec<[u32; 3]>,
+) -> Result<(), String> {
+    values.begin_item()?;
+    for property in &element.properties {
+        let PropertyKind::List { count, item } = property.kind else {
+            values.property(property)?;
+            continue;
+        };
+        if !matches!(property.name.as_str(), "vertex_indices" | "vertex_index") {
+            values.property(property)?;
+            continue;
+        }
+
+        let count = values.list_count(count)?;
+        if count < 3 {
+            return Err(format!("Faces need at least 3 vertices, found {count}"));
+        }
+        let mut corners = Vec::with_capacity(count.min(MAX_RESERVED));
+        for _ in 0..count {
+            let index = values.scalar(item)?;
+            if index < 0.0 || index.fract() != 0.0 {
+                return Err(format!("Invalid vertex index {index}"));
+            }
+            // Vertices are read first, so the indices can be checked as they are read
+            if index >= vertex_count as f64 {
+                return Err(format!(
+                    "Vertex index {index} out of range for {vertex_count} vertices"
+                ));
+            }
+            corners.push(index as u32);
+        }
+        for i in 1..corners.len() - 1 {
+            triangles.push([corners[0], corners[i], corners[i + 1]]);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SQUARE: &str = "ply
+format ascii 1.0
+comment unit square with a colored corner
+element vertex 5
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+element edge 1
+property int vertex1
+property int vertex2
+end_header
+0 0 0 255 0 0
+1 0 0 255 255 255
+1 1 0 255 255 255
+0 1 0 255 255 255
+0 0 0 255 0 0
+4 0 1 2 3
+0 1
+";
+
+    #[test]
+    fn reads_ascii_with_colors() {
+        let mesh = read(Cursor::new(SQUARE), Normals::Smooth).unwrap();
+        assert_eq!(mesh.triangle_count(), 2);
+        // The duplicate of the first vertex is welded
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.colors()[0], [1.0, 0.0, 0.0]);
+        assert_eq!(mesh.colors()[1], WHITE);
+        assert_eq!(mesh.normals(), [[0.0, 0.0, 1.0]; 4]);
+    }
+
+    #[test]
+    fn reads_binary_little_endian() {
+        let mut bytes = b"ply
+format binary_little_endian 1.0
+element vertex 3
+property double x
+property float y
+property float z
+property float nx
+element face 1
+property uchar flags
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+        for [x, y, z] in [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+            bytes.extend(f64::to_le_bytes(x));
+            bytes.extend((y as f32).to_le_bytes());
+            bytes.extend((z as f32).to_le_bytes());
+            bytes.extend(f32::to_le_bytes(0.0));
+        }
+        bytes.push(7);
+        bytes.push(3);
+        for index in [0u32, 1, 2] {
+            bytes.extend(index.to_le_bytes());
+        }
+
+        let mesh = read(Cursor::new(&bytes), Normals::Flat).unwrap();
+        assert_eq!(mesh.triangle_count(), 1);
+        assert_eq!(mesh.normals(), [[0.0, 0.0, 1.0]; 3]);
+
+        bytes.pop();
+        assert_eq!(
+            read(Cursor::new(&bytes), Normals::Flat)
+                .unwrap_err()
+                .to_string(),
+            "face 0: Unexpected end of file"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let cases = [
+            ("obj\n", "Header line 1: Not a PLY file"),
+            (
+                "ply\nformat binary_big_endian 1.0\n",
+                "Header line 2: Big endian binary files are not supported, convert to little endian",
+            ),
+            (
+                "ply\nformat ascii 1.0\n",
+                "Header line 3: Missing end_header",
+            ),
+            (
+                "ply\nformat ascii 1.0\nproperty float x\n",
+                "Header line 3: Property before element",
+            ),
+        ];
+        for (source, message) in cases {
+            let error = read(Cursor::new(source), Normals::Smooth).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+
+        let out_of_range = SQUARE.replace("4 0 1 2 3", "4 0 1 2 9");
+        assert_eq!(
+            read(Cursor::new(out_of_range), Normals::Smooth)
+                .unwrap_err()
+                .to_string(),
+            "face 0: Vertex index 9 out of range for 5 vertices"
+        );
+        let bad_number = SQUARE.replace("1 1 0 255", "1 x 0 255");
+        assert_eq!(
+            read(Cursor::new(bad_number), Normals::Smooth)
+                .unwrap_err()
+                .to_string(),
+            "vertex 2: Invalid number 'x'"
+        );
+        let no_faces = SQUARE.replace("element face 1", "element face 0");
+        let no_faces = no_faces.replace("4 0 1 2 3\n", "");
+        assert!(matches!(
+            read(Cursor::new(no_faces), Normals::Smooth),
+            Err(PlyError::NoFaces)
+        ));
+    }
+}
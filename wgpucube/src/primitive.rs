@@ -0,0 +1,676 @@
+//! Procedurally generated meshes
+//!
+//! Each `Primitive` generates a mesh with normals and texture coordinates that fits within the
+//! built-in cube's bounds. Primitives can be parsed from specifications such as `sphere` or
+//! `torus:radius=0.2,segments=64`, so they can be selected on the command line.
+//!
+//! Texture coordinates have their origin at the top left, like glTF. Curved surfaces repeat the
+//! vertices along their seams so the texture coordinates can wrap from 1 back to 0.
+
+use crate::color::srgb_to_linear;
+use crate::mesh::{Mesh, MeshBuilder};
+use glam::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+use std::fmt;
+use std::str::FromStr;
+
+/// Largest number of segments, rings, sides, or subdivisions of any primitive except icospheres
+const MAX_DIVISIONS: u32 = 1024;
+
+/// Largest icosphere subdivision level, which has 1,310,720 triangles
+const MAX_ICOSPHERE_SUBDIVISIONS: u32 = 8;
+
+/// A parameterized shape which can generate a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    /// Sphere of latitude rings and longitude segments, with its poles on the Y axis
+    UvSphere { segments: u32, rings: u32 },
+    /// Sphere made by repeatedly splitting the triangles of an icosahedron into four, so its
+    /// triangles have nearly equal areas
+    Icosphere { subdivisions: u32 },
+    /// Ring around the Y axis with a circular cross section. `radius` is the radius of the tube,
+    /// which must be in (0, 0.5] so the hole doesn't close.
+    Torus {
+        radius: f32,
+        segments: u32,
+        sides: u32,
+    },
+    /// Capped cylinder along the Y axis, divided into `rings` bands along its height
+    Cylinder { segments: u32, rings: u32 },
+    /// Capped cone along the Y axis with its apex at the top
+    Cone { segments: u32, rings: u32 },
+    /// Square grid in the XY plane facing +Z
+    Plane { subdivisions: u32 },
+    /// Cube with each face divided into a grid of `subdivisions` by `subdivisions` patches
+    Cube { subdivisions: u32 },
+}
+
+impl Primitive {
+    pub const NAMES: [&str; 7] = [
+        "sphere",
+        "icosphere",
+        "torus",
+        "cylinder",
+        "cone",
+        "plane",
+        "cube",
+    ];
+
+    /// The primitive with default parameters, if `name` is one of `NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sphere" => Self::UvSphere {
+                segments: 32,
+                rings: 16,
+            },
+            "icosphere" => Self::Icosphere { subdivisions: 3 },
+            "torus" => Self::Torus {
+                radius: 0.3,
+                segments: 48,
+                sides: 24,
+            },
+            "cylinder" => Self::Cylinder {
+                segments: 32,
+                rings: 1,
+            },
+            "cone" => Self::Cone {
+                segments: 32,
+                rings: 1,
+            },
+            "plane" => Self::Plane { subdivisions: 1 },
+            "cube" => Self::Cube { subdivisions: 1 },
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::UvSphere { .. } => "sphere",
+            Self::Icosphere { .. } => "icosphere",
+            Self::Torus { .. } => "torus",
+            Self::Cylinder { .. } => "cylinder",
+            Self::Cone { .. } => "cone",
+            Self::Plane { .. } => "plane",
+            Self::Cube { .. } => "cube",
+        }
+    }
+
+    /// Check that the parameters are in range
+    pub fn validate(&self) -> Result<(), ParsePrimitiveError> {
+        let check = |name: &str, value: u32, min: u32, max: u32| {
+            if (min..=max).contains(&value) {
+                Ok(())
+            } else {
+                Err(ParsePrimitiveError(format!(
+                    "{name} must be from {min} to {max}, got {value}"
+                )))
+            }
+        };
+        match *self {
+            Self::UvSphere { segments, rings } => {
+                check("segments", segments, 3, MAX_DIVISIONS)?;
+                check("rings", rings, 2, MAX_DIVISIONS)
+            }
+            Self::Icosphere { subdivisions } => {
+                check("subdivisions", subdivisions, 0, MAX_ICOSPHERE_SUBDIVISIONS)
+            }
+            Self::Torus {
+                radius,
+                segments,
+                sides,
+            } => {
+                if !(radius > 0.0 && radius <= 0.5) {
+                    return Err(ParsePrimitiveError(format!(
+                        "radius must be greater than 0 and at most 0.5, got {radius}"
+                    )));
+                }
+                check("segments", segments, 3, MAX_DIVISIONS)?;
+                check("sides", sides, 3, MAX_DIVISIONS)
+            }
+            Self::Cylinder { segments, rings } | Self::Cone { segments, rings } => {
+                check("segments", segments, 3, MAX_DIVISIONS)?;
+                check("rings", rings, 1, MAX_DIVISIONS)
+            }
+            Self::Plane { subdivisions } | Self::Cube { subdivisions } => {
+                check("subdivisions", subdivisions, 1, MAX_DIVISIONS)
+            }
+        }
+    }
+
+    /// Generate the primitive's mesh
+    ///
+    /// Panics if the parameters are out of range, which `FromStr` and `validate` rule out.
+    pub fn mesh(&self) -> Mesh {
+        assert_eq!(self.validate(), Ok(()));
+        let mut builder = Mesh::builder();
+        match *self {
+            Self::UvSphere { segments, rings } => {
+                grid(&mut builder, segments, rings, |s, t| {
+                    let angle = TAU * s;
+                    let latitude = PI * (t - 0.5);
+                    // Snap the poles so their triangles are detected as degenerate
+                    let ring_radius = if t == 0.0 || t == 1.0 {
+                        0.0
+                    } else {
+                        latitude.cos()
+                    };
+                    let position = Vec3::new(
+                        ring_radius * angle.cos(),
+                        latitude.sin(),
+                        -ring_radius * angle.sin(),
+                    );
+                    (position, position.normalize())
+                });
+            }
+            Self::Icosphere { subdivisions } => icosphere(&mut builder, subdivisions),
+            Self::Torus {
+                radius,
+                segments,
+                sides,
+            } => {
+                let major_radius = 1.0 - radius;
+                grid(&mut builder, segments, sides, |s, t| {
+                    let (angle, side) = (TAU * s, TAU * t);
+                    let around = Vec3::new(angle.cos(), 0.0, -angle.sin());
+                    let normal = around * side.cos() + Vec3::Y * side.sin();
+                    (around * major_radius + normal * radius, normal)
+                });
+            }
+            Self::Cylinder { segments, rings } => lathe(&mut builder, segments, rings, 1.0),
+            Self::Cone { segments, rings } => lathe(&mut builder, segments, rings, 0.0),
+            Self::Plane { subdivisions } => {
+                grid(&mut builder, subdivisions, subdivisions, |s, t| {
+                    (Vec3::new(2.0 * s - 1.0, 2.0 * t - 1.0, 0.0), Vec3::Z)
+                });
+            }
+            Self::Cube { subdivisions } => {
+                for (normal, up) in [
+                    (Vec3::Z, Vec3::Y),
+                    (Vec3::NEG_Z, Vec3::Y),
+                    (Vec3::X, Vec3::Y),
+                    (Vec3::NEG_X, Vec3::Y),
+                    (Vec3::Y, Vec3::NEG_Z),
+                    (Vec3::NEG_Y, Vec3::Z),
+                ] {
+                    // Right handed, so the grid is counter-clockwise when viewed from outside
+                    let right = up.cross(normal);
+                    grid(&mut builder, subdivisions, subdivisions, |s, t| {
+                        let position = normal + right * (2.0 * s - 1.0) + up * (2.0 * t - 1.0);
+                        (position, normal)
+                    });
+                }
+            }
+        }
+        builder.build().unwrap()
+    }
+}
+
+impl FromStr for Primitive {
+    type Err = ParsePrimitiveError;
+
+    /// Parse `NAME[:KEY=VALUE,...]`, where omitted parameters have their default values
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, parameters) = value.split_once(':').unwrap_or((value, ""));
+        let mut primitive = Self::from_name(name).ok_or_else(|| {
+            ParsePrimitiveError(format!(
+                "unknown primitive '{name}', expected one of {}",
+                Self::NAMES.join(", ")
+            ))
+        })?;
+        for parameter in parameters
+            .split(',')
+            .filter(|parameter| !parameter.is_empty())
+        {
+            let (key, value) = parameter.split_once('=').ok_or_else(|| {
+                ParsePrimitiveError(format!("expected KEY=VALUE, got '{parameter}'"))
+            })?;
+            let invalid = || ParsePrimitiveError(format!("invalid {key} '{value}'"));
+            let count = || value.parse::<u32>().map_err(|_| invalid());
+            match (&mut primitive, key) {
+                (
+                    Self::UvSphere { segments, .. }
+                    | Self::Torus { segments, .. }
+                    | Self::Cylinder { segments, .. }
+                    | Self::Cone { segments, .. },
+                    "segments",
+                ) => *segments = count()?,
+                (
+                    Self::UvSphere { rings, .. }
+                    | Self::Cylinder { rings, .. }
+                    | Self::Cone { rings, .. },
+                    "rings",
+                ) => *rings = count()?,
+                (Self::Torus { sides, .. }, "sides") => *sides = count()?,
+                (Self::Torus { radius, .. }, "radius") => {
+                    *radius = value.parse().map_err(|_| invalid())?;
+                }
+                (
+                    Self::Icosphere { subdivisions }
+                    | Self::Plane { subdivisions }
+                    | Self::Cube { subdivisions },
+                    "subdivisions",
+                ) => *subdivisions = count()?,
+                _ => {
+                    return Err(ParsePrimitiveError(format!(
+                        "{name} has no parameter '{key}'"
+                    )));
+                }
+            }
+        }
+        primitive.validate()?;
+        Ok(primitive)
+    }
+}
+
+impl fmt::Display for Primitive {
+    /// Format as a specification which parses back to the same primitive
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name())?;
+        match self {
+            Self::UvSphere { segments, rings }
+            | Self::Cylinder { segments, rings }
+            | Self::Cone { segments, rings } => write!(f, "segments={segments},rings={rings}"),
+            Self::Icosphere { subdivisions }
+            | Self::Plane { subdivisions }
+            | Self::Cube { subdivisions } => write!(f, "subdivisions={subdivisions}"),
+            Self::Torus {
+                radius,
+                segments,
+                sides,
+            } => write!(f, "radius={radius},segments={segments},sides={sides}"),
+        }
+    }
+}
+
+/// Reason a primitive specification could not be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsePrimitiveError(String);
+
+impl fmt::Display for ParsePrimitiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParsePrimitiveError {}
+
+/// Vertex color for a position within the cube's bounds, blending between the cube's corner
+/// colors
+fn color(position: Vec3) -> [f32; 3] {
+    (position * 0.5 + 0.5)
+        .clamp(Vec3::ZERO, Vec3::ONE)
+        .to_array()
+        .map(srgb_to_linear)
+}
+
+/// Add a `columns` by `rows` grid of quads, with the position and normal of each vertex given by
+/// its texture coordinates, measured from the bottom left
+///
+/// The derivative of the position along `s` crossed with its derivative along `t` must point
+/// outwards, so the quads are counter-clockwise when viewed from the front. Triangles with two
+/// identical corners, such as those at the poles of a sphere, are skipped.
+fn grid(
+    builder: &mut MeshBuilder,
+    columns: u32,
+    rows: u32,
+    surface: impl Fn(f32, f32) -> (Vec3, Vec3),
+) {
+    let first = builder.vertex_count() as u32;
+    let mut positions = Vec::with_capacity(((columns + 1) * (rows + 1)) as usize);
+    for row in 0..=rows {
+        let t = row as f32 / rows as f32;
+        for column in 0..=columns {
+            let s = column as f32 / columns as f32;
+            let (position, normal) = surface(s, t);
+            builder.vertex_with_uv(
+                position.to_array(),
+                color(position),
+                normal.to_array(),
+                [s, 1.0 - t],
+            );
+            positions.push(position);
+        }
+    }
+    let index = |column: u32, row: u32| row * (columns + 1) + column;
+    for row in 0..rows {
+        for column in 0..columns {
+            let quad = [
+                index(column, row),
+                index(column + 1, row),
+                index(column + 1, row + 1),
+                index(column, row + 1),
+            ];
+            for [a, b, c] in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                let [pa, pb, pc] = [a, b, c].map(|index| positions[index as usize]);
+                if pa != pb && pb != pc && pa != pc {
+                    builder.triangle([a, b, c].map(|index| first + index));
+                }
+            }
+        }
+    }
+}
+
+/// Add a capped surface of revolution around the Y axis, 2 units tall, with a bottom radius of 1
+/// and the given top radius
+fn lathe(builder: &mut MeshBuilder, segments: u32, rings: u32, top_radius: f32) {
+    // The radius shrinks by this much per unit of height, which tilts the normals up
+    let slope = (1.0 - top_radius) / 2.0;
+    grid(builder, segments, rings, |s, t| {
+        let angle = TAU * s;
+        let around = Vec3::new(angle.cos(), 0.0, -angle.sin());
+        let radius = 1.0 + (top_radius - 1.0) * t;
+        let position = around * radius + Vec3::Y * (2.0 * t - 1.0);
+        (position, (around + Vec3::Y * slope).normalize())
+    });
+
+    for (y, radius) in [(1.0, top_radius), (-1.0, 1.0)] {
+        if radius == 0.0 {
+            continue;
+        }
+        let normal = Vec3::Y * y;
+        let cap_vertex = |builder: &mut MeshBuilder, position: Vec3| {
+            builder.vertex_with_uv(
+                position.to_array(),
+                color(position),
+                normal.to_array(),
+                [0.5 + position.x / 2.0, 0.5 + position.z / 2.0],
+            )
+        };
+        let center = cap_vertex(builder, normal);
+        let first = builder.vertex_count() as u32;
+        for segment in 0..segments {
+            let angle = TAU * segment as f32 / segments as f32;
+            cap_vertex(
+                builder,
+                Vec3::new(radius * angle.cos(), y, -radius * angle.sin()),
+            );
+        }
+        for segment in 0..segments {
+            let a = first + segment;
+            let b = first + (segment + 1) % segments;
+            // The top cap is counter-clockwise viewed from above, the bottom from below
+            if y > 0.0 {
+                builder.triangle([center, a, b]);
+            } else {
+                builder.triangle([center, b, a]);
+            }
+        }
+    }
+}
+
+/// Add a unit icosphere with equirectangular texture coordinates
+fn icosphere(builder: &mut MeshBuilder, subdivisions: u32) {
+    // Icosahedron vertices are the corners of three orthogonal golden rectangles
+    let phi = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        [-1.0, phi, 0.0],
+        [1.0, phi, 0.0],
+        [-1.0, -phi, 0.0],
+        [1.0, -phi, 0.0],
+        [0.0, -1.0, phi],
+        [0.0, 1.0, phi],
+        [0.0, -1.0, -phi],
+        [0.0, 1.0, -phi],
+        [phi, 0.0, -1.0],
+        [phi, 0.0, 1.0],
+        [-phi, 0.0, -1.0],
+        [-phi, 0.0, 1.0],
+    ]
+    .map(|position| Vec3::from(position).normalize())
+    .to_vec();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Edge midpoints are shared by the two triangles on either side of the edge
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let position = (positions[a as usize] + positions[b as usize]).normalize();
+                positions.push(position);
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // Longitude increases counter-clockwise viewed from above from +X, matching the UV sphere
+    let uv = |position: Vec3| {
+        let u = (-position.z).atan2(position.x).rem_euclid(TAU) / TAU;
+        // Rounding can put vertices on the seam just short of a full turn
+        let u = if u > 1.0 - 1e-5 { 0.0 } else { u };
+        Vec2::new(u, position.y.clamp(-1.0, 1.0).acos() / PI)
+    };
+    let first = builder.vertex_count() as u32;
+    // Vertices near the seam are duplicated with 1 added to their u coordinate for the triangles
+    // which cross it, relying on the sampler repeating the texture
+    let mut wrapped: HashMap<u32, u32> = HashMap::new();
+    for &position in &positions {
+        builder.vertex_with_uv(
+            position.to_array(),
+            color(position),
+            position.to_array(),
+            uv(position).to_array(),
+        );
+    }
+    for triangle in triangles {
+        let mut uvs = triangle.map(|index| uv(positions[index as usize]));
+        let mut corners = triangle.map(|index| first + index);
+        let poles = triangle.map(|index| {
+            let position = positions[index as usize];
+            position.x.abs() < 1e-6 && position.z.abs() < 1e-6
+        });
+        let longitudes = || {
+            (0..3)
+                .filter(|&corner| !poles[corner])
+                .map(|corner| uvs[corner].x)
+        };
+        let wraps = longitudes().fold(f32::NEG_INFINITY, f32::max)
+            - longitudes().fold(f32::INFINITY, f32::min)
+            > 0.5;
+        for corner in 0..3 {
+            let position = positions[triangle[corner] as usize];
+            if wraps && uvs[corner].x < 0.5 && !poles[corner] {
+                uvs[corner].x += 1.0;
+                corners[corner] = *wrapped.entry(triangle[corner]).or_insert_with(|| {
+                    builder.vertex_with_uv(
+                        position.to_array(),
+                        color(position),
+                        position.to_array(),
+                        uvs[corner].to_array(),
+                    )
+                });
+            }
+        }
+        // Longitude is undefined at the poles, so they get a separate vertex per triangle in the
+        // middle of the other two corners' longitudes
+        for corner in 0..3 {
+            let position = positions[triangle[corner] as usize];
+            if poles[corner] {
+                let u = (uvs[(corner + 1) % 3].x + uvs[(corner + 2) % 3].x) / 2.0;
+                corners[corner] = builder.vertex_with_uv(
+                    position.to_array(),
+                    color(position),
+                    position.to_array(),
+                    [u, uvs[corner].y],
+                );
+            }
+        }
+        builder.triangle(corners);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_primitives() -> Vec<Primitive> {
+        let mut primitives: Vec<Primitive> = Primitive::NAMES
+            .iter()
+            .map(|name| Primitive::from_name(name).unwrap())
+            .collect();
+        primitives.extend([
+            Primitive::Icosphere { subdivisions: 0 },
+            Primitive::Cube { subdivisions: 3 },
+            Primitive::Cone {
+                segments: 5,
+                rings: 3,
+            },
+            Primitive::Torus {
+                radius: 0.5,
+                segments: 3,
+                sides: 3,
+            },
+        ]);
+        primitives
+    }
+
+    #[test]
+    fn primitives_are_valid_and_fit_the_cube() {
+        for primitive in all_primitives() {
+            let mesh = primitive.mesh();
+            assert_eq!(mesh.validate(), Ok(()), "{primitive}");
+            assert_eq!(mesh.check_winding(), Ok(()), "{primitive}");
+            // Icosphere triangles crossing the seam extend slightly past 1
+            assert!(
+                mesh.uvs()
+                    .unwrap()
+                    .iter()
+                    .all(|&[u, v]| (0.0..1.1).contains(&u) && (0.0..=1.0).contains(&v)),
+                "{primitive}"
+            );
+            let max = mesh
+                .positions()
+                .iter()
+                .flatten()
+                .fold(0f32, |max, coordinate| max.max(coordinate.abs()));
+            // Only an icosahedron doesn't touch the bounds
+            assert!(max > 0.8 && max < 1.0 + 1e-5, "{primitive}: {max}");
+        }
+    }
+
+    #[test]
+    fn triangles_face_their_normals() {
+        for primitive in all_primitives() {
+            let mesh = primitive.mesh();
+            for [a, b, c] in mesh.triangles() {
+                let [pa, pb, pc] =
+                    [a, b, c].map(|index| Vec3::from(mesh.positions()[index as usize]));
+                let face = (pb - pa).cross(pc - pa);
+                assert!(face.length() > 0.0, "{primitive}: degenerate triangle");
+                for index in [a, b, c] {
+                    let normal = Vec3::from(mesh.normals()[index as usize]);
+                    assert!(face.dot(normal) > 0.0, "{primitive}: triangle {a} {b} {c}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_surfaces_have_normals_along_the_surface_gradient() {
+        let sphere = Primitive::from_name("sphere").unwrap().mesh();
+        for (position, normal) in sphere.positions().iter().zip(sphere.normals()) {
+            assert!(Vec3::from(*position).abs_diff_eq(Vec3::from(*normal), 1e-5));
+        }
+        // The side of a cone with radius 1 and height 2 slopes by 1/2
+        let cone = Primitive::from_name("cone").unwrap().mesh();
+        let expected = Vec3::new(1.0, 0.5, 0.0).normalize().to_array();
+        assert_eq!(cone.normals()[0], expected);
+    }
+
+    #[test]
+    fn icosphere_triangles_do_not_span_the_seam() {
+        let mesh = Primitive::Icosphere { subdivisions: 4 }.mesh();
+        let uvs = mesh.uvs().unwrap();
+        for triangle in mesh.triangles() {
+            let [a, b, c] = triangle.map(|index| uvs[index as usize][0]);
+            assert!(a.max(b).max(c) - a.min(b).min(c) < 0.5, "{triangle:?}");
+        }
+    }
+
+    #[test]
+    fn counts_match_parameters() {
+        let count = |spec: &str| {
+            let mesh = spec.parse::<Primitive>().unwrap().mesh();
+            (mesh.vertex_count(), mesh.triangle_count())
+        };
+        // Each of the 6 faces has 3 x 3 quads on a 4 x 4 grid of vertices
+        assert_eq!(count("cube:subdivisions=3"), (96, 108));
+        assert_eq!(count("plane:subdivisions=2"), (9, 8));
+        // The pole rows each lose one triangle per segment, as does the row below a cone's apex
+        assert_eq!(count("sphere:segments=4,rings=3"), (20, 16));
+        assert_eq!(count("icosphere:subdivisions=2").1, 320);
+        assert_eq!(count("torus:segments=4,sides=3"), (20, 24));
+        // The side, then a top and bottom cap with a center vertex each
+        assert_eq!(count("cylinder:segments=4,rings=2"), (15 + 10, 16 + 8));
+        assert_eq!(count("cone:segments=4"), (10 + 5, 4 + 4));
+    }
+
+    #[test]
+    fn parses_specifications() {
+        assert_eq!(
+            "torus:radius=0.2,segments=64".parse(),
+            Ok(Primitive::Torus {
+                radius: 0.2,
+                segments: 64,
+                sides: 24,
+            })
+        );
+        for primitive in all_primitives() {
+            assert_eq!(primitive.to_string().parse(), Ok(primitive));
+        }
+
+        let errors = [
+            (
+                "pyramid",
+                "unknown primitive 'pyramid', expected one of sphere, icosphere, torus, cylinder, cone, plane, cube",
+            ),
+            ("sphere:rings", "expected KEY=VALUE, got 'rings'"),
+            ("sphere:sides=3", "sphere has no parameter 'sides'"),
+            ("cube:subdivisions=x", "invalid subdivisions 'x'"),
+            ("cone:segments=2", "segments must be from 3 to 1024, got 2"),
+            (
+                "icosphere:subdivisions=9",
+                "subdivisions must be from 0 to 8, got 9",
+            ),
+            (
+                "torus:radius=NaN",
+                "radius must be greater than 0 and at most 0.5, got NaN",
+            ),
+        ];
+        for (spec, message) in errors {
+            assert_eq!(
+                spec.parse::<Primitive>().unwrap_err().to_string(),
+                message,
+                "{spec}"
+            );
+        }
+    }
+}
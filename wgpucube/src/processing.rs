@@ -0,0 +1,923 @@
+//! Mesh processing operations
+//!
+//! Operations which generate vertex attributes for a mesh or change its topology. Each returns a
+//! new mesh, since most of them change the number of vertices. Vertices are treated as part of
+//! the same surface point when their positions are identical, even if their other attributes
+//! differ, so texture seams don't split the surface.
+
+use crate::mesh::{self, Indices, Mesh, MeshError};
+use glam::{DVec3, Vec3};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::{Add, AddAssign};
+
+/// Weight of the planes which keep boundary edges in place during simplification, relative to the
+/// planes of the triangles
+const BOUNDARY_WEIGHT: f64 = 100.0;
+
+/// Replace the normals with the area-weighted average of the normals of the triangles around each
+/// position
+pub fn smooth_normals(mesh: &Mesh) -> Mesh {
+    let (groups, group_count) = position_groups(mesh);
+    let mut group_positions = vec![[0.0; 3]; group_count];
+    for (vertex, &group) in groups.iter().enumerate() {
+        group_positions[group as usize] = mesh.positions()[vertex];
+    }
+    let group_normals = mesh::smooth_normals(
+        &group_positions,
+        mesh.triangles()
+            .map(|triangle| triangle.map(|vertex| groups[vertex as usize] as usize)),
+    );
+    let normals = groups
+        .iter()
+        .map(|&group| group_normals[group as usize])
+        .collect();
+    Mesh::new(
+        mesh.positions().to_vec(),
+        mesh.colors().to_vec(),
+        normals,
+        mesh.uvs().map(<[_]>::to_vec),
+        mesh.indices().clone(),
+    )
+    .expect("replacing valid normals keeps the mesh valid")
+}
+
+/// Replace the normals with flat normals, smoothing across edges where the triangles meet at an
+/// angle of at most `crease_angle` radians
+///
+/// Each corner's normal is the area-weighted average of the normals of the triangles around its
+/// position which are within the crease angle of its own triangle. Vertices are split where the
+/// corners sharing them end up with different normals. A crease angle of 0 makes every triangle
+/// flat, and one of π is the same as `smooth_normals`.
+pub fn flat_normals(mesh: &Mesh, crease_angle: f32) -> Result<Mesh, MeshError> {
+    let positions = mesh.positions();
+    let triangles: Vec<[u32; 3]> = mesh.triangles().collect();
+    // Unnormalized, so their lengths weight them by area
+    let face_normals: Vec<Vec3> = triangles
+        .iter()
+        .map(|triangle| {
+            let [a, b, c] = triangle.map(|vertex| Vec3::from(positions[vertex as usize]));
+            (b - a).cross(c - a)
+        })
+        .collect();
+    let (groups, group_count) = position_groups(mesh);
+    let mut group_triangles: Vec<Vec<usize>> = vec![vec![]; group_count];
+    for (triangle, corners) in triangles.iter().enumerate() {
+        for &vertex in corners {
+            let around = &mut group_triangles[groups[vertex as usize] as usize];
+            // A degenerate triangle can have two corners at the same position
+            if around.last() != Some(&triangle) {
+                around.push(triangle);
+            }
+        }
+    }
+
+    // Allow for rounding so coplanar triangles are always smoothed
+    let min_cos = crease_angle.cos() - 1e-6;
+    let mut sources = vec![];
+    let mut normals = vec![];
+    let mut split: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(triangles.len() * 3);
+    for (triangle, corners) in triangles.iter().enumerate() {
+        let own = face_normals[triangle].try_normalize();
+        for &vertex in corners {
+            let normal = match own {
+                Some(own) => group_triangles[groups[vertex as usize] as usize]
+                    .iter()
+                    .map(|&other| face_normals[other])
+                    .filter(|normal| {
+                        normal
+                            .try_normalize()
+                            .is_some_and(|normal| normal.dot(own) >= min_cos)
+                    })
+                    .sum::<Vec3>()
+                    .try_normalize()
+                    .unwrap_or(own),
+                // Degenerate triangles have no direction of their own
+                None => Vec3::from(mesh.normals()[vertex as usize]),
+            }
+            .to_array();
+            let key = (vertex, normal.map(|value| (value + 0.0).to_bits()));
+            let index = *split.entry(key).or_insert_with(|| {
+                sources.push(vertex);
+                normals.push(normal);
+                sources.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+    }
+    rebuild(mesh, &sources, Some(normals), indices)
+}
+
+/// Generate tangents for normal mapping with MikkTSpace, or `None` if the mesh has no texture
+/// coordinates
+///
+/// Returns the mesh with vertices split where the corners sharing them need different tangents,
+/// for example along mirrored texture seams, and a tangent for each of its vertices. `w` is the
+/// sign giving the bitangent as `w * normal.cross(tangent)`. Since v points down the texture, as
+/// in glTF, `w` is 1 when v decreases along the bitangent.
+pub fn tangents(mesh: &Mesh) -> Option<(Mesh, Vec<[f32; 4]>)> {
+    let mut geometry = TangentGeometry {
+        mesh,
+        uvs: mesh.uvs()?,
+        triangles: mesh.triangles().collect(),
+        tangents: vec![[0.0; 4]; mesh.triangle_count() * 3],
+    };
+    // This only fails for meshes without triangles, which have no corners to set tangents for
+    bevy_mikktspace::generate_tangents(&mut geometry);
+
+    let mut sources = vec![];
+    let mut tangents = vec![];
+    let mut split: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(geometry.tangents.len());
+    for (&vertex, &tangent) in geometry.triangles.iter().flatten().zip(&geometry.tangents) {
+        let normal = Vec3::from(mesh.normals()[vertex as usize]);
+        // Corners of degenerate triangles may be left without a tangent
+        let tangent = match Vec3::from_slice(&tangent).try_normalize() {
+            Some(direction) => direction.extend(tangent[3]),
+            None => normal.any_orthonormal_vector().extend(1.0),
+        }
+        .to_array();
+        let key = (vertex, tangent.map(|value| (value + 0.0).to_bits()));
+        let index = *split.entry(key).or_insert_with(|| {
+            sources.push(vertex);
+            tangents.push(tangent);
+            sources.len() as u32 - 1
+        });
+        indices.push(index);
+    }
+    let mesh =
+        rebuild(mesh, &sources, None, indices).expect("splitting vertices keeps the mesh valid");
+    Some((mesh, tangents))
+}
+
+/// A mesh's triangles as seen by MikkTSpace, which sets a tangent for each triangle corner
+struct TangentGeometry<'a> {
+    mesh: &'a Mesh,
+    uvs: &'a [[f32; 2]],
+    triangles: Vec<[u32; 3]>,
+    tangents: Vec<[f32; 4]>,
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, corner: usize) -> usize {
+        self.triangles[face][corner] as usize
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, corner: usize) -> [f32; 3] {
+        self.mesh.positions()[self.vertex(face, corner)]
+    }
+
+    fn normal(&self, face: usize, corner: usize) -> [f32; 3] {
+        self.mesh.normals()[self.vertex(face, corner)]
+    }
+
+    fn tex_coord(&self, face: usize, corner: usize) -> [f32; 2] {
+        // Flip v so it points up the texture like MikkTSpace expects
+        let [u, v] = self.uvs[self.vertex(face, corner)];
+        [u, 1.0 - v]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, corner: usize) {
+        self.tangents[face * 3 + corner] = tangent;
+    }
+}
+
+/// Merge vertices whose positions are within `tolerance` of each other and whose colors,
+/// normals, and texture coordinates each differ by at most `tolerance` in every component
+///
+/// Merged vertices keep the attributes of the first vertex. Triangles which become degenerate are
+/// removed. A tolerance of 0 merges only exact duplicates.
+pub fn weld(mesh: &Mesh, tolerance: f32) -> Result<Mesh, MeshError> {
+    let cell = |position: Vec3| -> [i64; 3] {
+        if tolerance > 0.0 {
+            (position / tolerance)
+                .floor()
+                .to_array()
+                .map(|value| value as i64)
+        } else {
+            position
+                .to_array()
+                .map(|value| (value + 0.0).to_bits() as i64)
+        }
+    };
+    let neighbor_offsets: Vec<[i64; 3]> = if tolerance > 0.0 {
+        (0..27)
+            .map(|i| [i % 3 - 1, i / 3 % 3 - 1, i / 9 - 1])
+            .collect()
+    } else {
+        vec![[0; 3]]
+    };
+    let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= tolerance);
+    let same = |a: usize, b: usize| {
+        Vec3::from(mesh.positions()[a]).distance(Vec3::from(mesh.positions()[b])) <= tolerance
+            && close(&mesh.colors()[a], &mesh.colors()[b])
+            && close(&mesh.normals()[a], &mesh.normals()[b])
+            && mesh.uvs().is_none_or(|uvs| close(&uvs[a], &uvs[b]))
+    };
+
+    let mut cells: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut sources: Vec<u32> = vec![];
+    let remap: Vec<u32> = (0..mesh.vertex_count())
+        .map(|vertex| {
+            let [x, y, z] = cell(Vec3::from(mesh.positions()[vertex]));
+            let existing = neighbor_offsets.iter().find_map(|[dx, dy, dz]| {
+                cells
+                    .get(&[x + dx, y + dy, z + dz])?
+                    .iter()
+                    .copied()
+                    .find(|&index| same(sources[index as usize] as usize, vertex))
+            });
+            existing.unwrap_or_else(|| {
+                sources.push(vertex as u32);
+                let index = sources.len() as u32 - 1;
+                cells.entry([x, y, z]).or_default().push(index);
+                index
+            })
+        })
+        .collect();
+
+    let indices = mesh
+        .triangles()
+        .map(|triangle| triangle.map(|vertex| remap[vertex as usize]))
+        .filter(|&[a, b, c]| a != b && b != c && a != c)
+        .flatten()
+        .collect();
+    rebuild(mesh, &sources, None, indices)
+}
+
+/// Reduce the mesh to at most `target_triangles` triangles by collapsing edges, using quadric
+/// error metrics to choose the collapses which change the shape least
+///
+/// Each collapse moves a vertex onto one of its neighbors, so the remaining vertices keep their
+/// original attributes. Boundary edges are weighted to stay in place, and collapses which would
+/// flip a triangle or make the surface non-manifold are skipped, so the target may not be reached
+/// if no allowed collapses remain.
+pub fn simplify(mesh: &Mesh, target_triangles: usize) -> Result<Mesh, MeshError> {
+    let (groups, group_count) = position_groups(mesh);
+    let mut members: Vec<Vec<u32>> = vec![vec![]; group_count];
+    let mut positions = vec![DVec3::ZERO; group_count];
+    for (vertex, &group) in groups.iter().enumerate() {
+        members[group as usize].push(vertex as u32);
+        positions[group as usize] = Vec3::from(mesh.positions()[vertex]).as_dvec3();
+    }
+    let triangles = mesh
+        .triangles()
+        .map(|triangle| triangle.map(|vertex| groups[vertex as usize]))
+        .collect();
+    let mut simplifier = Simplifier::new(positions, triangles);
+    simplifier.run(target_triangles);
+
+    // Corners whose position was collapsed away move to the vertex at the new position with the
+    // most similar attributes
+    let distance = |a: u32, b: u32| {
+        let (a, b) = (a as usize, b as usize);
+        let attribute =
+            |x: &[f32], y: &[f32]| -> f32 { x.iter().zip(y).map(|(x, y)| (x - y) * (x - y)).sum() };
+        4.0 * attribute(&mesh.normals()[a], &mesh.normals()[b])
+            + attribute(&mesh.colors()[a], &mesh.colors()[b])
+            + mesh.uvs().map_or(0.0, |uvs| attribute(&uvs[a], &uvs[b]))
+    };
+    let mut sources = vec![];
+    let mut remap: HashMap<u32, u32> = HashMap::new();
+    let mut indices = vec![];
+    for (triangle, corners) in mesh.triangles().enumerate() {
+        if !simplifier.alive[triangle] {
+            continue;
+        }
+        for vertex in corners {
+            let group = groups[vertex as usize];
+            let target = simplifier.resolve(group);
+            let source = if target == group {
+                vertex
+            } else {
+                *members[target as usize]
+                    .iter()
+                    .min_by(|&&a, &&b| distance(vertex, a).total_cmp(&distance(vertex, b)))
+                    .unwrap()
+            };
+            let index = *remap.entry(source).or_insert_with(|| {
+                sources.push(source);
+                sources.len() as u32 - 1
+            });
+            indices.push(index);
+        }
+    }
+    rebuild(mesh, &sources, None, indices)
+}
+
+/// Assign each vertex a group shared by the vertices with identical positions, returning the
+/// groups and the number of groups
+fn position_groups(mesh: &Mesh) -> (Vec<u32>, usize) {
+    let mut groups: HashMap<[u32; 3], u32> = HashMap::new();
+    let vertex_groups = mesh
+        .positions()
+        .iter()
+        .map(|position| {
+            let next = groups.len() as u32;
+            // Adding zero turns -0.0 into 0.0, so the two have the same bits
+            *groups
+                .entry(position.map(|value| (value + 0.0).to_bits()))
+                .or_insert(next)
+        })
+        .collect();
+    (vertex_groups, groups.len())
+}
+
+/// Build a mesh from a selection of another mesh's vertices, optionally with new normals
+fn rebuild(
+    mesh: &Mesh,
+    sources: &[u32],
+    normals: Option<Vec<[f32; 3]>>,
+    indices: Vec<u32>,
+) -> Result<Mesh, MeshError> {
+    let select = |attribute: &[[f32; 3]]| -> Vec<[f32; 3]> {
+        sources
+            .iter()
+            .map(|&vertex| attribute[vertex as usize])
+            .collect()
+    };
+    let uvs = mesh
+        .uvs()
+        .map(|uvs| sources.iter().map(|&vertex| uvs[vertex as usize]).collect());
+    Mesh::new(
+        select(mesh.positions()),
+        select(mesh.colors()),
+        normals.unwrap_or_else(|| select(mesh.normals())),
+        uvs,
+        Indices::from_u32(indices),
+    )
+}
+
+/// Symmetric 4x4 matrix giving the sum of weighted squared distances from a point to a set of
+/// planes, stored as its upper triangle
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// The plane through `point` with unit `normal`
+    fn plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        let d = -normal.dot(point);
+        Self([
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ])
+        .scale(weight)
+    }
+
+    fn scale(self, weight: f64) -> Self {
+        Self(self.0.map(|value| value * weight))
+    }
+
+    fn error(&self, point: DVec3) -> f64 {
+        let [x, y, z] = point.to_array();
+        let q = &self.0;
+        q[0] * x * x
+            + q[4] * y * y
+            + q[7] * z * z
+            + 2.0 * (q[1] * x * y + q[2] * x * z + q[5] * y * z)
+            + 2.0 * (q[3] * x + q[6] * y + q[8] * z)
+            + q[9]
+    }
+}
+
+impl Add for Quadric {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for Quadric {
+    fn add_assign(&mut self, other: Self) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+    }
+}
+
+/// Candidate collapse of `from` onto `to`, valid while neither vertex has changed
+#[derive(Debug)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    versions: [u32; 2],
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed, so the binary heap pops the cheapest collapse first. Ties are broken by vertex
+    /// so the result doesn't depend on the order of insertion.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| (other.from, other.to).cmp(&(self.from, self.to)))
+    }
+}
+
+/// Edge collapse state over vertices identified by position
+struct Simplifier {
+    positions: Vec<DVec3>,
+    quadrics: Vec<Quadric>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    alive_count: usize,
+    /// Indices of the live triangles using each vertex
+    vertex_triangles: Vec<Vec<usize>>,
+    /// Incremented whenever a vertex's quadric or neighborhood changes due to a collapse onto it
+    versions: Vec<u32>,
+    /// The vertex each collapsed vertex was moved onto
+    collapsed_onto: Vec<Option<u32>>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+    fn new(positions: Vec<DVec3>, triangles: Vec<[u32; 3]>) -> Self {
+        let vertex_count = positions.len();
+        let alive: Vec<bool> = triangles
+            .iter()
+            .map(|&[a, b, c]| a != b && b != c && a != c)
+            .collect();
+        let mut vertex_triangles = vec![vec![]; vertex_count];
+        let mut edge_uses: HashMap<(u32, u32), u32> = HashMap::new();
+        for (triangle, &[a, b, c]) in triangles.iter().enumerate() {
+            if !alive[triangle] {
+                continue;
+            }
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                vertex_triangles[from as usize].push(triangle);
+                *edge_uses.entry((from.min(to), from.max(to))).or_default() += 1;
+            }
+        }
+
+        let mut simplifier = Self {
+            quadrics: vec![Quadric::default(); vertex_count],
+            alive_count: alive.iter().filter(|&&alive| alive).count(),
+            alive,
+            positions,
+            triangles,
+            vertex_triangles,
+            versions: vec![0; vertex_count],
+            collapsed_onto: vec![None; vertex_count],
+            heap: BinaryHeap::new(),
+        };
+        for triangle in 0..simplifier.triangles.len() {
+            if !simplifier.alive[triangle] {
+                continue;
+            }
+            let corners = simplifier.triangles[triangle];
+            let normal = simplifier.normal(corners);
+            let length = normal.length();
+            if length == 0.0 {
+                continue;
+            }
+            let unit_normal = normal / length;
+            let point = simplifier.positions[corners[0] as usize];
+            // The cross product's length is twice the triangle's area
+            let plane = Quadric::plane(unit_normal, point, length / 2.0);
+            for vertex in corners {
+                simplifier.quadrics[vertex as usize] += plane;
+            }
+
+            // Boundary edges get a plane perpendicular to the triangle, so moving their vertices
+            // off the boundary line is costly
+            for corner in 0..3 {
+                let (from, to) = (corners[corner], corners[(corner + 1) % 3]);
+                if edge_uses[&(from.min(to), from.max(to))] != 1 {
+                    continue;
+                }
+                let start = simplifier.positions[from as usize];
+                let edge = simplifier.positions[to as usize] - start;
+                let Some(normal) = edge.cross(unit_normal).try_normalize() else {
+                    continue;
+                };
+                let plane = Quadric::plane(normal, start, edge.length_squared() * BOUNDARY_WEIGHT);
+                simplifier.quadrics[from as usize] += plane;
+                simplifier.quadrics[to as usize] += plane;
+            }
+        }
+        for vertex in 0..vertex_count as u32 {
+            for neighbor in simplifier.neighbors(vertex) {
+                simplifier.push(vertex, neighbor);
+            }
+        }
+        simplifier
+    }
+
+    fn run(&mut self, target_triangles: usize) {
+        while self.alive_count > target_triangles {
+            let Some(collapse) = self.heap.pop() else {
+                break;
+            };
+            let Collapse { from, to, .. } = collapse;
+            let current = [self.versions[from as usize], self.versions[to as usize]];
+            let stale = collapse.versions != current
+                || self.collapsed_onto[from as usize].is_some()
+                || self.collapsed_onto[to as usize].is_some();
+            if !stale && self.can_collapse(from, to) {
+                self.collapse(from, to);
+            }
+        }
+    }
+
+    /// The vertex which `vertex` was eventually collapsed onto, or itself if it remains
+    fn resolve(&self, mut vertex: u32) -> u32 {
+        while let Some(onto) = self.collapsed_onto[vertex as usize] {
+            vertex = onto;
+        }
+        vertex
+    }
+
+    fn normal(&self, [a, b, c]: [u32; 3]) -> DVec3 {
+        let [a, b, c] = [a, b, c].map(|vertex| self.positions[vertex as usize]);
+        (b - a).cross(c - a)
+    }
+
+    /// Sorted vertices sharing a live triangle with `vertex`
+    fn neighbors(&self, vertex: u32) -> Vec<u32> {
+        let mut neighbors: Vec<u32> = self.vertex_triangles[vertex as usize]
+            .iter()
+            .flat_map(|&triangle| self.triangles[triangle])
+            .filter(|&other| other != vertex)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn push(&mut self, from: u32, to: u32) {
+        let quadric = self.quadrics[from as usize] + self.quadrics[to as usize];
+        self.heap.push(Collapse {
+            cost: quadric.error(self.positions[to as usize]),
+            from,
+            to,
+            versions: [self.versions[from as usize], self.versions[to as usize]],
+        });
+    }
+
+    fn can_collapse(&self, from: u32, to: u32) -> bool {
+        // An edge in a manifold surface has at most two vertices adjacent to both its ends, the
+        // opposite corners of its triangles. Collapsing an edge with more would pinch the surface.
+        let to_neighbors = self.neighbors(to);
+        let shared = self
+            .neighbors(from)
+            .iter()
+            .filter(|neighbor| to_neighbors.binary_search(neighbor).is_ok())
+            .count();
+        if shared > 2 {
+            return false;
+        }
+
+        for &triangle in &self.vertex_triangles[from as usize] {
+            let corners = self.triangles[triangle];
+            if corners.contains(&to) {
+                continue;
+            }
+            let moved = corners.map(|vertex| if vertex == from { to } else { vertex });
+            let (old, new) = (self.normal(corners), self.normal(moved));
+            if new.dot(old) <= 0.0 {
+                return false;
+            }
+            // Folding a triangle onto another would make a double sided sheet
+            let mut sorted = moved;
+            sorted.sort_unstable();
+            let duplicate = self.vertex_triangles[to as usize].iter().any(|&other| {
+                let mut other = self.triangles[other];
+                other.sort_unstable();
+                other == sorted
+            });
+            if duplicate {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn collapse(&mut self, from: u32, to: u32) {
+        for triangle in std::mem::take(&mut self.vertex_triangles[from as usize]) {
+            let corners = &mut self.triangles[triangle];
+            if corners.contains(&to) {
+                self.alive[triangle] = false;
+                self.alive_count -= 1;
+            } else {
+                for vertex in corners.iter_mut() {
+                    if *vertex == from {
+                        *vertex = to;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(triangle);
+            }
+        }
+        // Also drop the removed triangles from the other vertices
+        for vertex in self.neighbors_including_removed(to) {
+            let alive = &self.alive;
+            self.vertex_triangles[vertex as usize].retain(|&triangle| alive[triangle]);
+        }
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize] += quadric;
+        self.collapsed_onto[from as usize] = Some(to);
+        self.versions[to as usize] += 1;
+
+        for neighbor in self.neighbors(to) {
+            self.push(to, neighbor);
+            self.push(neighbor, to);
+        }
+    }
+
+    /// `vertex` and every vertex sharing a triangle with it, including triangles removed by the
+    /// current collapse
+    fn neighbors_including_removed(&self, vertex: u32) -> Vec<u32> {
+        let mut vertices: Vec<u32> = self.vertex_triangles[vertex as usize]
+            .iter()
+            .flat_map(|&triangle| self.triangles[triangle])
+            .collect();
+        vertices.push(vertex);
+        vertices.sort_unstable();
+        vertices.dedup();
+        vertices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Primitive;
+    use std::f32::consts::PI;
+
+    fn assert_near(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            Vec3::from(a).abs_diff_eq(Vec3::from(b), 1e-5),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    /// Assert that every corner's normal is its triangle's normal
+    fn assert_faceted(mesh: &Mesh) {
+        for [a, b, c] in mesh.triangles() {
+            let [pa, pb, pc] =
+                [a, b, c].map(|vertex| Vec3::from(mesh.positions()[vertex as usize]));
+            let face = (pb - pa).cross(pc - pa).normalize().to_array();
+            for vertex in [a, b, c] {
+                assert_near(mesh.normals()[vertex as usize], face);
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_normals_are_shared_and_area_weighted() {
+        let cube = Mesh::cube();
+        let smooth = smooth_normals(&cube);
+        assert_eq!(smooth.vertex_count(), 24);
+        for (vertex, position) in smooth.positions().iter().enumerate() {
+            let normal = smooth.normals()[vertex];
+            // Each corner's normal points out between its three faces
+            for axis in 0..3 {
+                assert!(normal[axis] * position[axis] > 0.0, "{normal:?}");
+            }
+            for other in 0..24 {
+                if cube.positions()[other] == *position {
+                    assert_eq!(smooth.normals()[other], normal);
+                }
+            }
+        }
+
+        // A triangle facing +Z with area 1/2 and one facing +X with area 1, sharing an edge
+        let mut builder = Mesh::builder();
+        for position in [
+            [-1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, -2.0],
+        ] {
+            builder.vertex(position, [1.0; 3], [0.0, 0.0, 1.0]);
+        }
+        builder.triangle([0, 1, 2]).triangle([1, 3, 2]);
+        let tent = smooth_normals(&builder.build().unwrap());
+        assert_near(
+            tent.normals()[1],
+            Vec3::new(2.0, 0.0, 1.0).normalize().to_array(),
+        );
+        assert_eq!(tent.normals()[0], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn crease_angle_splits_sharp_edges() {
+        let smooth = smooth_normals(&Mesh::cube());
+        // The cube's faces meet at 90 degrees, so a smaller crease angle restores its faces
+        let flat = flat_normals(&smooth, PI / 4.0).unwrap();
+        assert_eq!(flat, Mesh::cube());
+        let rounded = flat_normals(&Mesh::cube(), PI).unwrap();
+        assert_eq!(rounded, smooth);
+
+        // A sphere has no sharp edges, so no vertices are split. Its unused pole vertices are
+        // dropped.
+        let sphere = Primitive::from_name("sphere").unwrap().mesh();
+        let creased = flat_normals(&sphere, PI / 6.0).unwrap();
+        assert_eq!(creased.vertex_count(), sphere.vertex_count() - 2);
+        for (position, normal) in creased.positions().iter().zip(creased.normals()) {
+            assert!(Vec3::from(*position).dot(Vec3::from(*normal)) > 0.99);
+        }
+        let faceted = flat_normals(&sphere, 0.0).unwrap();
+        assert_faceted(&faceted);
+    }
+
+    /// `plane` with each vertex's texture coordinates mapped through `uv`
+    fn with_uvs(plane: &Mesh, uv: impl Fn([f32; 2]) -> [f32; 2]) -> Mesh {
+        Mesh::new(
+            plane.positions().to_vec(),
+            plane.colors().to_vec(),
+            plane.normals().to_vec(),
+            Some(
+                plane
+                    .uvs()
+                    .unwrap()
+                    .iter()
+                    .map(|&value| uv(value))
+                    .collect(),
+            ),
+            plane.indices().clone(),
+        )
+        .unwrap()
+    }
+
+    fn assert_tangent(actual: [f32; 4], expected: [f32; 4]) {
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-5),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn tangents_follow_texture_coordinates() {
+        let cube = Mesh::cube();
+        let untextured = Mesh::new(
+            cube.positions().to_vec(),
+            cube.colors().to_vec(),
+            cube.normals().to_vec(),
+            None,
+            cube.indices().clone(),
+        )
+        .unwrap();
+        assert_eq!(tangents(&untextured), None);
+
+        // u increases along +X and v decreases along +Y
+        let plane = Primitive::Plane { subdivisions: 2 }.mesh();
+        let (split, plane_tangents) = tangents(&plane).unwrap();
+        assert_eq!(split.vertex_count(), plane.vertex_count());
+        for tangent in plane_tangents {
+            assert_tangent(tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+
+        // Mirroring the texture horizontally flips the tangent and the handedness
+        let mirrored = with_uvs(&plane, |[u, v]| [1.0 - u, v]);
+        for tangent in tangents(&mirrored).unwrap().1 {
+            assert_tangent(tangent, [-1.0, 0.0, 0.0, -1.0]);
+        }
+
+        let cube = Primitive::Cube { subdivisions: 2 }.mesh();
+        let (cube, cube_tangents) = tangents(&cube).unwrap();
+        assert_eq!(cube_tangents.len(), cube.vertex_count());
+        for (tangent, normal) in cube_tangents.iter().zip(cube.normals()) {
+            let xyz = Vec3::from_slice(tangent);
+            assert!((xyz.length() - 1.0).abs() < 1e-5);
+            assert!(xyz.dot(Vec3::from(*normal)).abs() < 1e-5);
+            assert_eq!(tangent[3], 1.0);
+        }
+    }
+
+    #[test]
+    fn tangents_split_mirrored_seams() {
+        // The texture is mirrored about the middle column of vertices, so each of them needs a
+        // tangent for each side
+        let plane = Primitive::Plane { subdivisions: 2 }.mesh();
+        let mirrored = with_uvs(&plane, |[u, v]| [(2.0 * u - 1.0).abs(), v]);
+        let seam = plane
+            .positions()
+            .iter()
+            .filter(|position| position[0] == 0.0)
+            .count();
+        assert!(seam > 0);
+        let (split, split_tangents) = tangents(&mirrored).unwrap();
+        assert_eq!(split.vertex_count(), plane.vertex_count() + seam);
+        assert_eq!(split.validate(), Ok(()));
+        for triangle in split.triangles() {
+            let center = triangle
+                .iter()
+                .map(|&vertex| split.positions()[vertex as usize][0])
+                .sum::<f32>();
+            let expected = if center < 0.0 {
+                [-1.0, 0.0, 0.0, -1.0]
+            } else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+            for vertex in triangle {
+                assert_tangent(split_tangents[vertex as usize], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn weld_merges_nearby_vertices() {
+        // Splitting every corner of the cube and then welding restores its shared vertices
+        let cube = Mesh::cube();
+        let triangles: Vec<[u32; 3]> = cube.triangles().collect();
+        let split = mesh::flat_mesh(cube.positions(), cube.colors(), None, &triangles).unwrap();
+        assert_eq!(split.vertex_count(), 36);
+        let welded = weld(&split, 0.0).unwrap();
+        assert_eq!(welded.vertex_count(), 24);
+        assert_eq!(welded.triangle_count(), 12);
+
+        // A vertex nudged by less than the tolerance merges with its neighbor, and the triangle
+        // between them is dropped
+        let mut builder = Mesh::builder();
+        let up = [0.0, 0.0, 1.0];
+        for position in [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1e-4, 0.0],
+        ] {
+            builder.vertex(position, [1.0; 3], up);
+        }
+        builder.triangle([0, 1, 2]).triangle([1, 3, 2]);
+        let mesh = builder.build().unwrap();
+        assert_eq!(weld(&mesh, 1e-5).unwrap().vertex_count(), 4);
+        let welded = weld(&mesh, 1e-3).unwrap();
+        assert_eq!(welded.vertex_count(), 3);
+        assert_eq!(welded.triangle_count(), 1);
+    }
+
+    #[test]
+    fn simplify_keeps_flat_faces_and_corners() {
+        let cube = Primitive::Cube { subdivisions: 4 }.mesh();
+        let simplified = simplify(&cube, 12).unwrap();
+        assert_eq!(simplified.triangle_count(), 12);
+        assert_eq!(simplified.vertex_count(), 24);
+        for &position in simplified.positions() {
+            assert!(position.iter().all(|value| value.abs() == 1.0));
+        }
+        // Each corner keeps the normal of its own face
+        assert_faceted(&simplified);
+
+        // Boundary vertices stay on the boundary
+        let plane = Primitive::Plane { subdivisions: 8 }.mesh();
+        let simplified = simplify(&plane, 2).unwrap();
+        assert_eq!(simplified.triangle_count(), 2);
+        for &[x, y, _] in simplified.positions() {
+            assert_eq!([x.abs(), y.abs()], [1.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn simplify_reduces_curved_surfaces() {
+        let sphere = Primitive::Icosphere { subdivisions: 3 }.mesh();
+        assert_eq!(simplify(&sphere, 5000).unwrap().triangle_count(), 1280);
+        let simplified = simplify(&sphere, 320).unwrap();
+        assert!((300..=320).contains(&simplified.triangle_count()));
+        assert_eq!(simplified.validate(), Ok(()));
+        assert_eq!(simplified.check_winding(), Ok(()));
+        // Vertices are only moved onto each other, so they stay on the sphere
+        for &position in simplified.positions() {
+            assert!((Vec3::from(position).length() - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,522 @@
+//! Scenes of meshes placed by model matrices
+//!
+//! A `Scene` is the flattened form of a model file's node hierarchy: every mesh primitive placed
+//! in the scene becomes an `Instance` with its material and its node's world transform. Meshes,
+//! materials, and textures are shared between instances by index.
+
+use crate::mesh::Mesh;
+use crate::yuv::YuvFrame;
+use glam::{Mat4, Vec3};
+use std::fmt;
+use std::path::Path;
+use zune_jpeg::JpegDecoder;
+use zune_jpeg::errors::DecodeErrors;
+use zune_jpeg::zune_core::bytestream::ZCursor;
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+
+/// RGBA8 image with sRGB encoded color channels and straight alpha, or a YUV video frame which is
+/// converted to one when it's uploaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    pixels: Pixels,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pixels {
+    Rgba8(Vec<u8>),
+    Yuv(YuvFrame),
+}
+
+impl Texture {
+    /// Returns `None` if the size is zero or doesn't match the number of pixels
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Option<Self> {
+        let expected = width as usize * height as usize * 4;
+        (width > 0 && height > 0 && pixels.len() == expected).then_some(Self {
+            width,
+            height,
+            pixels: Pixels::Rgba8(pixels),
+        })
+    }
+
+    /// Decode a PNG image, converting it to RGBA8
+    pub fn from_png(bytes: &[u8]) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(std::io::Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let size = reader
+            .output_buffer_size()
+            .ok_or(png::DecodingError::LimitsExceeded)?;
+        let mut buffer = vec![0; size];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|gray| [gray[0], gray[0], gray[0], gray[1]])
+                .collect(),
+            png::ColorType::Grayscale => buffer
+                .iter()
+                .flat_map(|&gray| [gray, gray, gray, 255])
+                .collect(),
+            // EXPAND converts indexed images to RGB or RGBA
+            png::ColorType::Indexed => unreachable!(),
+        };
+        Self::new(info.width, info.height, pixels).ok_or(png::DecodingError::LimitsExceeded)
+    }
+
+    /// Decode a JPEG image, converting it to RGBA8
+    pub fn from_jpeg(bytes: &[u8]) -> Result<Self, DecodeErrors> {
+        let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+        let mut decoder = JpegDecoder::new_with_options(ZCursor::new(bytes), options);
+        let pixels = decoder.decode()?;
+        // Headers were decoded by `decode`
+        let info = decoder.info().unwrap();
+        Self::new(info.width as u32, info.height as u32, pixels)
+            .ok_or_else(|| DecodeErrors::Format("Unexpected decoded image size".to_string()))
+    }
+
+    /// Decode a PNG or JPEG image, detecting the format from its signature
+    pub fn decode(bytes: &[u8]) -> Result<Self, TextureError> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Ok(Self::from_png(bytes)?)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Ok(Self::from_jpeg(bytes)?)
+        } else {
+            Err(TextureError::UnknownFormat)
+        }
+    }
+
+    /// Read and decode a PNG or JPEG image file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGBA8 pixels, or `None` for a YUV frame
+    pub fn pixels(&self) -> Option<&[u8]> {
+        match &self.pixels {
+            Pixels::Rgba8(pixels) => Some(pixels),
+            Pixels::Yuv(_) => None,
+        }
+    }
+
+    pub fn yuv(&self) -> Option<&YuvFrame> {
+        match &self.pixels {
+            Pixels::Rgba8(_) => None,
+            Pixels::Yuv(frame) => Some(frame),
+        }
+    }
+}
+
+impl From<YuvFrame> for Texture {
+    fn from(frame: YuvFrame) -> Self {
+        Self {
+            width: frame.width(),
+            height: frame.height(),
+            pixels: Pixels::Yuv(frame),
+        }
+    }
+}
+
+/// Reason an image could not be loaded as a texture
+#[derive(Debug)]
+pub enum TextureError {
+    Io(std::io::Error),
+    Png(png::DecodingError),
+    Jpeg(DecodeErrors),
+    /// The data is neither a PNG nor a JPEG image
+    UnknownFormat,
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Png(error) => write!(f, "Invalid PNG image: {error}"),
+            Self::Jpeg(error) => write!(f, "Invalid JPEG image: {error}"),
+            Self::UnknownFormat => write!(f, "Unknown image format, expected PNG or JPEG"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Png(error) => Some(error),
+            Self::Jpeg(error) => Some(error),
+            Self::UnknownFormat => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TextureError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<png::DecodingError> for TextureError {
+    fn from(error: png::DecodingError) -> Self {
+        Self::Png(error)
+    }
+}
+
+impl From<DecodeErrors> for TextureError {
+    fn from(error: DecodeErrors) -> Self {
+        Self::Jpeg(error)
+    }
+}
+
+/// Surface appearance of an instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    /// Linear RGBA factor multiplied with the vertex color and base color texture. Alpha is
+    /// ignored, since every material is rendered opaque.
+    pub base_color: [f32; 4],
+    /// Index into the scene's textures. The mesh must have texture coordinates for the texture to
+    /// be visible.
+    pub base_color_texture: Option<usize>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            base_color_texture: None,
+        }
+    }
+}
+
+/// A mesh drawn with a material and model matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instance {
+    /// Index into the scene's meshes
+    pub mesh: usize,
+    /// Index into the scene's materials
+    pub material: usize,
+    /// Transform from mesh space to scene space
+    pub transform: Mat4,
+}
+
+/// Reason a scene failed validation
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneError {
+    /// An instance or material refers to a mesh, material, or texture which doesn't exist
+    IndexOutOfBounds {
+        kind: &'static str,
+        index: usize,
+        count: usize,
+    },
+    /// An instance's transform contains NaN or infinite values
+    InvalidTransform { instance: usize },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IndexOutOfBounds { kind, index, count } => {
+                write!(
+                    f,
+                    "{kind} index {index} is out of bounds for {count} {kind}s"
+                )
+            }
+            Self::InvalidTransform { instance } => {
+                write!(f, "Instance {instance} has a non-finite transform")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene {
+    meshes: Vec<Mesh>,
+    materials: Vec<Material>,
+    textures: Vec<Texture>,
+    instances: Vec<Instance>,
+}
+
+impl Scene {
+    /// Create a scene, validating that every index refers to an existing item
+    pub fn new(
+        meshes: Vec<Mesh>,
+        materials: Vec<Material>,
+        textures: Vec<Texture>,
+        instances: Vec<Instance>,
+    ) -> Result<Self, SceneError> {
+        let check = |kind, index, count| {
+            if index < count {
+                Ok(())
+            } else {
+                Err(SceneError::IndexOutOfBounds { kind, index, count })
+            }
+        };
+        for material in &materials {
+            if let Some(texture) = material.base_color_texture {
+                check("texture", texture, textures.len())?;
+            }
+        }
+        for (index, instance) in instances.iter().enumerate() {
+            check("mesh", instance.mesh, meshes.len())?;
+            check("material", instance.material, materials.len())?;
+            if !instance.transform.is_finite() {
+                return Err(SceneError::InvalidTransform { instance: index });
+            }
+        }
+        Ok(Self {
+            meshes,
+            materials,
+            textures,
+            instances,
+        })
+    }
+
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    pub fn textures(&self) -> &[Texture] {
+        &self.textures
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// The kmscube cube with white vertices and a texture on each face, like kmscube's RGBA mode
+    ///
+    /// The faces are front, back, right, left, top, and bottom, and face `i` shows texture `i`
+    /// modulo the number of textures. A single texture therefore covers every face, while six give
+    /// each face its own image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `textures` is empty.
+    pub fn textured_cube(textures: Vec<Texture>) -> Self {
+        assert!(!textures.is_empty(), "a textured cube needs a texture");
+        let cube = Mesh::cube();
+        let uvs = cube.uvs().unwrap();
+        let triangles: Vec<_> = cube.triangles().collect();
+        let mut meshes = vec![];
+        let mut materials = vec![];
+        let mut instances = vec![];
+        // Each face has 4 vertices and 2 triangles
+        for face in 0..6 {
+            let mut builder = Mesh::builder();
+            let vertices = face * 4..face * 4 + 4;
+            for ((&position, &normal), &uv) in cube.positions()[vertices.clone()]
+                .iter()
+                .zip(&cube.normals()[vertices.clone()])
+                .zip(&uvs[vertices])
+            {
+                builder.vertex_with_uv(position, [1.0; 3], normal, uv);
+            }
+            for triangle in &triangles[face * 2..face * 2 + 2] {
+                builder.triangle(triangle.map(|index| index - face as u32 * 4));
+            }
+            meshes.push(builder.build().unwrap());
+            materials.push(Material {
+                base_color_texture: Some(face % textures.len()),
+                ..Default::default()
+            });
+            instances.push(Instance {
+                mesh: face,
+                material: face,
+                transform: Mat4::IDENTITY,
+            });
+        }
+        Self {
+            meshes,
+            materials,
+            textures,
+            instances,
+        }
+    }
+
+    /// Center the bounding box of every instance on the origin and scale the scene uniformly so
+    /// its largest dimension is 2 units, matching the built-in cube
+    pub fn fit_to_cube(&mut self) {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+        for instance in &self.instances {
+            for &position in self.meshes[instance.mesh].positions() {
+                let position = instance.transform.transform_point3(Vec3::from(position));
+                min = min.min(position);
+                max = max.max(position);
+            }
+        }
+        if !min.is_finite() || !max.is_finite() {
+            return;
+        }
+        let center = (min + max) / 2.0;
+        let half_extent = ((max - min) / 2.0).max_element();
+        // A single point has no size to scale
+        let scale = if half_extent > 0.0 {
+            1.0 / half_extent
+        } else {
+            1.0
+        };
+        let fit = Mat4::from_scale(Vec3::splat(scale)) * Mat4::from_translation(-center);
+        for instance in &mut self.instances {
+            instance.transform = fit * instance.transform;
+        }
+    }
+}
+
+impl From<Mesh> for Scene {
+    /// A scene drawing a single mesh untransformed with the default material
+    fn from(mesh: Mesh) -> Self {
+        Self {
+            meshes: vec![mesh],
+            materials: vec![Material::default()],
+            textures: vec![],
+            instances: vec![Instance {
+                mesh: 0,
+                material: 0,
+                transform: Mat4::IDENTITY,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_indices() {
+        let instance = Instance {
+            mesh: 0,
+            material: 1,
+            transform: Mat4::IDENTITY,
+        };
+        assert_eq!(
+            Scene::new(
+                vec![Mesh::cube()],
+                vec![Material::default()],
+                vec![],
+                vec![instance]
+            ),
+            Err(SceneError::IndexOutOfBounds {
+                kind: "material",
+                index: 1,
+                count: 1
+            })
+        );
+        let material = Material {
+            base_color_texture: Some(0),
+            ..Default::default()
+        };
+        assert!(Scene::new(vec![], vec![material], vec![], vec![]).is_err());
+    }
+
+    #[test]
+    fn fits_instances_to_cube() {
+        let mut scene = Scene::from(Mesh::cube());
+        let instance = Instance {
+            transform: Mat4::from_translation(Vec3::new(4.0, 0.0, 0.0)),
+            ..scene.instances[0].clone()
+        };
+        scene.instances.push(instance);
+        scene.fit_to_cube();
+
+        // The two cubes span x from -1 to 5, so they are scaled by 1/3 and shifted by -2
+        let corner =
+            |instance: &Instance, x| instance.transform.transform_point3(Vec3::new(x, 1.0, 1.0));
+        assert!(
+            corner(&scene.instances[0], -1.0)
+                .abs_diff_eq(Vec3::new(-1.0, 1.0 / 3.0, 1.0 / 3.0), 1e-6)
+        );
+        assert!(
+            corner(&scene.instances[1], 1.0)
+                .abs_diff_eq(Vec3::new(1.0, 1.0 / 3.0, 1.0 / 3.0), 1e-6)
+        );
+    }
+
+    #[test]
+    fn converts_png_color_types() {
+        let encode = |color_type, data: &[u8]| {
+            let mut bytes = vec![];
+            let mut encoder = png::Encoder::new(&mut bytes, 1, 1);
+            encoder.set_color(color_type);
+            encoder
+                .write_header()
+                .unwrap()
+                .write_image_data(data)
+                .unwrap();
+            bytes
+        };
+        let gray = Texture::from_png(&encode(png::ColorType::Grayscale, &[7])).unwrap();
+        assert_eq!(gray.pixels().unwrap(), [7, 7, 7, 255]);
+        let rgb = Texture::from_png(&encode(png::ColorType::Rgb, &[1, 2, 3])).unwrap();
+        assert_eq!(rgb.pixels().unwrap(), [1, 2, 3, 255]);
+        assert!(Texture::from_png(b"not a png").is_err());
+    }
+
+    #[test]
+    fn decodes_jpeg_by_signature() {
+        // Orange on the left half and blue on the right, lossily compressed
+        let bytes = include_bytes!("../tests/textures/rgb.jpg");
+        let rgb = Texture::decode(bytes).unwrap();
+        assert_eq!((rgb.width(), rgb.height()), (16, 8));
+        let pixel = |x: usize| &rgb.pixels().unwrap()[x * 4..x * 4 + 4];
+        for (actual, expected) in pixel(2).iter().zip([255, 128, 0, 255]) {
+            assert!(actual.abs_diff(expected) <= 4, "{:?}", pixel(2));
+        }
+        for (actual, expected) in pixel(13).iter().zip([0, 64, 255, 255]) {
+            assert!(actual.abs_diff(expected) <= 4, "{:?}", pixel(13));
+        }
+
+        let gray = Texture::decode(include_bytes!("../tests/textures/gray.jpg")).unwrap();
+        assert_eq!(&gray.pixels().unwrap()[..4], [100, 100, 100, 255]);
+
+        assert!(matches!(
+            Texture::decode(b"GIF89a"),
+            Err(TextureError::UnknownFormat)
+        ));
+        assert!(matches!(
+            Texture::decode(&bytes[..100]),
+            Err(TextureError::Jpeg(_))
+        ));
+    }
+
+    #[test]
+    fn textured_cube_faces_use_textures_in_turn() {
+        let texture = |value| Texture::new(1, 1, vec![value; 4]).unwrap();
+        let cube = Scene::textured_cube(vec![texture(0), texture(1)]);
+        assert_eq!(cube.meshes().len(), 6);
+        let textures: Vec<_> = cube
+            .materials()
+            .iter()
+            .map(|material| material.base_color_texture.unwrap())
+            .collect();
+        assert_eq!(textures, [0, 1, 0, 1, 0, 1]);
+        for mesh in cube.meshes() {
+            assert_eq!(mesh.vertex_count(), 4);
+            assert_eq!(mesh.colors(), [[1.0; 3]; 4]);
+            assert_eq!(mesh.validate(), Ok(()));
+            assert_eq!(mesh.check_winding(), Ok(()));
+        }
+    }
+}
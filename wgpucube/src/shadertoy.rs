@@ -0,0 +1,599 @@
+//! Shadertoy compatible fragment shaders
+//!
+//! Like kmscube's `shadertoy` mode, a fragment shader written for Shadertoy can be rendered into a
+//! texture on the cube's faces, or over the whole frame instead of the cube. Shaders define a
+//! `mainImage` function in GLSL or WGSL and read the `iResolution`, `iTime`, `iFrame`, and `iMouse`
+//! inputs. They are wrapped with an entry point declaring the inputs, then parsed and validated
+//! with naga when they're loaded, so errors are reported with the line and column in the shader
+//! file instead of failing when the pipeline is created.
+//!
+//! GLSL shaders define Shadertoy's usual `void mainImage(out vec4 fragColor, in vec2 fragCoord)`.
+//! WGSL shaders define `fn mainImage(fragCoord: vec2<f32>) -> vec4<f32>`. Texture channels
+//! (`iChannel0` to `iChannel3`) are not supported.
+
+use crate::texture::fullscreen_pipeline;
+use glam::{Vec2, Vec4};
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+use winit::dpi::PhysicalSize;
+
+/// Size of the texture the shader renders into when it's applied to the cube's faces
+const TEXTURE_SIZE: u32 = 512;
+
+/// Format of that texture, which the cube samples like any other texture
+const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Declares the inputs before the user's GLSL. The declarations must match `InputUniforms`.
+const GLSL_HEADER: &str = "#version 450
+layout(set = 0, binding = 0) uniform ShadertoyInputs {
+    vec3 iResolution;
+    float iTime;
+    vec4 iMouse;
+    int iFrame;
+};
+";
+
+/// Calls the user's `mainImage` from a GLSL entry point
+const GLSL_FOOTER: &str = "
+layout(location = 0) out vec4 shadertoy_frag_color;
+
+// Shadertoy writes sRGB encoded colors, and the render target encodes them again
+vec3 shadertoy_srgb_to_linear(vec3 color) {
+    return mix(
+        pow((color + 0.055) / 1.055, vec3(2.4)),
+        color / 12.92,
+        lessThanEqual(color, vec3(0.04045))
+    );
+}
+
+void main() {
+    vec4 color = vec4(0.0);
+    // Shadertoy's origin is at the bottom left
+    mainImage(color, vec2(gl_FragCoord.x, iResolution.y - gl_FragCoord.y));
+    shadertoy_frag_color = vec4(shadertoy_srgb_to_linear(clamp(color.rgb, 0.0, 1.0)), 1.0);
+}
+";
+
+/// Declares the inputs and calls the user's `mainImage` from a WGSL entry point. WGSL allows
+/// declarations in any order, so this follows the user's code and leaves its line numbers alone.
+const WGSL_WRAPPER: &str = "
+struct ShadertoyInputs {
+    resolution: vec3<f32>,
+    time: f32,
+    mouse: vec4<f32>,
+    frame: i32,
+};
+
+@group(0) @binding(0)
+var<uniform> shadertoy_inputs: ShadertoyInputs;
+
+var<private> iResolution: vec3<f32>;
+var<private> iTime: f32;
+var<private> iMouse: vec4<f32>;
+var<private> iFrame: i32;
+
+// Shadertoy writes sRGB encoded colors, and the render target encodes them again
+fn shadertoy_srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    iResolution = shadertoy_inputs.resolution;
+    iTime = shadertoy_inputs.time;
+    iMouse = shadertoy_inputs.mouse;
+    iFrame = shadertoy_inputs.frame;
+    // Shadertoy's origin is at the bottom left
+    let color = mainImage(vec2<f32>(position.x, iResolution.y - position.y));
+    let rgb = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(shadertoy_srgb_to_linear(rgb), 1.0);
+}
+";
+
+/// Name of the fragment entry point in both wrappers. naga names GLSL entry points `main`.
+const ENTRY_POINT: &str = "main";
+
+/// Language of a shader's source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Glsl,
+    Wgsl,
+}
+
+impl ShaderLanguage {
+    /// The language of a file with `path`'s extension, or `None` if it's not a shader extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "glsl" | "frag" | "fs" => Some(Self::Glsl),
+            "wgsl" => Some(Self::Wgsl),
+            _ => None,
+        }
+    }
+}
+
+/// Where a Shadertoy shader is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShadertoyTarget {
+    /// Into a texture on each face of the cube
+    #[default]
+    Cube,
+    /// Over the whole frame, instead of the cube
+    Fullscreen,
+}
+
+/// A parsed and validated Shadertoy shader
+#[derive(Debug, Clone)]
+pub struct Shadertoy {
+    module: naga::Module,
+}
+
+impl Shadertoy {
+    /// Wrap and compile a shader's source, returning every error found
+    pub fn new(source: &str, language: ShaderLanguage) -> Result<Self, ShadertoyError> {
+        // The range of the user's code within the wrapped source, and the number of lines before it
+        let (wrapped, user, line_offset) = match language {
+            ShaderLanguage::Glsl => (
+                format!("{GLSL_HEADER}{source}\n{GLSL_FOOTER}"),
+                GLSL_HEADER.len()..GLSL_HEADER.len() + source.len(),
+                GLSL_HEADER.lines().count() as u32,
+            ),
+            ShaderLanguage::Wgsl => (format!("{source}\n{WGSL_WRAPPER}"), 0..source.len(), 0),
+        };
+        let error = |span: naga::Span, message: String| CompileError {
+            location: locate(span, &wrapped, &user, line_offset),
+            message,
+        };
+        let module = match language {
+            ShaderLanguage::Glsl => {
+                let options = naga::front::glsl::Options::from(naga::ShaderStage::Fragment);
+                naga::front::glsl::Frontend::default()
+                    .parse(&options, &wrapped)
+                    .map_err(|errors| {
+                        errors
+                            .errors
+                            .into_iter()
+                            .map(|parse_error| {
+                                error(parse_error.meta, parse_error.kind.to_string())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+            }
+            ShaderLanguage::Wgsl => naga::front::wgsl::parse_str(&wrapped).map_err(|parse_error| {
+                let span = parse_error
+                    .labels()
+                    .next()
+                    .map_or(naga::Span::UNDEFINED, |(span, _)| span);
+                vec![error(span, parse_error.message().to_string())]
+            }),
+        }
+        .map_err(ShadertoyError::Compile)?;
+
+        // The device may support more capabilities, but Shadertoy shaders shouldn't need any
+        let mut validator = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        );
+        if let Err(validation_error) = validator.validate(&module) {
+            // The inner errors say which expression or statement is invalid and why
+            let mut message = validation_error.as_inner().to_string();
+            let mut source = std::error::Error::source(&validation_error);
+            while let Some(inner) = source {
+                message.push_str(&format!(": {inner}"));
+                source = inner.source();
+            }
+            // Spans go from the enclosing function to the invalid expression, so the last one in
+            // the user's code is the most precise
+            let location = validation_error
+                .spans()
+                .filter_map(|&(span, _)| locate(span, &wrapped, &user, line_offset))
+                .last();
+            return Err(ShadertoyError::Compile(vec![CompileError {
+                location,
+                message,
+            }]));
+        }
+        Ok(Self { module })
+    }
+
+    /// Read and compile a shader file, choosing the language by its extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ShadertoyError> {
+        let path = path.as_ref();
+        let language = ShaderLanguage::from_path(path).ok_or(ShadertoyError::UnknownLanguage)?;
+        Self::new(&std::fs::read_to_string(path)?, language)
+    }
+}
+
+/// The line and column of `span` in the user's code, or `None` if it's in the wrapper
+fn locate(
+    span: naga::Span,
+    source: &str,
+    user: &Range<usize>,
+    line_offset: u32,
+) -> Option<(u32, u32)> {
+    let range = span.to_range()?;
+    if !user.contains(&range.start) {
+        return None;
+    }
+    let location = span.location(source);
+    Some((location.line_number - line_offset, location.line_position))
+}
+
+/// An error at a location in a shader's source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// One-based line and column, or `None` if the error isn't within the user's code, such as a
+    /// missing `mainImage` function
+    pub location: Option<(u32, u32)>,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "line {line}, column {column}: {}", self.message),
+            // Usually a missing `mainImage` or one with the wrong signature
+            None => write!(f, "in the wrapper calling mainImage: {}", self.message),
+        }
+    }
+}
+
+/// Reason a Shadertoy shader could not be loaded
+#[derive(Debug)]
+pub enum ShadertoyError {
+    Io(std::io::Error),
+    /// The file extension isn't a known shader language
+    UnknownLanguage,
+    Compile(Vec<CompileError>),
+}
+
+impl fmt::Display for ShadertoyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::UnknownLanguage => write!(
+                f,
+                "Unknown shader language, expected .glsl, .frag, .fs, or .wgsl"
+            ),
+            Self::Compile(errors) => {
+                write!(f, "Shader failed to compile")?;
+                for error in errors {
+                    write!(f, "\n  {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShadertoyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ShadertoyError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Mouse state following Shadertoy's `iMouse` convention, in window pixels with the origin at
+/// the bottom left
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ShadertoyMouse {
+    /// Latest cursor position, whether or not the button is pressed
+    cursor: Vec2,
+    /// Cursor position while the button was last held
+    position: Vec2,
+    /// Where the button was last pressed
+    click: Vec2,
+    pressed: bool,
+    /// Whether the button was pressed since the last frame
+    clicked: bool,
+}
+
+impl ShadertoyMouse {
+    /// Track the cursor, given its position from the top left of a window `window_height` tall
+    pub fn cursor_moved(&mut self, x: f32, y: f32, window_height: u32) {
+        self.cursor = Vec2::new(x, window_height as f32 - y);
+        if self.pressed {
+            self.position = self.cursor;
+        }
+    }
+
+    /// Track the main button being pressed or released
+    pub fn button(&mut self, pressed: bool) {
+        if pressed && !self.pressed {
+            self.position = self.cursor;
+            self.click = self.cursor;
+            self.clicked = true;
+        }
+        self.pressed = pressed;
+    }
+
+    /// Clear the click once a frame has seen it
+    pub fn frame_rendered(&mut self) {
+        self.clicked = false;
+    }
+
+    /// `iMouse`, scaling window pixels by `scale`. The click position's X is negative while the
+    /// button is released, and its Y is negative except in the frame the button was pressed.
+    fn uniform(&self, scale: Vec2) -> Vec4 {
+        let position = self.position * scale;
+        let click = self.click * scale;
+        let sign = |positive: bool| if positive { 1.0 } else { -1.0 };
+        Vec4::new(
+            position.x,
+            position.y,
+            click.x * sign(self.pressed),
+            click.y * sign(self.clicked),
+        )
+    }
+}
+
+/// The uniforms declared by the wrappers
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct InputUniforms {
+    resolution: [f32; 3],
+    time: f32,
+    mouse: [f32; 4],
+    frame: i32,
+    _padding: [u32; 3],
+}
+
+/// Renders a Shadertoy shader into a texture for the cube, or into the frame
+#[derive(Debug)]
+pub(crate) struct ShadertoyRenderer {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// The texture the shader renders into, or `None` if it's drawn over the whole frame
+    texture: Option<wgpu::TextureView>,
+    window_size: PhysicalSize<u32>,
+}
+
+impl ShadertoyRenderer {
+    /// Create the renderer for `target`. `texture_format` and `sample_count` describe the frame,
+    /// and are only used when drawing over it.
+    pub fn new(
+        shadertoy: &Shadertoy,
+        target: ShadertoyTarget,
+        texture_format: wgpu::TextureFormat,
+        sample_count: u32,
+        size: PhysicalSize<u32>,
+        device: &wgpu::Device,
+    ) -> Self {
+        let texture = (target == ShadertoyTarget::Cube).then(|| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Shadertoy Texture"),
+                    size: wgpu::Extent3d {
+                        width: TEXTURE_SIZE,
+                        height: TEXTURE_SIZE,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: TEXTURE_FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let (format, sample_count) = match target {
+            ShadertoyTarget::Cube => (TEXTURE_FORMAT, 1),
+            ShadertoyTarget::Fullscreen => (texture_format, sample_count),
+        };
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadertoy Uniform Buffer"),
+            size: std::mem::size_of::<InputUniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Shadertoy Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadertoy Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let fragment_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadertoy Fragment Shader"),
+            source: wgpu::ShaderSource::Naga(std::borrow::Cow::Owned(shadertoy.module.clone())),
+        });
+        let pipeline = fullscreen_pipeline(
+            "Shadertoy",
+            &fragment_shader,
+            ENTRY_POINT,
+            &bind_group_layout,
+            format,
+            sample_count,
+            device,
+        );
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            texture,
+            window_size: size,
+        }
+    }
+
+    /// The texture the shader renders into when it's applied to the cube
+    pub fn texture(&self) -> Option<&wgpu::TextureView> {
+        self.texture.as_ref()
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.window_size = size;
+    }
+
+    /// Size of the image the shader renders
+    fn resolution(&self) -> Vec2 {
+        match &self.texture {
+            Some(_) => Vec2::splat(TEXTURE_SIZE as f32),
+            None => Vec2::new(
+                self.window_size.width as f32,
+                self.window_size.height as f32,
+            ),
+        }
+    }
+
+    /// Update the shader's inputs for the next frame
+    pub fn write_inputs(
+        &self,
+        time: Duration,
+        frame: u64,
+        mouse: &ShadertoyMouse,
+        queue: &wgpu::Queue,
+    ) {
+        let resolution = self.resolution();
+        let window_size = Vec2::new(
+            self.window_size.width.max(1) as f32,
+            self.window_size.height.max(1) as f32,
+        );
+        let uniforms = InputUniforms {
+            // Pixels are square
+            resolution: resolution.extend(1.0).to_array(),
+            time: time.as_secs_f32(),
+            mouse: mouse.uniform(resolution / window_size).to_array(),
+            frame: frame.min(i32::MAX as u64) as i32,
+            _padding: [0; 3],
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    /// Draw the shader into `view`, resolving into `resolve_target` if it's multisampled
+    pub fn render(
+        &self,
+        view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let store = match resolve_target {
+            Some(_) => wgpu::StoreOp::Discard,
+            None => wgpu::StoreOp::Store,
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadertoy Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target,
+                ops: wgpu::Operations {
+                    // Every pixel is overwritten
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_glsl_and_wgsl() {
+        let glsl = "void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = fragCoord / iResolution.xy;
+    fragColor = vec4(uv, 0.5 + 0.5 * sin(iTime + float(iFrame)), 1.0) + iMouse * 0.0;
+}
+";
+        Shadertoy::new(glsl, ShaderLanguage::Glsl).unwrap();
+        let wgsl = "fn mainImage(fragCoord: vec2<f32>) -> vec4<f32> {
+    let uv = fragCoord / iResolution.xy;
+    return vec4<f32>(uv, 0.5 + 0.5 * sin(iTime + f32(iFrame)), 1.0) + iMouse * 0.0;
+}
+";
+        Shadertoy::new(wgsl, ShaderLanguage::Wgsl).unwrap();
+    }
+
+    fn compile_errors(source: &str, language: ShaderLanguage) -> Vec<CompileError> {
+        match Shadertoy::new(source, language) {
+            Err(ShadertoyError::Compile(errors)) => errors,
+            result => panic!("expected compile errors, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn reports_error_lines() {
+        let glsl = "void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+
+    fragColor = vec4(undefined_color, 1.0);
+}
+";
+        let errors = compile_errors(glsl, ShaderLanguage::Glsl);
+        assert_eq!(errors[0].location, Some((3, 22)), "{errors:?}");
+
+        let wgsl = "fn mainImage(fragCoord: vec2<f32>) -> vec4<f32> {
+    return vec4<f32>(fragCoord, 0.0);
+}
+";
+        let errors = compile_errors(wgsl, ShaderLanguage::Wgsl);
+        assert_eq!(
+            errors[0].location.map(|(line, _)| line),
+            Some(2),
+            "{errors:?}"
+        );
+
+        // A missing `mainImage` is only referenced from the wrapper
+        let errors = compile_errors("fn main_image() {}", ShaderLanguage::Wgsl);
+        assert_eq!(errors[0].location, None, "{errors:?}");
+    }
+
+    #[test]
+    fn mouse_follows_shadertoy_convention() {
+        let mut mouse = ShadertoyMouse::default();
+        mouse.cursor_moved(10.0, 90.0, 100);
+        assert_eq!(mouse.uniform(Vec2::ONE), Vec4::ZERO);
+        mouse.button(true);
+        assert_eq!(mouse.uniform(Vec2::ONE), Vec4::new(10.0, 10.0, 10.0, 10.0));
+        mouse.frame_rendered();
+        mouse.cursor_moved(20.0, 80.0, 100);
+        assert_eq!(
+            mouse.uniform(Vec2::splat(2.0)),
+            Vec4::new(40.0, 40.0, 20.0, -20.0)
+        );
+        mouse.button(false);
+        mouse.cursor_moved(30.0, 70.0, 100);
+        assert_eq!(
+            mouse.uniform(Vec2::ONE),
+            Vec4::new(20.0, 20.0, -10.0, -10.0)
+        );
+    }
+}
@@ -0,0 +1,56 @@
+/// How surfaces are lit
+///
+/// Every mode is drawn with the same vertex layout and uniforms, so the mode can be changed
+/// between frames without rebuilding the scene's buffers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shading {
+    /// Diffuse lighting with one normal per triangle, for a faceted look
+    Flat,
+    /// Diffuse lighting evaluated per vertex and interpolated across triangles, like kmscube
+    #[default]
+    Gouraud,
+    /// Diffuse lighting evaluated per fragment from interpolated normals
+    Lambert,
+    /// Per fragment diffuse lighting with Blinn-Phong specular highlights
+    BlinnPhong,
+    /// Vertex and material colors without lighting
+    Unlit,
+}
+
+impl Shading {
+    /// Every mode, in the order `next` cycles through them
+    pub const ALL: [Self; 5] = [
+        Self::Flat,
+        Self::Gouraud,
+        Self::Lambert,
+        Self::BlinnPhong,
+        Self::Unlit,
+    ];
+
+    /// The name used on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Gouraud => "gouraud",
+            Self::Lambert => "lambert",
+            Self::BlinnPhong => "blinn-phong",
+            Self::Unlit => "unlit",
+        }
+    }
+
+    /// The mode after this one, wrapping around after the last
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// Vertex and fragment shader entry points in `cube.wgsl`
+    pub(crate) fn entry_points(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Flat => ("vs_main", "fs_flat"),
+            Self::Gouraud => ("vs_gouraud", "fs_color"),
+            Self::Lambert => ("vs_main", "fs_lambert"),
+            Self::BlinnPhong => ("vs_main", "fs_blinn_phong"),
+            Self::Unlit => ("vs_main", "fs_color"),
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! STL model loading
+//!
+//! Reads binary and ASCII STL files. STL stores every triangle's corners separately, so corners
+//! with identical positions are welded into shared vertices. The stored facet normals are
+//! ignored, since exporters often leave them zero or get them wrong, and normals are generated
+//! instead. STL has no standard color information, so models are white.
+//!
+//! Files are read incrementally, so memory use is proportional to the number of unique vertices
+//! rather than the file size.
+
+use crate::mesh::{Mesh, MeshError, Normals, Welder, mesh_with_normals};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Size of the binary header, which is an 80 byte comment followed by the triangle count
+const BINARY_HEADER_SIZE: u64 = 84;
+/// Size of each binary triangle: normal, 3 corners, and an attribute word
+const BINARY_TRIANGLE_SIZE: u64 = 50;
+
+const WHITE: [f32; 3] = [1.0; 3];
+
+#[derive(Debug)]
+pub enum StlError {
+    Io(io::Error),
+    /// An ASCII statement could not be parsed
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A binary file ends before the number of triangles in its header
+    Truncated {
+        expected: u32,
+        actual: u32,
+    },
+    /// A binary triangle has a NaN or infinite coordinate
+    InvalidVertex {
+        triangle: u32,
+    },
+    /// The file contains no triangles
+    NoTriangles,
+    Mesh(MeshError),
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Syntax { line, message } => write!(f, "line {line}: {message}"),
+            Self::Truncated { expected, actual } => write!(
+                f,
+                "The file ends after {actual} of its {expected} triangles"
+            ),
+            Self::InvalidVertex { triangle } => {
+                write!(f, "Triangle {triangle} has a non-finite coordinate")
+            }
+            Self::NoTriangles => write!(f, "The model has no triangles"),
+            Self::Mesh(error) => write!(f, "Invalid mesh: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for StlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Mesh(error) => Some(error),
+            Self::Syntax { .. }
+            | Self::Truncated { .. }
+            | Self::InvalidVertex { .. }
+            | Self::NoTriangles => None,
+        }
+    }
+}
+
+impl From<io::Error> for StlError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<MeshError> for StlError {
+    fn from(error: MeshError) -> Self {
+        Self::Mesh(error)
+    }
+}
+
+/// Load a binary or ASCII STL file
+///
+/// The model is centered and scaled to fit the built-in cube's bounds.
+pub fn load(path: impl AsRef<Path>, normals: Normals) -> Result<Mesh, StlError> {
+    read(BufReader::new(File::open(path)?), normals)
+}
+
+/// Read a binary or ASCII STL file
+///
+/// The format is detected from the header and the stream's length. The model is centered and
+/// scaled to fit the built-in cube's bounds.
+pub fn read(mut reader: impl BufRead + Seek, normals: Normals) -> Result<Mesh, StlError> {
+    let length = reader.seek(SeekFrom::End(0))?;
+    reader.rewind()?;
+    let mut header = Vec::with_capacity(BINARY_HEADER_SIZE as usize);
+    (&mut reader)
+        .take(BINARY_HEADER_SIZE)
+        .read_to_end(&mut header)?;
+
+    let mut triangles = Triangles::default();
+    if is_binary(&header, length) {
+        let count = header.get(80..84).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The file is too short for an STL header",
+            )
+        })?;
+        let count = u32::from_le_bytes(count.try_into().unwrap());
+        read_binary(reader, count, length, &mut triangles)?;
+    } else {
+        reader.rewind()?;
+        read_ascii(reader, &mut triangles)?;
+    }
+    if triangles.triangles.is_empty() {
+        return Err(StlError::NoTriangles);
+    }
+
+    let (positions, colors) = triangles.welder.into_vertices();
+    let mut mesh = mesh_with_normals(positions, colors, triangles.triangles, normals)?;
+    mesh.fit_to_cube();
+    Ok(mesh)
+}
+
+/// ASCII files start with "solid", but so do the headers of some binary files, so those are
+/// distinguished by checking whether the length matches the binary triangle count
+fn is_binary(header: &[u8], length: u64) -> bool {
+    if !header.starts_with(b"solid") {
+        return true;
+    }
+    if header.len() < BINARY_HEADER_SIZE as usize {
+        return false;
+    }
+    let count = u32::from_le_bytes(header[80..84].try_into().unwrap());
+    BINARY_HEADER_SIZE + count as u64 * BINARY_TRIANGLE_SIZE == length
+}
+
+/// Welded triangles read so far
+#[derive(Debug, Default)]
+struct Triangles {
+    welder: Welder,
+    triangles: Vec<[u32; 3]>,
+}
+
+impl Triangles {
+    fn add(&mut self, corners: [[f32; 3]; 3]) {
+        let triangle = corners.map(|corner| self.welder.vertex(corner, WHITE));
+        // Triangles with welded corners have no area and are dropped
+        let [a, b, c] = triangle;
+        if a != b && b != c && a != c {
+            self.triangles.push(triangle);
+        }
+    }
+}
+
+fn read_binary(
+    mut reader: impl Read,
+    count: u32,
+    length: u64,
+    triangles: &mut Triangles,
+) -> Result<(), StlError> {
+    let available = length.saturating_sub(BINARY_HEADER_SIZE) / BINARY_TRIANGLE_SIZE;
+    triangles
+        .triangles
+        .reserve(available.min(count as u64) as usize);
+
+    let mut record = [0; BINARY_TRIANGLE_SIZE as usize];
+    for triangle in 0..count {
+        reader.read_exact(&mut record).map_err(|error| {
+            if error.kind() == io::ErrorKind::UnexpectedEof {
+                StlError::Truncated {
+                    expected: count,
+                    actual: triangle,
+                }
+            } else {
+                StlError::Io(error)
+            }
+        })?;
+        // The corners follow the 12 byte facet normal
+        let corners: [[f32; 3]; 3] = std::array::from_fn(|corner| {
+            std::array::from_fn(|axis| {
+                let offset = 12 + corner * 12 + axis * 4;
+                f32::from_le_bytes(record[offset..offset + 4].try_into().unwrap())
+            })
+        });
+        if !corners.as_flattened().iter().all(|value| value.is_finite()) {
+            return Err(StlError::InvalidVertex { triangle });
+        }
+        triangles.add(corners);
+    }
+    Ok(())
+}
+
+fn read_ascii(mut reader: impl BufRead, triangles: &mut Triangles) -> Result<(), StlError> {
+    let mut line = vec![];
+    let mut line_number = 0;
+    let mut corners: Vec<[f32; 3]> = vec![];
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        line_number += 1;
+        let syntax_error = |message: String| StlError::Syntax {
+            line: line_number,
+            message,
+        };
+        let text = std::str::from_utf8(&line)
+            .map_err(|_| syntax_error("Invalid text, the file may be binary".to_string()))?;
+
+        let mut tokens = text.split_whitespace();
+        match tokens.next() {
+            Some("vertex") => {
+                let mut corner = [0.0; 3];
+                for value in &mut corner {
+                    let token = tokens
+                        .next()
+                        .ok_or_else(|| syntax_error("Expected 3 coordinates".to_string()))?;
+                    *value = token
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|value| value.is_finite())
+                        .ok_or_else(|| syntax_error(format!("Invalid number '{token}'")))?;
+                }
+                corners.push(corner);
+            }
+            Some("endloop") => {
+                if corners.len() < 3 {
+                    return Err(syntax_error(format!(
+                        "Facets need at least 3 vertices, found {}",
+                        corners.len()
+                    )));
+                }
+                // Some exporters write polygons, which are triangulated as fans
+                for i in 1..corners.len() - 1 {
+                    triangles.add([corners[0], corners[i], corners[i + 1]]);
+                }
+                corners.clear();
+            }
+            Some("solid" | "endsolid" | "facet" | "endfacet" | "outer") | None => {}
+            Some(keyword) => return Err(syntax_error(format!("Unexpected '{keyword}'"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A tetrahedron with outward facing triangles
+    const TETRAHEDRON: [[[f32; 3]; 3]; 4] = [
+        [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    ];
+
+    fn binary(header: &[u8], triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(80, 0);
+        bytes.extend((triangles.len() as u32).to_le_bytes());
+        for triangle in triangles {
+            bytes.extend([0; 12]);
+            for value in triangle.as_flattened() {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.extend([0; 2]);
+        }
+        bytes
+    }
+
+    fn ascii(triangles: &[[[f32; 3]; 3]]) -> String {
+        let mut text = "solid tetrahedron\n".to_string();
+        for triangle in triangles {
+            text += "  facet normal 0 0 0\n    outer loop\n";
+            for [x, y, z] in triangle {
+                text += &format!("      vertex {x:e} {y:e} {z:e}\n");
+            }
+            text += "    endloop\n  endfacet\n";
+        }
+        text + "endsolid tetrahedron\n"
+    }
+
+    #[test]
+    fn reads_binary_and_welds_vertices() {
+        let mesh = read(Cursor::new(binary(b"", &TETRAHEDRON)), Normals::Smooth).unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangle_count(), 4);
+        assert_eq!(mesh.colors(), [WHITE; 4]);
+    }
+
+    #[test]
+    fn reads_binary_with_solid_header() {
+        let bytes = binary(b"solid exported by a CAD tool", &TETRAHEDRON);
+        let mesh = read(Cursor::new(bytes), Normals::Smooth).unwrap();
+        assert_eq!(mesh.triangle_count(), 4);
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let mesh = read(Cursor::new(ascii(&TETRAHEDRON)), Normals::Smooth).unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangle_count(), 4);
+    }
+
+    #[test]
+    fn generates_flat_normals() {
+        let mesh = read(Cursor::new(binary(b"", &TETRAHEDRON)), Normals::Flat).unwrap();
+        assert_eq!(mesh.vertex_count(), 12);
+        assert_eq!(&mesh.normals()[..3], [[0.0, 0.0, -1.0]; 3]);
+    }
+
+    #[test]
+    fn drops_degenerate_triangles() {
+        let mut triangles = TETRAHEDRON.to_vec();
+        triangles.push([[1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        let mesh = read(Cursor::new(binary(b"", &triangles)), Normals::Smooth).unwrap();
+        assert_eq!(mesh.triangle_count(), 4);
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut truncated = binary(b"", &TETRAHEDRON);
+        truncated.truncate(truncated.len() - 1);
+        assert!(matches!(
+            read(Cursor::new(truncated), Normals::Smooth),
+            Err(StlError::Truncated {
+                expected: 4,
+                actual: 3
+            })
+        ));
+
+        let invalid = ascii(&TETRAHEDRON).replacen("vertex 0e0", "vertex x", 1);
+        assert_eq!(
+            read(Cursor::new(invalid), Normals::Smooth)
+                .unwrap_err()
+                .to_string(),
+            "line 4: Invalid number 'x'"
+        );
+
+        let mut nan = TETRAHEDRON;
+        nan[2][1][0] = f32::NAN;
+        assert!(matches!(
+            read(Cursor::new(binary(b"", &nan)), Normals::Smooth),
+            Err(StlError::InvalidVertex { triangle: 2 })
+        ));
+
+        assert!(matches!(
+            read(
+                Cursor::new("solid empty\nendsolid empty\n"),
+                Normals::Smooth
+            ),
+            Err(StlError::NoTriangles)
+        ));
+        assert!(matches!(
+            read(Cursor::new(b"short"), Normals::Smooth),
+            Err(StlError::Io(_))
+        ));
+    }
+}
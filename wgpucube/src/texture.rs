@@ -0,0 +1,489 @@
+//! Texture upload and sampling
+//!
+//! Textures are uploaded with a full mip chain, which is generated on the GPU by repeatedly
+//! downsampling each level into the next with a linear filter. YUV frames are first converted into
+//! the top level from textures holding their planes.
+
+use crate::scene::Texture;
+use crate::yuv::{YuvFormat, YuvFrame};
+use tracing::warn;
+use wgpu::TextureFormat;
+use wgpu::util::DeviceExt;
+
+/// Format of every uploaded texture. Texels are sRGB encoded, so filtering and mipmap generation
+/// happen in linear space.
+const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// The built-in texture, a checkerboard with an arrow pointing up
+pub fn builtin() -> Texture {
+    Texture::from_png(include_bytes!("../assets/texture.png")).unwrap()
+}
+
+/// How textures are filtered and repeated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerOptions {
+    /// Filter used when a texture is magnified or minified
+    pub filter: wgpu::FilterMode,
+    /// `Nearest` uses the closest mipmap level and `Linear` blends between the two closest
+    pub mipmap_filter: wgpu::FilterMode,
+    /// How texture coordinates outside 0 to 1 are handled
+    pub address_mode: wgpu::AddressMode,
+    /// Maximum anisotropic filtering ratio, from 1 (disabled) to 16. This only applies when both
+    /// filters are linear, and is ignored by adapters without anisotropic filtering.
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            address_mode: wgpu::AddressMode::Repeat,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub(crate) fn create_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        let linear = self.filter == wgpu::FilterMode::Linear
+            && self.mipmap_filter == wgpu::FilterMode::Linear;
+        // wgpu rejects anisotropy with other filters, rather than ignoring it
+        let anisotropy_clamp = if linear {
+            self.anisotropy.clamp(1, 16)
+        } else {
+            1
+        };
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Cube Base Color Sampler"),
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            mag_filter: self.filter,
+            min_filter: self.filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+/// Number of mip levels down to 1x1 for a texture of the given size
+fn mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).leading_zeros()
+}
+
+/// The uniforms of `yuv.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ConversionUniforms {
+    // mat3x3 columns are padded to 16 bytes
+    matrix: [[f32; 4]; 3],
+    offset: [f32; 3],
+    interleaved: u32,
+}
+
+impl From<&YuvFrame> for ConversionUniforms {
+    fn from(frame: &YuvFrame) -> Self {
+        let (matrix, offset) = frame.color_space().to_rgb_transform();
+        Self {
+            matrix: [
+                matrix.x_axis.extend(0.0).to_array(),
+                matrix.y_axis.extend(0.0).to_array(),
+                matrix.z_axis.extend(0.0).to_array(),
+            ],
+            offset: offset.to_array(),
+            interleaved: (frame.format() == YuvFormat::Nv12).into(),
+        }
+    }
+}
+
+/// A pipeline drawing a triangle covering its whole target with the vertex stage in
+/// `fullscreen.wgsl`, which passes the target's texture coordinates to `fragment_shader`
+pub(crate) fn fullscreen_pipeline(
+    label: &str,
+    fragment_shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    device: &wgpu::Device,
+) -> wgpu::RenderPipeline {
+    let vertex_shader = device.create_shader_module(wgpu::include_wgsl!("fullscreen.wgsl"));
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{label} Pipeline Layout")),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("{label} Render Pipeline")),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &vertex_shader,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: fragment_shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            targets: &[Some(format.into())],
+        }),
+        multiview: None,
+        cache: None,
+    })
+}
+
+fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
+
+/// Draw a triangle covering `target` with `pipeline`
+fn draw_fullscreen(
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::TextureView,
+    encoder: &mut wgpu::CommandEncoder,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            depth_slice: None,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+/// Uploads textures and renders their mip chains
+#[derive(Debug)]
+pub(crate) struct TextureUploader {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    yuv_pipeline: wgpu::RenderPipeline,
+    yuv_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl TextureUploader {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Bind Group Layout"),
+            entries: &[texture_entry(0), sampler_entry(1)],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("mipmap.wgsl"));
+        let pipeline = fullscreen_pipeline(
+            "Mipmap",
+            &shader,
+            "fs_main",
+            &bind_group_layout,
+            FORMAT,
+            1,
+            device,
+        );
+
+        let yuv_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("YUV Bind Group Layout"),
+                entries: &[
+                    texture_entry(0),
+                    texture_entry(1),
+                    texture_entry(2),
+                    sampler_entry(3),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let yuv_shader = device.create_shader_module(wgpu::include_wgsl!("yuv.wgsl"));
+        let yuv_pipeline = fullscreen_pipeline(
+            "YUV",
+            &yuv_shader,
+            "fs_main",
+            &yuv_bind_group_layout,
+            FORMAT,
+            1,
+            device,
+        );
+
+        // Each level is half the size of the one above it, so bilinear sampling at the center of
+        // each destination texel averages the 2x2 source texels it covers
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            pipeline,
+            bind_group_layout,
+            yuv_pipeline,
+            yuv_bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// Upload a texture and record the commands generating its mip levels into `encoder`, or
+    /// return `None` with a warning if it exceeds the device's limits
+    pub fn upload(
+        &self,
+        texture: &Texture,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Option<wgpu::TextureView> {
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if texture.width() > max_dimension || texture.height() > max_dimension {
+            warn!(
+                "Texture size {}x{} exceeds the device limit of {}, so it is ignored",
+                texture.width(),
+                texture.height(),
+                max_dimension
+            );
+            return None;
+        }
+        let size = wgpu::Extent3d {
+            width: texture.width(),
+            height: texture.height(),
+            depth_or_array_layers: 1,
+        };
+        let mip_level_count = mip_level_count(texture.width(), texture.height());
+        let gpu_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Cube Base Color Texture"),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let level_view = |level| {
+            gpu_texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+        match (texture.pixels(), texture.yuv()) {
+            (Some(pixels), _) => queue.write_texture(
+                gpu_texture.as_image_copy(),
+                pixels,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(texture.width() * 4),
+                    rows_per_image: None,
+                },
+                size,
+            ),
+            (None, Some(frame)) => self.convert(frame, &level_view(0), device, queue, encoder),
+            (None, None) => unreachable!(),
+        }
+
+        // Render each level from the one above it
+        for level in 1..mip_level_count {
+            let source = level_view(level - 1);
+            let destination = level_view(level);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Mipmap Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+            draw_fullscreen(
+                "Mipmap Render Pass",
+                &self.pipeline,
+                &bind_group,
+                &destination,
+                encoder,
+            );
+        }
+
+        Some(gpu_texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// Upload a YUV frame's planes and record the commands converting them to RGB in `target`
+    fn convert(
+        &self,
+        frame: &YuvFrame,
+        target: &wgpu::TextureView,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let plane = |label, format: TextureFormat, width, height, data: &[u8]| {
+            let size = wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let bytes_per_pixel = format.block_copy_size(None).unwrap();
+            queue.write_texture(
+                texture.as_image_copy(),
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width * bytes_per_pixel),
+                    rows_per_image: None,
+                },
+                size,
+            );
+            texture.create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let (width, height) = (frame.width(), frame.height());
+        let (chroma_width, chroma_height) = YuvFormat::chroma_size(width, height);
+        let luma = plane(
+            "YUV Luma Plane",
+            TextureFormat::R8Unorm,
+            width,
+            height,
+            frame.luma(),
+        );
+        // NV12 binds its interleaved chroma plane twice
+        let (chroma_u, chroma_v) = match frame.format() {
+            YuvFormat::Nv12 => {
+                let chroma = plane(
+                    "YUV Chroma Plane",
+                    TextureFormat::Rg8Unorm,
+                    chroma_width,
+                    chroma_height,
+                    frame.chroma(),
+                );
+                (chroma.clone(), chroma)
+            }
+            YuvFormat::I420 => {
+                let (u, v) = frame.chroma().split_at(frame.chroma().len() / 2);
+                (
+                    plane(
+                        "YUV U Plane",
+                        TextureFormat::R8Unorm,
+                        chroma_width,
+                        chroma_height,
+                        u,
+                    ),
+                    plane(
+                        "YUV V Plane",
+                        TextureFormat::R8Unorm,
+                        chroma_width,
+                        chroma_height,
+                        v,
+                    ),
+                )
+            }
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("YUV Conversion Buffer"),
+            contents: bytemuck::bytes_of(&ConversionUniforms::from(frame)),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("YUV Bind Group"),
+            layout: &self.yuv_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&luma),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&chroma_u),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&chroma_v),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+        draw_fullscreen(
+            "YUV Render Pass",
+            &self.yuv_pipeline,
+            &bind_group,
+            target,
+            encoder,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_ends_at_one_texel() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(builtin().width(), 256);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::error::Error;
+use wasm_bindgen::JsCast;
+
+/// Id of the canvas element in index.html which the window renders into
+pub(crate) const CANVAS_ID: &str = "wgpucube-canvas";
+
+pub(crate) fn canvas() -> web_sys::HtmlCanvasElement {
+    web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .get_element_by_id(CANVAS_ID)
+        .unwrap()
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .unwrap()
+}
+
+/// Replace the canvas with an explanation of why nothing can be rendered
+pub(crate) fn show_error(error: &Error) {
+    let summary = match error {
+        Error::CreateSurface(_)
+        | Error::RequestAdapter(_)
+        | Error::NoMatchingAdapter(_)
+        | Error::IncompatibleSurface => "WebGPU unavailable, try a different browser",
+        Error::RequestDevice(_) => "WebGPU device unavailable, try updating your browser",
+        Error::SurfaceFormatChanged(_) => "Canvas format unsupported, try reloading the page",
+        Error::Surface(_) => "Rendering failed, try reloading the page",
+        Error::TargetTooLarge { .. } => "Canvas too large for this device",
+    };
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let message = document.create_element("div").unwrap();
+    message.set_id("wgpucube-error");
+    let heading = document.create_element("h1").unwrap();
+    heading.set_text_content(Some(summary));
+    let details = document.create_element("p").unwrap();
+    details.set_text_content(Some(&error.to_string()));
+    message.append_with_node_2(&heading, &details).unwrap();
+    canvas().replace_with_with_node_1(&message).unwrap();
+}
@@ -0,0 +1,408 @@
+//! Planar YUV video frames
+//!
+//! kmscube's `nv12-1img` and `nv12-2img` modes texture the cube with NV12 video frames. Here each
+//! plane of a frame is uploaded as its own texture, luma as R8 and chroma as RG8 for NV12 or as
+//! two R8 textures for I420, and converted to RGB in WGSL with the frame's color space. The
+//! result is an ordinary texture, so it is filtered, mipmapped, and lit like any other.
+//!
+//! Both formats subsample chroma 2:1 horizontally and vertically (4:2:0). The converted values
+//! are treated as sRGB encoded, which is close to the BT.601 and BT.709 transfer functions as
+//! displayed.
+
+use glam::{Mat3, Vec3};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+/// Layout of the planes in a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvFormat {
+    /// A luma plane followed by one plane of interleaved U and V samples
+    Nv12,
+    /// A luma plane followed by a U plane and a V plane
+    I420,
+}
+
+impl YuvFormat {
+    /// Size of a frame's chroma planes, rounding odd luma sizes up
+    pub fn chroma_size(width: u32, height: u32) -> (u32, u32) {
+        (width.div_ceil(2), height.div_ceil(2))
+    }
+
+    /// Number of bytes in a frame. Both formats store the same samples in different orders.
+    pub fn frame_len(width: u32, height: u32) -> usize {
+        let (chroma_width, chroma_height) = Self::chroma_size(width, height);
+        width as usize * height as usize + 2 * chroma_width as usize * chroma_height as usize
+    }
+}
+
+/// Coefficients relating RGB to luma
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YuvMatrix {
+    /// Standard definition video
+    #[default]
+    Bt601,
+    /// High definition video
+    Bt709,
+}
+
+impl YuvMatrix {
+    /// Red and blue luma coefficients. The green coefficient is the remainder.
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/// Range of code values representing black to white
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YuvRange {
+    /// Luma from 0 to 255 and chroma from 0 to 255 around 128
+    Full,
+    /// Luma from 16 to 235 and chroma from 16 to 240, as broadcast video uses
+    #[default]
+    Limited,
+}
+
+/// How a frame's YUV values map to RGB. The default matches EGL's defaults for YUV images, which
+/// kmscube relies on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct YuvColorSpace {
+    pub matrix: YuvMatrix,
+    pub range: YuvRange,
+}
+
+impl YuvColorSpace {
+    /// Offsets and scales converting code values from 0 to 1 into luma from 0 to 1 and chroma from
+    /// -0.5 to 0.5
+    fn normalization(self) -> (Vec3, Vec3) {
+        let offset = Vec3::new(0.0, 128.0, 128.0) / 255.0;
+        match self.range {
+            YuvRange::Full => (offset, Vec3::ONE),
+            YuvRange::Limited => (
+                Vec3::new(16.0 / 255.0, offset.y, offset.z),
+                Vec3::new(255.0 / 219.0, 255.0 / 224.0, 255.0 / 224.0),
+            ),
+        }
+    }
+
+    /// The matrix and offset converting YUV code values from 0 to 1 into RGB, as
+    /// `rgb = matrix * (yuv - offset)`
+    pub(crate) fn to_rgb_transform(self) -> (Mat3, Vec3) {
+        let (kr, kb) = self.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        // Columns are the contributions of luma, U (Cb), and V (Cr)
+        let matrix = Mat3::from_cols(
+            Vec3::ONE,
+            Vec3::new(0.0, -2.0 * kb * (1.0 - kb) / kg, 2.0 * (1.0 - kb)),
+            Vec3::new(2.0 * (1.0 - kr), -2.0 * kr * (1.0 - kr) / kg, 0.0),
+        );
+        let (offset, scale) = self.normalization();
+        (matrix * Mat3::from_diagonal(scale), offset)
+    }
+
+    /// Convert 8-bit YUV to 8-bit RGB, clamping colors outside the RGB gamut
+    pub fn to_rgb(self, yuv: [u8; 3]) -> [u8; 3] {
+        let (matrix, offset) = self.to_rgb_transform();
+        let rgb = matrix * (Vec3::from(yuv.map(f32::from)) / 255.0 - offset);
+        rgb.clamp(Vec3::ZERO, Vec3::ONE)
+            .to_array()
+            .map(|component| (component * 255.0).round() as u8)
+    }
+
+    /// Convert 8-bit RGB to 8-bit YUV
+    pub fn from_rgb(self, rgb: [u8; 3]) -> [u8; 3] {
+        let (kr, kb) = self.matrix.coefficients();
+        let [r, g, b] = rgb.map(|component| component as f32 / 255.0);
+        let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+        let yuv = Vec3::new(
+            y,
+            (b - y) / (2.0 * (1.0 - kb)),
+            (r - y) / (2.0 * (1.0 - kr)),
+        );
+        let (offset, scale) = self.normalization();
+        (yuv / scale + offset)
+            .to_array()
+            .map(|component| (component * 255.0).round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+/// A single 8-bit 4:2:0 YUV frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct YuvFrame {
+    format: YuvFormat,
+    width: u32,
+    height: u32,
+    color_space: YuvColorSpace,
+    data: Vec<u8>,
+}
+
+impl YuvFrame {
+    /// Create a frame from its planes, stored one after another without padding
+    pub fn new(
+        format: YuvFormat,
+        width: u32,
+        height: u32,
+        color_space: YuvColorSpace,
+        data: Vec<u8>,
+    ) -> Result<Self, YuvError> {
+        if width == 0 || height == 0 {
+            return Err(YuvError::ZeroSize);
+        }
+        let expected = YuvFormat::frame_len(width, height);
+        if data.len() != expected {
+            return Err(YuvError::Size {
+                expected,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            format,
+            width,
+            height,
+            color_space,
+            data,
+        })
+    }
+
+    /// Read the first frame of a raw YUV file, such as those written by `ffmpeg -f rawvideo`
+    pub fn load(
+        path: impl AsRef<Path>,
+        format: YuvFormat,
+        width: u32,
+        height: u32,
+        color_space: YuvColorSpace,
+    ) -> Result<Self, YuvError> {
+        let expected = YuvFormat::frame_len(width, height);
+        let mut data = Vec::with_capacity(expected);
+        std::fs::File::open(path)?
+            .take(expected as u64)
+            .read_to_end(&mut data)?;
+        Self::new(format, width, height, color_space, data)
+    }
+
+    /// Vertical color bars in the top two thirds and a luma ramp from black to white below them
+    ///
+    /// Colors change only between 2x2 blocks of pixels, so the subsampled chroma is exact and the
+    /// converted frame matches `to_rgba` away from the color boundaries.
+    pub fn test_pattern(
+        format: YuvFormat,
+        width: u32,
+        height: u32,
+        color_space: YuvColorSpace,
+    ) -> Self {
+        const BARS: [[u8; 3]; 8] = [
+            [255, 255, 255],
+            [255, 255, 0],
+            [0, 255, 255],
+            [0, 255, 0],
+            [255, 0, 255],
+            [255, 0, 0],
+            [0, 0, 255],
+            [0, 0, 0],
+        ];
+        let (chroma_width, chroma_height) = YuvFormat::chroma_size(width, height);
+        // The color of the 2x2 block at chroma sample (x, y), or `None` in the ramp
+        let bar = |x: u32, y: u32| {
+            (y * 3 < chroma_height * 2).then(|| BARS[(x * 8 / chroma_width) as usize])
+        };
+        let mut luma = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                luma.push(match bar(x / 2, y / 2) {
+                    Some(rgb) => color_space.from_rgb(rgb)[0],
+                    None => {
+                        let gray = ((x / 2 * 2) * 255 / (width - 1).max(1)) as u8;
+                        color_space.from_rgb([gray; 3])[0]
+                    }
+                });
+            }
+        }
+        let mut u = vec![];
+        let mut v = vec![];
+        for y in 0..chroma_height {
+            for x in 0..chroma_width {
+                let [_, cb, cr] = color_space.from_rgb(bar(x, y).unwrap_or([128; 3]));
+                u.push(cb);
+                v.push(cr);
+            }
+        }
+        let mut data = luma;
+        match format {
+            YuvFormat::Nv12 => data.extend(u.into_iter().zip(v).flat_map(|(u, v)| [u, v])),
+            YuvFormat::I420 => {
+                data.extend(u);
+                data.extend(v);
+            }
+        }
+        Self::new(format, width, height, color_space, data).unwrap()
+    }
+
+    pub fn format(&self) -> YuvFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn color_space(&self) -> YuvColorSpace {
+        self.color_space
+    }
+
+    /// The luma plane, one byte per pixel
+    pub fn luma(&self) -> &[u8] {
+        &self.data[..self.width as usize * self.height as usize]
+    }
+
+    /// The chroma planes: interleaved U and V for NV12, or the U plane followed by the V plane for
+    /// I420
+    pub fn chroma(&self) -> &[u8] {
+        &self.data[self.width as usize * self.height as usize..]
+    }
+
+    /// The U and V samples covering pixel (x, y)
+    fn chroma_at(&self, x: u32, y: u32) -> [u8; 2] {
+        let (chroma_width, chroma_height) = YuvFormat::chroma_size(self.width, self.height);
+        let index = (y / 2 * chroma_width + x / 2) as usize;
+        let chroma = self.chroma();
+        match self.format {
+            YuvFormat::Nv12 => [chroma[index * 2], chroma[index * 2 + 1]],
+            YuvFormat::I420 => {
+                let plane = (chroma_width * chroma_height) as usize;
+                [chroma[index], chroma[plane + index]]
+            }
+        }
+    }
+
+    /// Convert to RGBA8 on the CPU, using each pixel's nearest chroma samples. This is the
+    /// reference the GPU conversion is checked against.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let luma = self.luma();
+        let mut pixels = Vec::with_capacity(luma.len() * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [u, v] = self.chroma_at(x, y);
+                let [r, g, b] =
+                    self.color_space
+                        .to_rgb([luma[(y * self.width + x) as usize], u, v]);
+                pixels.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+        pixels
+    }
+}
+
+/// Reason a YUV frame could not be created
+#[derive(Debug)]
+pub enum YuvError {
+    Io(std::io::Error),
+    ZeroSize,
+    /// The data doesn't hold exactly one frame of the given size
+    Size {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for YuvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::ZeroSize => write!(f, "Frame width and height must be non-zero"),
+            Self::Size { expected, actual } => write!(
+                f,
+                "Expected {expected} bytes for one frame, got {actual} bytes"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for YuvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for YuvError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR_SPACES: [YuvColorSpace; 4] = [
+        YuvColorSpace {
+            matrix: YuvMatrix::Bt601,
+            range: YuvRange::Full,
+        },
+        YuvColorSpace {
+            matrix: YuvMatrix::Bt601,
+            range: YuvRange::Limited,
+        },
+        YuvColorSpace {
+            matrix: YuvMatrix::Bt709,
+            range: YuvRange::Full,
+        },
+        YuvColorSpace {
+            matrix: YuvMatrix::Bt709,
+            range: YuvRange::Limited,
+        },
+    ];
+
+    #[test]
+    fn converts_reference_colors() {
+        let limited_601 = YuvColorSpace::default();
+        assert_eq!(limited_601.from_rgb([0, 0, 0]), [16, 128, 128]);
+        assert_eq!(limited_601.from_rgb([255, 255, 255]), [235, 128, 128]);
+        // BT.601 red and BT.709 blue from their specifications
+        assert_eq!(limited_601.from_rgb([255, 0, 0]), [81, 90, 240]);
+        let full_709 = YuvColorSpace {
+            matrix: YuvMatrix::Bt709,
+            range: YuvRange::Full,
+        };
+        assert_eq!(full_709.from_rgb([0, 0, 255]), [18, 255, 116]);
+
+        // Every primary and secondary color survives a round trip
+        for color_space in COLOR_SPACES {
+            for rgb in [[255, 0, 0], [0, 255, 255], [255, 0, 255], [128, 128, 128]] {
+                let round_trip = color_space.to_rgb(color_space.from_rgb(rgb));
+                for (actual, expected) in round_trip.iter().zip(rgb) {
+                    assert!(actual.abs_diff(expected) <= 2, "{color_space:?} {rgb:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pattern_layouts_match() {
+        let nv12 = YuvFrame::test_pattern(YuvFormat::Nv12, 33, 17, YuvColorSpace::default());
+        let i420 = YuvFrame::test_pattern(YuvFormat::I420, 33, 17, YuvColorSpace::default());
+        assert_eq!(nv12.chroma().len(), 17 * 9 * 2);
+        assert_eq!(nv12.luma(), i420.luma());
+        assert_eq!(nv12.to_rgba(), i420.to_rgba());
+        // The first bar is white and the ramp starts at black
+        assert_eq!(nv12.to_rgba()[..4], [255, 255, 255, 255]);
+        assert_eq!(nv12.to_rgba()[16 * 33 * 4..16 * 33 * 4 + 4], [0, 0, 0, 255]);
+
+        assert!(matches!(
+            YuvFrame::new(YuvFormat::I420, 4, 4, YuvColorSpace::default(), vec![0; 16]),
+            Err(YuvError::Size {
+                expected: 24,
+                actual: 16
+            })
+        ));
+    }
+}
@@ -0,0 +1,40 @@
+// Fragment stage of a pass drawn with the vertex stage in `fullscreen.wgsl`
+
+// Matches `ConversionUniforms` in `texture.rs`
+struct Conversion {
+    // Converts YUV code values from 0 to 1 into sRGB encoded RGB, after subtracting `offset`
+    matrix: mat3x3<f32>,
+    offset: vec3<f32>,
+    // Non-zero if U and V are interleaved in `chroma_u`, as in NV12
+    interleaved: u32,
+};
+
+@group(0) @binding(0)
+var luma: texture_2d<f32>;
+@group(0) @binding(1)
+var chroma_u: texture_2d<f32>;
+@group(0) @binding(2)
+var chroma_v: texture_2d<f32>;
+@group(0) @binding(3)
+var plane_sampler: sampler;
+@group(0) @binding(4)
+var<uniform> conversion: Conversion;
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn fs_main(@location(0) uv: vec2<f32>) -> @location(0) vec4<f32> {
+    let y = textureSample(luma, plane_sampler, uv).r;
+    // The chroma planes are half the size of the luma plane, so chroma is interpolated between
+    // samples
+    let u = textureSample(chroma_u, plane_sampler, uv);
+    let v = textureSample(chroma_v, plane_sampler, uv);
+    let chroma = select(vec2<f32>(u.r, v.r), u.rg, conversion.interleaved != 0u);
+    let rgb = conversion.matrix * (vec3<f32>(y, chroma) - conversion.offset);
+    // The target is sRGB, which encodes the output again
+    return vec4<f32>(srgb_to_linear(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0))), 1.0);
+}
@@ -0,0 +1,497 @@
+//! Golden image regression tests
+//!
+//! Each test renders a fixed animation step on the software fallback adapter (llvmpipe, lavapipe,
+//! WARP) and compares it against a reference image checked in under `tests/golden`. Software
+//! rasterizers are not bit-exact across versions, so each channel may differ by a small amount and
+//! a small fraction of pixels (mostly along the cube edges) may exceed that.
+//!
+//! Tests are skipped if no fallback adapter is available, except when the `CI` environment
+//! variable is set, where a missing adapter means the software drivers are broken and the tests
+//! fail instead.
+//!
+//! When a comparison fails, the rendered frame and a diff image are written to the test's target
+//! temporary directory. Set `WGPUCUBE_UPDATE_GOLDEN=1` to regenerate the reference images after an
+//! intentional change to the rendered output.
+
+use glam::Vec3;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use wgpucube::headless::{Frame, Headless};
+use wgpucube::light::{Light, Lighting};
+use wgpucube::primitive::Primitive;
+use wgpucube::scene::{Scene, Texture};
+use wgpucube::shadertoy::{ShaderLanguage, Shadertoy, ShadertoyMouse, ShadertoyTarget};
+use wgpucube::shading::Shading;
+use wgpucube::texture::SamplerOptions;
+use wgpucube::yuv::{YuvColorSpace, YuvFormat, YuvFrame, YuvMatrix, YuvRange};
+use wgpucube::{Error, Options};
+use winit::dpi::PhysicalSize;
+
+const SIZE: PhysicalSize<u32> = PhysicalSize::new(256, 256);
+const CHANNEL_TOLERANCE: u8 = 8;
+const MAX_MISMATCHED_FRACTION: f64 = 0.005;
+
+/// The software fallback adapter, or `None` if `test` should be skipped because there isn't one
+fn fallback_adapter(test: &str) -> Option<wgpu::Adapter> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let request_adapter_options = wgpu::RequestAdapterOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    };
+    match pollster::block_on(instance.request_adapter(&request_adapter_options)) {
+        Ok(adapter) => Some(adapter),
+        Err(error) if std::env::var_os("CI").is_some() => {
+            panic!("No fallback adapter available for {test} in CI: {error}")
+        }
+        Err(_) => {
+            eprintln!("Skipping {test}: no fallback adapter available");
+            None
+        }
+    }
+}
+
+fn read_png(path: &Path) -> Frame {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path).unwrap()));
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba);
+    assert_eq!(info.bit_depth, png::BitDepth::Eight);
+    pixels.truncate(info.buffer_size());
+    Frame {
+        size: PhysicalSize::new(info.width, info.height),
+        pixels,
+    }
+}
+
+/// Compare two frames, returning the number of mismatched pixels and an image highlighting them
+fn diff(expected: &Frame, actual: &Frame) -> (usize, Frame) {
+    let mut mismatched = 0;
+    let mut pixels = Vec::with_capacity(expected.pixels.len());
+    for (expected, actual) in expected
+        .pixels
+        .chunks_exact(4)
+        .zip(actual.pixels.chunks_exact(4))
+    {
+        let matches = expected
+            .iter()
+            .zip(actual)
+            .all(|(e, a)| e.abs_diff(*a) <= CHANNEL_TOLERANCE);
+        if matches {
+            // Dimmed grayscale of the expected image gives context for the highlighted pixels
+            let luma = (expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 3 / 4;
+            pixels.extend_from_slice(&[luma as u8, luma as u8, luma as u8, 255]);
+        } else {
+            mismatched += 1;
+            pixels.extend_from_slice(&[255, 0, 0, 255]);
+        }
+    }
+    let diff = Frame {
+        size: expected.size,
+        pixels,
+    };
+    (mismatched, diff)
+}
+
+fn check_golden(name: &str, step: u64, options: &Options) {
+    let Some(adapter) = fallback_adapter(name) else {
+        return;
+    };
+    let mut headless = pollster::block_on(Headless::from_adapter(&adapter, SIZE, options)).unwrap();
+    let actual = headless.render(step);
+
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"));
+    if std::env::var_os("WGPUCUBE_UPDATE_GOLDEN").is_some() {
+        actual.write_png(&reference_path).unwrap();
+        return;
+    }
+
+    let expected = read_png(&reference_path);
+    assert_eq!(expected.size, actual.size, "{name}: size mismatch");
+
+    let (mismatched, diff) = diff(&expected, &actual);
+    let total = (expected.size.width * expected.size.height) as usize;
+    if mismatched as f64 > total as f64 * MAX_MISMATCHED_FRACTION {
+        let output_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        let actual_path = output_dir.join(format!("{name}-actual.png"));
+        let diff_path = output_dir.join(format!("{name}-diff.png"));
+        actual.write_png(&actual_path).unwrap();
+        diff.write_png(&diff_path).unwrap();
+        panic!(
+            "{name}: {mismatched} of {total} pixels differ from {}\nactual: {}\ndiff: {}",
+            reference_path.display(),
+            actual_path.display(),
+            diff_path.display(),
+        );
+    }
+}
+
+#[test]
+fn step_0() {
+    check_golden("step_0", 0, &Options::default());
+}
+
+#[test]
+fn step_120() {
+    check_golden("step_120", 120, &Options::default());
+}
+
+#[test]
+fn step_360() {
+    check_golden("step_360", 360, &Options::default());
+}
+
+#[test]
+fn step_1000() {
+    check_golden("step_1000", 1000, &Options::default());
+}
+
+#[test]
+fn step_120_msaa_4x() {
+    // 4x is guaranteed by WebGPU, so every fallback adapter supports it
+    let options = Options {
+        sample_count: 4,
+        ..Options::default()
+    };
+    check_golden("step_120_msaa_4x", 120, &options);
+}
+
+#[test]
+fn step_120_depth24plus_stencil8() {
+    // A depth buffer with a stencil aspect must not change the image
+    let options = Options {
+        depth_format: wgpu::TextureFormat::Depth24PlusStencil8,
+        ..Options::default()
+    };
+    check_golden("step_120", 120, &options);
+}
+
+#[test]
+fn gltf_scene_step_120() {
+    // A textured cube, a mirrored pyramid with vertex colors, and a smaller copy of the cube, each
+    // placed by a different kind of node transform under a rotated root node
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("models")
+        .join("scene.gltf");
+    let options = Options {
+        scene: Some(Arc::new(wgpucube::gltf::load(path).unwrap())),
+        ..Options::default()
+    };
+    check_golden("gltf_scene_step_120", 120, &options);
+}
+
+/// Options rendering a UV sphere, whose curved surface shows the differences between modes
+fn sphere_options(shading: Shading) -> Options {
+    let primitive = Primitive::UvSphere {
+        segments: 16,
+        rings: 8,
+    };
+    Options {
+        scene: Some(Arc::new(Scene::from(primitive.mesh()))),
+        shading,
+        ..Options::default()
+    }
+}
+
+#[test]
+fn sphere_gouraud_step_120() {
+    check_golden(
+        "sphere_gouraud_step_120",
+        120,
+        &sphere_options(Shading::Gouraud),
+    );
+}
+
+#[test]
+fn sphere_flat_step_120() {
+    check_golden("sphere_flat_step_120", 120, &sphere_options(Shading::Flat));
+}
+
+#[test]
+fn sphere_lambert_step_120() {
+    check_golden(
+        "sphere_lambert_step_120",
+        120,
+        &sphere_options(Shading::Lambert),
+    );
+}
+
+#[test]
+fn sphere_blinn_phong_step_120() {
+    check_golden(
+        "sphere_blinn_phong_step_120",
+        120,
+        &sphere_options(Shading::BlinnPhong),
+    );
+}
+
+#[test]
+fn sphere_unlit_step_120() {
+    check_golden(
+        "sphere_unlit_step_120",
+        120,
+        &sphere_options(Shading::Unlit),
+    );
+}
+
+#[test]
+fn sphere_lights_step_120() {
+    // One light of each kind, with ambient light on the side facing away from them
+    let lights = [
+        "point:position=-3/0/-6,color=1/0.2/0.2,attenuation=0/0/0.5",
+        "spot:position=0/3/-4,direction=0/-3/-4,inner-angle=5,outer-angle=15,color=0.2/0.4/1",
+        "directional:direction=-1/0/0,color=0.2/1/0.2,intensity=0.5",
+    ];
+    let options = Options {
+        lighting: Lighting {
+            ambient: Vec3::splat(0.1),
+            lights: lights.map(|light| light.parse::<Light>().unwrap()).to_vec(),
+        },
+        ..sphere_options(Shading::BlinnPhong)
+    };
+    check_golden("sphere_lights_step_120", 120, &options);
+}
+
+#[test]
+fn textured_cube_step_120() {
+    let options = Options {
+        scene: Some(Arc::new(Scene::textured_cube(vec![
+            wgpucube::texture::builtin(),
+        ]))),
+        ..Options::default()
+    };
+    check_golden("textured_cube_step_120", 120, &options);
+}
+
+#[test]
+fn textured_faces_nearest_step_120() {
+    // A different 2x2 image on each face, magnified without filtering so each texel is a sharp
+    // quadrant of its face
+    let faces = [
+        [255, 0, 0],
+        [0, 255, 0],
+        [0, 0, 255],
+        [255, 255, 0],
+        [0, 255, 255],
+        [255, 0, 255],
+    ];
+    let textures = faces
+        .iter()
+        .map(|&[r, g, b]| {
+            let pixels = [
+                [r, g, b, 255],
+                [255; 4],
+                [255; 4],
+                [r / 2, g / 2, b / 2, 255],
+            ];
+            Texture::new(2, 2, pixels.concat()).unwrap()
+        })
+        .collect();
+    let options = Options {
+        scene: Some(Arc::new(Scene::textured_cube(textures))),
+        sampler: SamplerOptions {
+            filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..SamplerOptions::default()
+        },
+        ..Options::default()
+    };
+    check_golden("textured_faces_nearest_step_120", 120, &options);
+}
+
+#[test]
+fn yuv_nv12_cube_step_120() {
+    let frame = YuvFrame::test_pattern(YuvFormat::Nv12, 256, 256, YuvColorSpace::default());
+    let options = Options {
+        scene: Some(Arc::new(Scene::textured_cube(vec![frame.into()]))),
+        ..Options::default()
+    };
+    check_golden("yuv_nv12_cube_step_120", 120, &options);
+}
+
+/// The U and V samples of chroma block (x, y), clamped to the frame
+fn chroma_block(frame: &YuvFrame, x: i32, y: i32) -> [u8; 2] {
+    let (width, height) = YuvFormat::chroma_size(frame.width(), frame.height());
+    let x = x.clamp(0, width as i32 - 1) as usize;
+    let y = y.clamp(0, height as i32 - 1) as usize;
+    let index = y * width as usize + x;
+    let chroma = frame.chroma();
+    match frame.format() {
+        YuvFormat::Nv12 => [chroma[index * 2], chroma[index * 2 + 1]],
+        YuvFormat::I420 => [chroma[index], chroma[chroma.len() / 2 + index]],
+    }
+}
+
+#[test]
+fn yuv_conversion_matches_reference() {
+    let Some(adapter) = fallback_adapter("yuv_conversion_matches_reference") else {
+        return;
+    };
+    let headless = pollster::block_on(Headless::from_adapter(
+        &adapter,
+        PhysicalSize::new(16, 16),
+        &Options::default(),
+    ))
+    .unwrap();
+    for format in [YuvFormat::Nv12, YuvFormat::I420] {
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709] {
+            for range in [YuvRange::Full, YuvRange::Limited] {
+                let color_space = YuvColorSpace { matrix, range };
+                let frame = YuvFrame::test_pattern(format, 64, 48, color_space);
+                let expected = frame.to_rgba();
+                let actual = headless.upload_texture(&frame.clone().into()).unwrap();
+                assert_eq!(actual.size, PhysicalSize::new(64, 48));
+
+                // The GPU interpolates chroma between samples, so pixels next to a change in
+                // chroma are skipped
+                let mut compared = 0;
+                for y in 0..frame.height() as i32 {
+                    for x in 0..frame.width() as i32 {
+                        let (chroma_x, chroma_y) = (x / 2, y / 2);
+                        let center = chroma_block(&frame, chroma_x, chroma_y);
+                        let uniform = (-1..=1).all(|dy| {
+                            (-1..=1).all(|dx| {
+                                chroma_block(&frame, chroma_x + dx, chroma_y + dy) == center
+                            })
+                        });
+                        if !uniform {
+                            continue;
+                        }
+                        let index = (y * frame.width() as i32 + x) as usize * 4;
+                        let pixel = |pixels: &[u8]| pixels[index..index + 4].to_vec();
+                        let matches = pixel(&expected)
+                            .iter()
+                            .zip(pixel(&actual.pixels))
+                            .all(|(e, a)| e.abs_diff(a) <= 2);
+                        assert!(
+                            matches,
+                            "{format:?} {color_space:?} at ({x}, {y}): expected {:?}, got {:?}",
+                            pixel(&expected),
+                            pixel(&actual.pixels)
+                        );
+                        compared += 1;
+                    }
+                }
+                assert!(compared > frame.width() * frame.height() / 2);
+            }
+        }
+    }
+}
+
+/// Stripes which move with `iTime` and `iFrame`, over a gradient across `iResolution`
+const SHADERTOY_GLSL: &str = "
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    vec2 uv = fragCoord / iResolution.xy;
+    float stripes = step(0.5, fract(uv.x * 4.0 + iTime * 0.125 + float(iFrame) / 480.0));
+    fragColor = vec4(uv, stripes, 1.0);
+}
+";
+
+#[test]
+fn shadertoy_cube_step_120() {
+    let options = Options {
+        shadertoy: Some(Arc::new(
+            Shadertoy::new(SHADERTOY_GLSL, ShaderLanguage::Glsl).unwrap(),
+        )),
+        ..Options::default()
+    };
+    check_golden("shadertoy_cube_step_120", 120, &options);
+}
+
+#[test]
+fn shadertoy_fullscreen_step_120() {
+    let wgsl = "
+fn mainImage(fragCoord: vec2<f32>) -> vec4<f32> {
+    let centered = (fragCoord - 0.5 * iResolution.xy) / iResolution.y;
+    let rings = 0.5 + 0.5 * sin(40.0 * length(centered) - iTime * 4.0);
+    // The top left corner is white, checking that the origin is at the bottom left
+    let corner = step(0.9, fragCoord.y / iResolution.y) * step(fragCoord.x / iResolution.x, 0.1);
+    return mix(vec4<f32>(rings, 0.3 * rings, 1.0 - rings, 1.0), vec4<f32>(1.0), corner);
+}
+";
+    let options = Options {
+        shadertoy: Some(Arc::new(
+            Shadertoy::new(wgsl, ShaderLanguage::Wgsl).unwrap(),
+        )),
+        shadertoy_target: ShadertoyTarget::Fullscreen,
+        ..Options::default()
+    };
+    check_golden("shadertoy_fullscreen_step_120", 120, &options);
+}
+
+#[test]
+fn shadertoy_inputs_reach_the_shader() {
+    let Some(adapter) = fallback_adapter("shadertoy_inputs_reach_the_shader") else {
+        return;
+    };
+    // Red on odd frames, green within a few pixels of the mouse, and blue while it's pressed
+    let glsl = "
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    float odd = mod(float(iFrame), 2.0);
+    float near = step(distance(fragCoord, iMouse.xy), 4.0);
+    float pressed = step(0.0, iMouse.z);
+    fragColor = vec4(odd, near, pressed, 1.0);
+}
+";
+    let options = Options {
+        shadertoy: Some(Arc::new(
+            Shadertoy::new(glsl, ShaderLanguage::Glsl).unwrap(),
+        )),
+        shadertoy_target: ShadertoyTarget::Fullscreen,
+        ..Options::default()
+    };
+    let size = PhysicalSize::new(64, 64);
+    let mut headless =
+        pollster::block_on(Headless::from_adapter(&adapter, size, &options)).unwrap();
+    let pixel = |frame: &Frame, x: u32, y: u32| {
+        let start = ((y * frame.size.width + x) * 4) as usize;
+        [0, 1, 2].map(|channel| frame.pixels[start + channel])
+    };
+
+    // Window coordinates are measured from the top left, like the rendered frame's rows
+    let mut mouse = ShadertoyMouse::default();
+    mouse.cursor_moved(20.0, 30.0, size.height);
+    mouse.button(true);
+    headless.set_mouse(mouse);
+    let even = headless.render(120);
+    assert_eq!(pixel(&even, 20, 30), [0, 255, 255]);
+    assert_eq!(pixel(&even, 50, 10), [0, 0, 255]);
+    let odd = headless.render(121);
+    assert_eq!(pixel(&odd, 20, 30), [255, 255, 255]);
+    assert_eq!(pixel(&odd, 50, 10), [255, 0, 255]);
+
+    // Moving without the button pressed leaves iMouse.xy where the button was last held
+    mouse.button(false);
+    mouse.cursor_moved(50.0, 10.0, size.height);
+    headless.set_mouse(mouse);
+    let released = headless.render(121);
+    assert_eq!(pixel(&released, 20, 30), [255, 255, 0]);
+    assert_eq!(pixel(&released, 50, 10), [255, 0, 0]);
+}
+
+#[test]
+fn headless_rejects_targets_larger_than_the_device_limit() {
+    let Some(adapter) = fallback_adapter("headless_rejects_targets_larger_than_the_device_limit")
+    else {
+        return;
+    };
+    let max = adapter.limits().max_texture_dimension_2d;
+    let size = PhysicalSize::new(16, max + 1);
+    match pollster::block_on(Headless::from_adapter(&adapter, size, &Options::default())) {
+        Err(Error::TargetTooLarge {
+            size: actual,
+            max: actual_max,
+        }) => {
+            assert_eq!(actual, size);
+            assert!(actual_max <= max);
+        }
+        other => panic!("Expected TargetTooLarge, got {other:?}"),
+    }
+}